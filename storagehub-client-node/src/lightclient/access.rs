@@ -0,0 +1,68 @@
+use libp2p::PeerId;
+use subxt::{OnlineClient, PolkadotConfig};
+
+use crate::options::LightClientOptions;
+
+use super::{
+	client::Client,
+	errors::StorageHubError,
+	node_runtime::{self, runtime_types::bounded_collections::bounded_vec::BoundedVec},
+};
+
+/// Checks on-chain whether a peer can read a file, before serving it.
+pub(crate) struct FileAccess {
+	api: OnlineClient<PolkadotConfig>,
+}
+
+impl FileAccess {
+	/// Connect to the chain selected in the light client options.
+	pub(crate) async fn new(config: &LightClientOptions) -> Result<Self, StorageHubError> {
+		let ws_address = config.ws_address.clone().unwrap_or(config.chain.bootnode());
+
+		let rpc_client = Client::create_rpc_client(ws_address).await?;
+		let api = Client::create_online_client_from_rpc(rpc_client).await?;
+
+		Ok(FileAccess { api })
+	}
+
+	/// Whether `peer` can read the file with id `file_id`.
+	///
+	/// Public files can be read by anyone. Private files can only be read by the peers
+	/// registered on-chain by their owner, their MSP, their BSPs or the accounts in their
+	/// allow-list.
+	/// Files that are not registered on-chain are never served.
+	pub(crate) async fn is_allowed(
+		&self,
+		file_id: &str,
+		peer: &PeerId,
+	) -> Result<bool, StorageHubError> {
+		let location = BoundedVec(file_id.as_bytes().to_vec());
+		let file_system = node_runtime::storage().pallet_file_system();
+		let storage = self.api.storage().at_latest().await?;
+
+		// The file is either stored or still requested.
+		let file = match storage.fetch(&file_system.files_mapping(location.clone())).await? {
+			Some(file) => file,
+			None => match storage.fetch(&file_system.storage_requests(location.clone())).await? {
+				Some(file) => file,
+				None => return Ok(false),
+			},
+		};
+
+		if file.is_public {
+			return Ok(true)
+		}
+
+		// Find out which account is requesting the file.
+		let Some(account) =
+			storage.fetch(&file_system.peer_accounts(BoundedVec(peer.to_bytes()))).await?
+		else {
+			return Ok(false)
+		};
+
+		Ok(file.owner == account ||
+			file.bsps.0.contains(&account) ||
+			file.msp.as_ref() == Some(&account) ||
+			storage.fetch(&file_system.file_readers(location, account)).await?.is_some())
+	}
+}
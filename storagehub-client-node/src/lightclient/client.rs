@@ -1,8 +1,10 @@
-use std::{collections::HashMap, fs, path::Path, thread, time};
+use std::{collections::HashMap, fs, path::Path, str::FromStr, thread, time};
 
 use clap::ValueEnum;
 use sp_core::crypto;
-use subxt::{backend::rpc::RpcClient, rpc_params, Error, OnlineClient, PolkadotConfig};
+use subxt::{
+	backend::rpc::RpcClient, rpc_params, utils::AccountId32, Error, OnlineClient, PolkadotConfig,
+};
 use tracing::{error, info, warn};
 
 use crate::{lightclient::support::ChainPrefix, options, p2p, Role};
@@ -25,6 +27,8 @@ pub(crate) enum DevAccounts {
 pub(crate) struct Client {
 	/// The account used to sign transactions.
 	pub(crate) account: DevAccounts,
+	/// The account on whose behalf transactions are submitted, through a proxy, if any.
+	pub(crate) proxied_account: Option<AccountId32>,
 	/// The runtime to use.
 	pub(crate) runtime: SupportedRuntime,
 	/// The RPC client.
@@ -45,9 +49,8 @@ impl Client {
 	) {
 		match run_as {
 			Role::User => Self::_run_as_user().await,
-			Role::BspProvider =>
-				Self::run_as_bsp_provider(config, command_sender, download_path).await,
-			Role::MspProvider => Self::_run_as_msp_provider().await,
+			Role::BspProvider | Role::MspProvider =>
+				Self::run_as_provider(run_as, config, command_sender, download_path).await,
 		}
 	}
 
@@ -102,9 +105,17 @@ impl Client {
 			fs::create_dir_all(&download_path).expect("Failed to create directory");
 		}
 
+		let proxied_account = config.proxied_account.as_ref().map(|address| {
+			AccountId32::from_str(address).expect("Failed to parse proxied account address")
+		});
+		if let Some(proxied_account) = &proxied_account {
+			info!("Operating on behalf of {} through a StorageProvider proxy", proxied_account);
+		}
+
 		Client {
 			download_path,
 			account: config.dev_account.clone(),
+			proxied_account,
 			runtime: config.chain,
 			rpc_client,
 			command_sender,
@@ -113,7 +124,8 @@ impl Client {
 
 	async fn _run_as_user() {}
 
-	async fn run_as_bsp_provider(
+	async fn run_as_provider(
+		run_as: Role,
 		config: options::LightClientOptions,
 		command_sender: p2p::service::CommandSender,
 		download_path: String,
@@ -122,7 +134,7 @@ impl Client {
 		loop {
 			let mut client =
 				Client::new(&config, command_sender.clone(), download_path.clone()).await;
-			if let Err(e) = client.run_and_subscribe_to_events().await {
+			if let Err(e) = client.run_and_subscribe_to_events(&run_as).await {
 				match e {
 					StorageHubError::SubscriptionFinished => warn!("{}", e),
 					_ => {
@@ -139,13 +151,11 @@ impl Client {
 		}
 	}
 
-	async fn _run_as_msp_provider() {
-		unimplemented!("MSP provider execution not implemented.")
-	}
-
-	async fn run_and_subscribe_to_events(&mut self) -> Result<(), StorageHubError> {
-		match self.runtime {
-			SupportedRuntime::Local | SupportedRuntime::Compose => local::run(self).await,
+	async fn run_and_subscribe_to_events(&mut self, run_as: &Role) -> Result<(), StorageHubError> {
+		match (self.runtime, run_as) {
+			(SupportedRuntime::Local | SupportedRuntime::Compose, Role::MspProvider) =>
+				local::run_as_msp(self).await,
+			(SupportedRuntime::Local | SupportedRuntime::Compose, _) => local::run(self).await,
 		}
 	}
 
@@ -1,31 +1,55 @@
-#[subxt::subxt(
-	runtime_metadata_path = "metadata/local.scale",
-	derive_for_all_types = "Clone, PartialEq"
-)]
-mod node_runtime {}
-
+use super::node_runtime::{
+	pallet_file_system::events::{
+		FileUpdateRequested, FileUpdated, NewStorageRequest, NewStorageRequestBatch,
+	},
+	proxy::events::ProxyExecuted,
+	runtime_types::{
+		bounded_collections::bounded_vec::BoundedVec,
+		node_template_runtime::{ProxyType, RuntimeCall},
+		pallet_file_system::pallet::Call as FileSystemCall,
+		sp_core::ed25519::Signature,
+	},
+};
 use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
-use node_runtime::pallet_file_system::events::NewStorageRequest;
-use std::{fs, str::FromStr, thread, time};
+use std::{fs, path::Path, str::FromStr, thread, time};
 use subxt::{
-	ext::sp_core::{sr25519::Pair, Pair as PairT},
-	tx::PairSigner,
-	utils::AccountId32,
+	ext::{
+		codec::Encode,
+		sp_core::{sr25519::Pair, Pair as PairT},
+	},
+	tx::{PairSigner, TxPayload},
+	utils::{AccountId32, MultiAddress, H256},
+	OnlineClient, PolkadotConfig,
 };
 use tokio::sync::oneshot;
 use tracing::{debug, error, info};
 
-use crate::{lightclient::client::DevAccounts, p2p};
+use crate::{
+	lightclient::client::DevAccounts,
+	p2p::{self, download},
+};
+
+use super::{client::Client, errors::StorageHubError, node_runtime};
+
+/// Prefix of the payload signed by the key of a PeerId to register it, as in the runtime.
+const PEER_ID_REGISTRATION_CONTEXT: &[u8] = b"storagehub:register_peer_id";
 
-use super::{client::Client, errors::StorageHubError};
+/// A requested file: its location, fingerprint and size.
+type StorageRequest = (BoundedVec<u8>, H256, u128);
 
 pub(crate) async fn run(storage_hub: &mut Client) -> Result<(), StorageHubError> {
-	info!("Subscribe 'NewStorageRequest' on-chain finalized event");
+	info!(
+		"Subscribe 'NewStorageRequest', 'NewStorageRequestBatch', 'FileUpdateRequested' and \
+		 'FileUpdated' on-chain finalized events"
+	);
 
 	let api = Client::create_online_client_from_rpc(storage_hub.rpc_client.clone())
 		.await
 		.expect("Failed to create online client from rpc client");
 
+	register_peer_id(storage_hub, &api).await?;
+	resume_downloads(storage_hub).await;
+
 	let mut block_sub = api.blocks().subscribe_finalized().await?;
 
 	while let Some(block) = block_sub.next().await {
@@ -38,127 +62,595 @@ pub(crate) async fn run(storage_hub: &mut Client) -> Result<(), StorageHubError>
 		if let Some(event) = events.find_first::<NewStorageRequest>()? {
 			debug!("Received event storage::NewStorageRequest: {:?}", event);
 
-			let account_id: AccountId32 = AccountId32::from_str(&event.who.to_string())
-				.expect("Failed to convert `who` to AccountId32");
+			handle_storage_requests(
+				storage_hub,
+				&api,
+				event.who,
+				event.sender_multiaddress,
+				vec![(event.location, event.fingerprint, event.size)],
+			)
+			.await?;
+		}
+
+		// Event --> storage::NewStorageRequestBatch
+		if let Some(event) = events.find_first::<NewStorageRequestBatch>()? {
+			debug!("Received event storage::NewStorageRequestBatch: {:?}", event);
 
-			let mut sender_multiaddr: Multiaddr = Multiaddr::from_str(
-				String::from_utf8(event.sender_multiaddress.0)
-					.expect("Failed to cast event address bytes to Multiaddr")
-					.as_str(),
+			handle_storage_requests(
+				storage_hub,
+				&api,
+				event.who,
+				event.sender_multiaddress,
+				event.requests.0,
 			)
-			.expect("Failed to cast string to Multiaddr");
-
-			let file_id: String = String::from_utf8(event.location.0.to_vec())
-				.expect("Failed to convert bounded vec to string for file_id");
-			let content_hash: String = event.fingerprint.to_string();
-			let size: String = event.size.to_string();
-
-			info!(
-                "Received NewStorageRequest event - account_id: {}, peer: {}, file_id: {}, content_hash: {}, size: {}",
-                account_id, sender_multiaddr, file_id, content_hash, size
-            );
-
-			let (sender, receiver) = oneshot::channel();
-
-			storage_hub
-				.command_sender
-				.send(p2p::commands::NetworkCommand::Multiaddresses { channel: sender })
-				.expect("Failed to send get multiaddresses command");
-			let multiaddresses = receiver.await.expect("Failed to receive multiaddresses");
-
-			// find first multiaddr that is not localhost
-			let multiaddr = multiaddresses
-				.iter()
-				.find(|multiaddr| {
-					multiaddr.iter().any(|protocol| match protocol {
-						Protocol::Ip4(ip) => ip != std::net::Ipv4Addr::new(127, 0, 0, 1),
-						_ => false,
-					})
-				})
-				.expect("Failed to find multiaddr that is not localhost");
-
-			let peer = node_runtime::runtime_types::bounded_collections::bounded_vec::BoundedVec(
-				multiaddr.as_ref().to_vec(),
-			);
+			.await?;
+		}
 
-			let volunteer_tx = node_runtime::tx().pallet_file_system().bsp_volunteer(
-				event.location,
-				event.fingerprint,
-				peer,
-			);
+		// Event --> storage::FileUpdateRequested
+		if let Some(event) = events.find_first::<FileUpdateRequested>()? {
+			debug!("Received event storage::FileUpdateRequested: {:?}", event);
 
-			let account = match storage_hub.account {
-				DevAccounts::Alice => "//Alice",
-				DevAccounts::Bob => "//Bob",
-				DevAccounts::Charlie => "//Charlie",
-				DevAccounts::Dave => "//Dave",
-			};
-			let owner: Pair =
-				Pair::from_string(account, None).expect("Failed to create pair from string");
+			handle_file_update(storage_hub, &api, event).await?;
+		}
 
-			let signer = PairSigner::new(owner);
-			let _ = api
+		// Event --> storage::FileUpdated
+		if let Some(event) = events.find_first::<FileUpdated>()? {
+			debug!("Received event storage::FileUpdated: {:?}", event);
+
+			release_previous_version(storage_hub, event);
+		}
+	}
+	// If subscription has closed for some reason await and subscribe again
+	Err(StorageHubError::SubscriptionFinished)
+}
+
+/// Run as a Main Storage Provider: accept the files users pick this node for and fetch them, so
+/// they can be retrieved from it.
+pub(crate) async fn run_as_msp(storage_hub: &mut Client) -> Result<(), StorageHubError> {
+	info!(
+		"Subscribe 'NewStorageRequest', 'NewStorageRequestBatch', 'FileUpdateRequested' and \
+		 'FileUpdated' on-chain finalized events as MSP"
+	);
+
+	let api = Client::create_online_client_from_rpc(storage_hub.rpc_client.clone())
+		.await
+		.expect("Failed to create online client from rpc client");
+
+	register_peer_id(storage_hub, &api).await?;
+
+	let mut block_sub = api.blocks().subscribe_finalized().await?;
+
+	while let Some(block) = block_sub.next().await {
+		let block = block?;
+		debug!("Received block: {}", block.hash());
+
+		let events = block.events().await?;
+
+		// Event --> storage::NewStorageRequest
+		if let Some(event) = events.find_first::<NewStorageRequest>()? {
+			debug!("Received event storage::NewStorageRequest: {:?}", event);
+
+			handle_msp_storage_requests(
+				storage_hub,
+				&api,
+				event.msp,
+				event.sender_multiaddress,
+				vec![(event.location, event.fingerprint, event.size)],
+			)
+			.await?;
+		}
+
+		// Event --> storage::NewStorageRequestBatch
+		if let Some(event) = events.find_first::<NewStorageRequestBatch>()? {
+			debug!("Received event storage::NewStorageRequestBatch: {:?}", event);
+
+			handle_msp_storage_requests(
+				storage_hub,
+				&api,
+				event.msp,
+				event.sender_multiaddress,
+				event.requests.0,
+			)
+			.await?;
+		}
+
+		// Event --> storage::FileUpdateRequested
+		if let Some(event) = events.find_first::<FileUpdateRequested>()? {
+			debug!("Received event storage::FileUpdateRequested: {:?}", event);
+
+			handle_msp_file_update(storage_hub, &api, event).await?;
+		}
+
+		// Event --> storage::FileUpdated
+		if let Some(event) = events.find_first::<FileUpdated>()? {
+			debug!("Received event storage::FileUpdated: {:?}", event);
+
+			release_previous_version(storage_hub, event);
+		}
+	}
+	// If subscription has closed for some reason await and subscribe again
+	Err(StorageHubError::SubscriptionFinished)
+}
+
+/// Volunteer to store the requested files and download them from their sender.
+///
+/// All the files are requested in a single pass, dialing the sender only once.
+async fn handle_storage_requests(
+	storage_hub: &mut Client,
+	api: &OnlineClient<PolkadotConfig>,
+	who: AccountId32,
+	sender_multiaddress: BoundedVec<u8>,
+	requests: Vec<StorageRequest>,
+) -> Result<(), StorageHubError> {
+	let account_id: AccountId32 =
+		AccountId32::from_str(&who.to_string()).expect("Failed to convert `who` to AccountId32");
+
+	// There is no point in volunteering for files that cannot be fetched.
+	let Some(sender_multiaddr) = parse_multiaddress(sender_multiaddress) else { return Ok(()) };
+	let peer = own_multiaddress(storage_hub).await;
+	let signer = signer(storage_hub);
+
+	// Volunteer for every file first, so the sender is only dialed if there is something to
+	// download.
+	let mut volunteered_files = Vec::new();
+	for (location, fingerprint, size) in requests {
+		let file_id: String = String::from_utf8(location.0.to_vec())
+			.expect("Failed to convert bounded vec to string for file_id");
+
+		info!(
+			"Received storage request - account_id: {}, peer: {}, file_id: {}, content_hash: {}, size: {}",
+			account_id, sender_multiaddr, file_id, fingerprint, size
+		);
+
+		let volunteer_call = RuntimeCall::PalletFileSystem(FileSystemCall::bsp_volunteer {
+			location: location.clone(),
+			fingerprint,
+			bsp_multiaddress: peer.clone(),
+		});
+		let volunteer_tx = node_runtime::tx().pallet_file_system().bsp_volunteer(
+			location,
+			fingerprint,
+			peer.clone(),
+		);
+
+		if submit_as_provider(storage_hub, api, &signer, volunteer_call, volunteer_tx).await? {
+			info!("Successfully volunteered for file_id: {}", file_id);
+			volunteered_files.push((file_id, fingerprint));
+		}
+	}
+
+	if volunteered_files.is_empty() {
+		return Ok(())
+	}
+
+	// Download the files to the specified location
+	let files = volunteered_files
+		.into_iter()
+		.map(|(file_id, fingerprint)| {
+			let file_path = format!("{}/{}", storage_hub.download_path, file_id);
+			(file_id, fingerprint, file_path)
+		})
+		.collect();
+	request_files(storage_hub, sender_multiaddr, files).await;
+
+	let wait: u64 = 3;
+	info!("Waiting {} seconds before run batch", wait);
+	thread::sleep(time::Duration::from_secs(wait));
+
+	Ok(())
+}
+
+/// Accept the requested files this node was picked as MSP for, and download them from their
+/// sender.
+///
+/// All the files are requested in a single pass, dialing the sender only once.
+async fn handle_msp_storage_requests(
+	storage_hub: &mut Client,
+	api: &OnlineClient<PolkadotConfig>,
+	msp: AccountId32,
+	sender_multiaddress: BoundedVec<u8>,
+	requests: Vec<StorageRequest>,
+) -> Result<(), StorageHubError> {
+	let signer = signer(storage_hub);
+
+	// Only the picked MSP has to store the files.
+	if msp != provider_account(storage_hub, &signer) {
+		return Ok(())
+	}
+
+	// There is no point in accepting files that cannot be fetched.
+	let Some(sender_multiaddr) = parse_multiaddress(sender_multiaddress) else { return Ok(()) };
+
+	let mut accepted_files = Vec::new();
+	for (location, fingerprint, size) in requests {
+		let file_id: String = String::from_utf8(location.0.to_vec())
+			.expect("Failed to convert bounded vec to string for file_id");
+
+		info!(
+			"Received storage request as MSP - peer: {}, file_id: {}, content_hash: {}, size: {}",
+			sender_multiaddr, file_id, fingerprint, size
+		);
+
+		let accept_call =
+			RuntimeCall::PalletFileSystem(FileSystemCall::msp_accept_storage_request {
+				location: location.clone(),
+			});
+		let accept_tx =
+			node_runtime::tx().pallet_file_system().msp_accept_storage_request(location);
+
+		if submit_as_provider(storage_hub, api, &signer, accept_call, accept_tx).await? {
+			info!("Successfully accepted file_id: {}", file_id);
+			accepted_files.push((file_id, fingerprint));
+		}
+	}
+
+	if accepted_files.is_empty() {
+		return Ok(())
+	}
+
+	let files = accepted_files
+		.into_iter()
+		.map(|(file_id, fingerprint)| {
+			let file_path = format!("{}/{}", storage_hub.download_path, file_id);
+			(file_id, fingerprint, file_path)
+		})
+		.collect();
+	request_files(storage_hub, sender_multiaddr, files).await;
+
+	Ok(())
+}
+
+/// Fetch the new version of a file this BSP stores and confirm it on-chain.
+///
+/// The new version is kept next to the current one until every BSP has confirmed it.
+async fn handle_file_update(
+	storage_hub: &mut Client,
+	api: &OnlineClient<PolkadotConfig>,
+	event: FileUpdateRequested,
+) -> Result<(), StorageHubError> {
+	let signer = signer(storage_hub);
+
+	// Only the BSPs storing the file have to fetch the new version.
+	if !event.bsps.0.contains(&provider_account(storage_hub, &signer)) {
+		return Ok(())
+	}
+
+	let Some(file_id) = download_new_version(storage_hub, &event).await else { return Ok(()) };
+
+	let confirm_call = RuntimeCall::PalletFileSystem(FileSystemCall::bsp_confirm_update {
+		location: event.location.clone(),
+		fingerprint: event.fingerprint,
+	});
+	let confirm_tx = node_runtime::tx()
+		.pallet_file_system()
+		.bsp_confirm_update(event.location, event.fingerprint);
+
+	if submit_as_provider(storage_hub, api, &signer, confirm_call, confirm_tx).await? {
+		info!("Successfully confirmed new version of file_id: {}", file_id);
+	}
+
+	Ok(())
+}
+
+/// Fetch the new version of a file this MSP stores, to keep serving its latest version.
+///
+/// The new version is kept next to the current one until every BSP has confirmed it.
+async fn handle_msp_file_update(
+	storage_hub: &mut Client,
+	api: &OnlineClient<PolkadotConfig>,
+	event: FileUpdateRequested,
+) -> Result<(), StorageHubError> {
+	let provider = provider_account(storage_hub, &signer(storage_hub));
+
+	// Only the MSP that accepted the file has to fetch the new version.
+	let file = api
+		.storage()
+		.at_latest()
+		.await?
+		.fetch(
+			&node_runtime::storage()
+				.pallet_file_system()
+				.files_mapping(event.location.clone()),
+		)
+		.await?;
+	if !file.is_some_and(|file| file.msp_accepted && file.msp == Some(provider)) {
+		return Ok(())
+	}
+
+	download_new_version(storage_hub, &event).await;
+
+	Ok(())
+}
+
+/// Download the new version of an updated file next to its current version.
+///
+/// Returns the file id if it was downloaded.
+async fn download_new_version(storage_hub: &Client, event: &FileUpdateRequested) -> Option<String> {
+	let file_id: String = String::from_utf8(event.location.0.to_vec())
+		.expect("Failed to convert bounded vec to string for file_id");
+	let sender_multiaddr = parse_multiaddress(event.sender_multiaddress.clone())?;
+
+	info!(
+		"Received file update - account_id: {}, peer: {}, file_id: {}, content_hash: {}, size: {}",
+		event.who, sender_multiaddr, file_id, event.fingerprint, event.size
+	);
+
+	// Keep the current version until the update is completed.
+	let file_path = pending_version_path(storage_hub, &file_id);
+	request_files(storage_hub, sender_multiaddr, vec![(file_id, event.fingerprint, file_path)])
+		.await
+		.pop()
+}
+
+/// Register the PeerId of this node on-chain, if not registered yet.
+///
+/// Users serving private files use it to know which provider is requesting them. The registration
+/// is signed with the key of the PeerId, for the runtime to check that this node controls it.
+async fn register_peer_id(
+	storage_hub: &Client,
+	api: &OnlineClient<PolkadotConfig>,
+) -> Result<(), StorageHubError> {
+	let peer_id = BoundedVec(local_peer_id(storage_hub).await.to_bytes());
+
+	let signer = signer(storage_hub);
+	let provider = provider_account(storage_hub, &signer);
+
+	let registered_account = api
+		.storage()
+		.at_latest()
+		.await?
+		.fetch(&node_runtime::storage().pallet_file_system().peer_accounts(peer_id.clone()))
+		.await?;
+	if registered_account == Some(provider.clone()) {
+		return Ok(())
+	}
+
+	let payload = (PEER_ID_REGISTRATION_CONTEXT, &provider, api.genesis_hash()).encode();
+	let signature = Signature(sign_with_peer_key(storage_hub, payload).await?);
+
+	let register_call = RuntimeCall::PalletFileSystem(FileSystemCall::register_peer_id {
+		peer_id: peer_id.clone(),
+		signature: signature.clone(),
+	});
+	let register_tx = node_runtime::tx().pallet_file_system().register_peer_id(peer_id, signature);
+
+	if submit_as_provider(storage_hub, api, &signer, register_call, register_tx).await? {
+		info!("Registered PeerId for account: {}", provider);
+	}
+
+	Ok(())
+}
+
+/// The account this node provides storage for: the proxied account if there is one, or the
+/// signer otherwise.
+fn provider_account(
+	storage_hub: &Client,
+	signer: &PairSigner<PolkadotConfig, Pair>,
+) -> AccountId32 {
+	storage_hub
+		.proxied_account
+		.clone()
+		.unwrap_or_else(|| signer.account_id().clone())
+}
+
+/// Replace the previous version of an updated file with the new one, if it was fetched.
+fn release_previous_version(storage_hub: &Client, event: FileUpdated) {
+	let file_id: String = String::from_utf8(event.location.0.to_vec())
+		.expect("Failed to convert bounded vec to string for file_id");
+
+	let pending_path = pending_version_path(storage_hub, &file_id);
+	if !Path::new(&pending_path).exists() {
+		return
+	}
+
+	let file_path = format!("{}/{}", storage_hub.download_path, file_id);
+	fs::rename(&pending_path, file_path).expect("Failed to replace file with its new version");
+
+	info!(
+		"Released previous version {} of file_id: {}, now at version {}",
+		event.previous_fingerprint, file_id, event.fingerprint
+	);
+}
+
+/// The path where the new version of a file is kept until the update is completed.
+fn pending_version_path(storage_hub: &Client, file_id: &str) -> String {
+	format!("{}/{}.pending", storage_hub.download_path, file_id)
+}
+
+/// Parse a multiaddress from an event, or `None` if it is not valid.
+fn parse_multiaddress(multiaddress: BoundedVec<u8>) -> Option<Multiaddr> {
+	let multiaddr = String::from_utf8(multiaddress.0)
+		.ok()
+		.and_then(|multiaddress| Multiaddr::from_str(&multiaddress).ok());
+
+	if multiaddr.is_none() {
+		error!("Failed to parse multiaddress from event");
+	}
+
+	multiaddr
+}
+
+/// The multiaddress this node is reachable at, to be submitted on-chain.
+///
+/// It is submitted in its text form and ends with the PeerId of this node, so other peers can
+/// dial it.
+async fn own_multiaddress(storage_hub: &Client) -> BoundedVec<u8> {
+	let (sender, receiver) = oneshot::channel();
+
+	storage_hub
+		.command_sender
+		.send(p2p::commands::NetworkCommand::Multiaddresses { channel: sender })
+		.expect("Failed to send get multiaddresses command");
+	let multiaddresses = receiver.await.expect("Failed to receive multiaddresses");
+
+	// find first multiaddr that is not localhost
+	let multiaddr = multiaddresses
+		.iter()
+		.find(|multiaddr| {
+			multiaddr.iter().any(|protocol| match protocol {
+				Protocol::Ip4(ip) => ip != std::net::Ipv4Addr::new(127, 0, 0, 1),
+				_ => false,
+			})
+		})
+		.expect("Failed to find multiaddr that is not localhost")
+		.clone()
+		.with(Protocol::P2p(local_peer_id(storage_hub).await));
+
+	BoundedVec(multiaddr.to_string().into_bytes())
+}
+
+/// The PeerId of this node.
+async fn local_peer_id(storage_hub: &Client) -> PeerId {
+	let (sender, receiver) = oneshot::channel();
+
+	storage_hub
+		.command_sender
+		.send(p2p::commands::NetworkCommand::LocalPeerId { channel: sender })
+		.expect("Failed to send get local peer id command");
+
+	receiver.await.expect("Failed to receive local peer id")
+}
+
+/// Sign `message` with the key the PeerId of this node is derived from.
+async fn sign_with_peer_key(
+	storage_hub: &Client,
+	message: Vec<u8>,
+) -> Result<[u8; 64], StorageHubError> {
+	let (sender, receiver) = oneshot::channel();
+
+	storage_hub
+		.command_sender
+		.send(p2p::commands::NetworkCommand::SignWithPeerKey { message, channel: sender })
+		.expect("Failed to send sign with peer key command");
+
+	let signature = receiver
+		.await
+		.expect("Failed to receive signature")
+		.map_err(|e| StorageHubError::Other(e.to_string()))?;
+	signature.try_into().map_err(|_| "PeerId key is not an ed25519 key".into())
+}
+
+/// The signer of the configured dev account.
+fn signer(storage_hub: &Client) -> PairSigner<PolkadotConfig, Pair> {
+	let account = match storage_hub.account {
+		DevAccounts::Alice => "//Alice",
+		DevAccounts::Bob => "//Bob",
+		DevAccounts::Charlie => "//Charlie",
+		DevAccounts::Dave => "//Dave",
+	};
+	let owner: Pair = Pair::from_string(account, None).expect("Failed to create pair from string");
+
+	PairSigner::new(owner)
+}
+
+/// Dial the sender once and download every file from it, to the path paired with its file id and
+/// fingerprint.
+///
+/// Returns the ids of the files that were downloaded.
+async fn request_files(
+	storage_hub: &Client,
+	sender_multiaddr: Multiaddr,
+	files: Vec<(String, H256, String)>,
+) -> Vec<String> {
+	dial(storage_hub, sender_multiaddr.clone()).await;
+
+	let mut downloaded_files = Vec::new();
+	for (file_id, fingerprint, file_path) in files {
+		match download::download_file(
+			&storage_hub.command_sender,
+			&sender_multiaddr,
+			&file_id,
+			fingerprint.0,
+			Path::new(&file_path),
+		)
+		.await
+		{
+			Ok(()) => {
+				info!("File {} downloaded from {} to: {}", file_id, sender_multiaddr, file_path);
+				downloaded_files.push(file_id);
+			},
+			Err(e) => {
+				error!("Failed to request file {}, keeping it to resume later: {}", file_id, e);
+			},
+		}
+	}
+
+	downloaded_files
+}
+
+/// Resume the downloads left unfinished when the client stopped.
+async fn resume_downloads(storage_hub: &Client) {
+	for (path, progress) in download::unfinished_downloads(Path::new(&storage_hub.download_path)) {
+		let Ok(sender_multiaddr) = Multiaddr::from_str(&progress.sender) else {
+			error!("Invalid sender multiaddress for file {}", progress.file_id);
+			continue
+		};
+		dial(storage_hub, sender_multiaddr.clone()).await;
+
+		match download::download_file(
+			&storage_hub.command_sender,
+			&sender_multiaddr,
+			&progress.file_id,
+			progress.fingerprint,
+			&path,
+		)
+		.await
+		{
+			Ok(()) => info!("File {} downloaded to: {}", progress.file_id, path.display()),
+			Err(e) => error!("Failed to resume the download of file {}: {}", progress.file_id, e),
+		}
+	}
+}
+
+/// Dial the sender of files.
+async fn dial(storage_hub: &Client, sender_multiaddr: Multiaddr) {
+	if let Err(e) = download::dial(&storage_hub.command_sender, sender_multiaddr).await {
+		error!("Failed to dial the sender of files: {}", e);
+	}
+}
+
+/// Submit a storage provider call, on behalf of the proxied account if there is one.
+///
+/// `call` is what gets submitted through the proxy, and `tx` is the same call submitted
+/// directly. Returns whether the call succeeded.
+async fn submit_as_provider(
+	storage_hub: &Client,
+	api: &OnlineClient<PolkadotConfig>,
+	signer: &PairSigner<PolkadotConfig, Pair>,
+	call: RuntimeCall,
+	tx: impl TxPayload,
+) -> Result<bool, StorageHubError> {
+	match storage_hub.proxied_account.clone() {
+		// Submit on behalf of the proxied account.
+		Some(real) => {
+			let proxy_tx = node_runtime::tx().proxy().proxy(
+				MultiAddress::Id(real),
+				Some(ProxyType::StorageProvider),
+				call,
+			);
+
+			let events = api
 				.tx()
-				.sign_and_submit_then_watch_default(&volunteer_tx, &signer)
+				.sign_and_submit_then_watch_default(&proxy_tx, signer)
 				.await?
 				.wait_for_finalized_success()
 				.await?;
 
-			info!("Successfully volunteered for file_id: {}", file_id);
-
-			let sender_peer_id: PeerId = match sender_multiaddr.pop().unwrap() {
-				Protocol::P2p(peer_id) => peer_id,
-				_ => {
-					eprintln!("Expected peer id in multiaddr");
-					continue;
-				},
-			};
-
-			let (sender, receiver) = oneshot::channel();
-
-			storage_hub
-				.command_sender
-				.send(p2p::commands::NetworkCommand::ExternalDial {
-					multiaddr: sender_multiaddr.clone(),
-					channel: sender,
-				})
-				.expect("Failed to send dial command");
-			let _ = receiver.await.expect("Failed to receive dial command");
-
-			let (sender, receiver) = oneshot::channel();
-
-			storage_hub
-				.command_sender
-				.send(p2p::commands::NetworkCommand::RequestFile {
-					file_id: file_id.clone(),
-					peer_id: sender_peer_id,
-					multiaddr: sender_multiaddr,
-					channel: sender,
-				})
-				.expect("Failed to send request file command");
-			let maybe_file = receiver.await.expect("Failed to receive file");
-
-			match maybe_file {
-				Ok(file) => {
-					info!("Received file from peer {:?}", sender_peer_id);
-
-					let file_path = format!("{}/{}", storage_hub.download_path, file_id);
-
-					// Download the file to the specified location
-					fs::write(&file_path, &file).expect("Failed to write file");
-
-					info!("File downloaded to: {}", file_path);
-
-					let wait: u64 = 3;
-					info!("Waiting {} seconds before run batch", wait);
-					thread::sleep(time::Duration::from_secs(wait));
-				},
-				Err(e) => {
-					error!("Failed to request file: {}", e);
-				},
+			// The proxy call succeeds even if the proxied call fails.
+			if let Some(ProxyExecuted { result: Err(e) }) = events.find_first::<ProxyExecuted>()? {
+				error!("Failed to submit through proxy: {:?}", e);
+				return Ok(false)
 			}
-		}
+		},
+		None => {
+			let _ = api
+				.tx()
+				.sign_and_submit_then_watch_default(&tx, signer)
+				.await?
+				.wait_for_finalized_success()
+				.await?;
+		},
 	}
-	// If subscription has closed for some reason await and subscribe again
-	Err(StorageHubError::SubscriptionFinished)
+
+	Ok(true)
 }
@@ -1,3 +1,10 @@
+#[subxt::subxt(
+	runtime_metadata_path = "metadata/local.scale",
+	derive_for_all_types = "Clone, PartialEq"
+)]
+pub(crate) mod node_runtime {}
+
+pub(crate) mod access;
 pub(crate) mod client;
 pub(crate) mod errors;
 pub(crate) mod local;
@@ -29,11 +29,19 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
 	let opts: Options = Options::parse();
 
-	let service = p2p::service::Service::new(
-		opts.run_as.clone(),
-		opts.libp2p_options.port,
-		opts.upload_path,
-	)?;
+	// MSPs are the main retrieval point of the files they store.
+	let serve_path = match opts.run_as {
+		Role::MspProvider => opts.download_path.clone(),
+		_ => opts.upload_path,
+	};
+	let mut service =
+		p2p::service::Service::new(opts.run_as.clone(), opts.libp2p_options.port, serve_path)?;
+
+	// Users and MSPs serve files, so they check on-chain who can read them.
+	if let Role::User | Role::MspProvider = opts.run_as {
+		let file_access = lightclient::access::FileAccess::new(&opts.light_client_options).await?;
+		service = service.with_file_access(file_access);
+	}
 
 	let sender = service.command_sender();
 
@@ -25,7 +25,7 @@ pub(crate) struct Options {
 	pub upload_path: String,
 	/// Path where files will be download to.
 	///
-	/// This is used by Msp and Bsp providers.
+	/// This is used by Msp and Bsp providers. Msp providers also serve files from it.
 	#[arg(help = "Path where files will be download to")]
 	#[arg(long, default_value = "./")]
 	pub download_path: String,
@@ -50,6 +50,13 @@ pub struct LightClientOptions {
 	#[arg(help = "Dev account to sign transactions with")]
 	#[arg(long, default_value = "alice", value_enum)]
 	pub dev_account: DevAccounts,
+	/// Account to operate on behalf of, as an SS58 address.
+	///
+	/// When set, the dev account acts as a hot key for this account: it must be registered as
+	/// its `StorageProvider` proxy, and provider transactions are submitted through `proxy(...)`.
+	#[arg(help = "Account to operate on behalf of, through a StorageProvider proxy")]
+	#[arg(long)]
+	pub proxied_account: Option<String>,
 	/// Websocket address to connect to.
 	///
 	/// This will override the default ws address selected based on the chain.
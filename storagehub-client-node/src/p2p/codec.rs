@@ -0,0 +1,140 @@
+//! Codec of the file transfer protocol spoken by StorageHub nodes running an embedded BSP.
+//!
+//! Substrate request-response protocols prefix requests and responses with their length, as an
+//! unsigned varint. Requests are the offset and length of the requested range followed by the file
+//! id, and responses the size of the file followed by the range, integers as little endian `u64`.
+
+use async_trait::async_trait;
+use libp2p::{
+	futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+	request_response::Codec,
+	StreamProtocol,
+};
+use std::io;
+
+use super::service::{FileRequest, FileResponse, FILE_CHUNK_SIZE};
+
+/// Size of each integer leading requests and responses.
+const HEADER_SIZE: usize = std::mem::size_of::<u64>();
+/// Largest request accepted, with a file id of up to 1024 bytes.
+const MAX_REQUEST_SIZE: usize = 2 * HEADER_SIZE + 1024;
+/// Largest response accepted, a whole chunk.
+const MAX_RESPONSE_SIZE: usize = HEADER_SIZE + FILE_CHUNK_SIZE as usize;
+
+/// Length-prefixed codec of the node file transfer protocol.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NodeFileCodec;
+
+#[async_trait]
+impl Codec for NodeFileCodec {
+	type Protocol = StreamProtocol;
+	type Request = FileRequest;
+	type Response = FileResponse;
+
+	async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<FileRequest>
+	where
+		T: AsyncRead + Unpin + Send,
+	{
+		let request = read_length_prefixed(io, MAX_REQUEST_SIZE).await?;
+		let (offset, rest) = split_header(request)?;
+		let (length, file_id) = split_header(rest)?;
+		let file_id = String::from_utf8(file_id)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+		Ok(FileRequest { file_id, offset, length })
+	}
+
+	async fn read_response<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<FileResponse>
+	where
+		T: AsyncRead + Unpin + Send,
+	{
+		let response = read_length_prefixed(io, MAX_RESPONSE_SIZE).await?;
+		let (file_size, chunk) = split_header(response)?;
+
+		Ok(FileResponse { file_size, chunk })
+	}
+
+	async fn write_request<T>(
+		&mut self,
+		_: &StreamProtocol,
+		io: &mut T,
+		FileRequest { file_id, offset, length }: FileRequest,
+	) -> io::Result<()>
+	where
+		T: AsyncWrite + Unpin + Send,
+	{
+		let request = [&offset.to_le_bytes()[..], &length.to_le_bytes(), file_id.as_bytes()];
+		write_length_prefixed(io, &request.concat()).await
+	}
+
+	async fn write_response<T>(
+		&mut self,
+		_: &StreamProtocol,
+		io: &mut T,
+		FileResponse { file_size, chunk }: FileResponse,
+	) -> io::Result<()>
+	where
+		T: AsyncWrite + Unpin + Send,
+	{
+		write_length_prefixed(io, &[&file_size.to_le_bytes()[..], &chunk].concat()).await
+	}
+}
+
+/// Split a message into its leading integer and the rest of it.
+fn split_header(mut message: Vec<u8>) -> io::Result<(u64, Vec<u8>)> {
+	if message.len() < HEADER_SIZE {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "Message too short"))
+	}
+
+	let rest = message.split_off(HEADER_SIZE);
+	let header =
+		u64::from_le_bytes(message.try_into().expect("Message is split at the header size"));
+
+	Ok((header, rest))
+}
+
+/// Read a message prefixed with its length, failing if it is longer than `max_size`.
+async fn read_length_prefixed<T>(io: &mut T, max_size: usize) -> io::Result<Vec<u8>>
+where
+	T: AsyncRead + Unpin + Send,
+{
+	let mut length: usize = 0;
+	for shift in (0..usize::BITS).step_by(7) {
+		let mut byte = [0u8];
+		io.read_exact(&mut byte).await?;
+		length |= usize::from(byte[0] & 0x7f) << shift;
+		if byte[0] & 0x80 == 0 {
+			break
+		}
+	}
+	if length > max_size {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "Message too large"))
+	}
+
+	let mut message = vec![0; length];
+	io.read_exact(&mut message).await?;
+
+	Ok(message)
+}
+
+/// Write `message` prefixed with its length, and close the stream.
+async fn write_length_prefixed<T>(io: &mut T, message: &[u8]) -> io::Result<()>
+where
+	T: AsyncWrite + Unpin + Send,
+{
+	let mut length = message.len();
+	let mut prefix = Vec::new();
+	loop {
+		let byte = (length & 0x7f) as u8;
+		length >>= 7;
+		if length == 0 {
+			prefix.push(byte);
+			break
+		}
+		prefix.push(byte | 0x80);
+	}
+
+	io.write_all(&prefix).await?;
+	io.write_all(message).await?;
+	io.close().await
+}
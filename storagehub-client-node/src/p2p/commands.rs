@@ -1,8 +1,8 @@
 use anyhow::Error;
-use libp2p::{Multiaddr, PeerId};
+use libp2p::{request_response::ResponseChannel, Multiaddr, PeerId};
 use tokio::sync::{mpsc::UnboundedSender, oneshot};
 
-use super::service::{FileRequest, Service};
+use super::service::{FileRequest, FileResponse, Service};
 
 /// Network commands that can be sent to the service asynchrously through an mpsc channel.
 #[derive(Debug)]
@@ -11,13 +11,19 @@ pub enum NetworkCommand {
 	ExternalDial { multiaddr: Multiaddr, channel: oneshot::Sender<Result<(), Error>> },
 	/// Get the current list of multiaddresses we are listening on.
 	Multiaddresses { channel: oneshot::Sender<Vec<Multiaddr>> },
-	/// Request a file from a peer.
+	/// Get our own PeerId.
+	LocalPeerId { channel: oneshot::Sender<PeerId> },
+	/// Sign a message with the key our PeerId is derived from.
+	SignWithPeerKey { message: Vec<u8>, channel: oneshot::Sender<Result<Vec<u8>, Error>> },
+	/// Request a byte range of a file from a peer.
 	RequestFile {
-		file_id: String,
+		request: FileRequest,
 		peer_id: PeerId,
 		multiaddr: Multiaddr,
-		channel: oneshot::Sender<Result<Vec<u8>, Error>>,
+		channel: oneshot::Sender<Result<FileResponse, Error>>,
 	},
+	/// Send a requested byte range of a file to a peer, once it is allowed to read the file.
+	SendFile { peer: PeerId, request: FileRequest, channel: ResponseChannel<FileResponse> },
 }
 
 impl Service {
@@ -37,23 +43,35 @@ impl Service {
 					.map_err(|_| anyhow::anyhow!("Failed to send dial command"))?;
 			},
 			NetworkCommand::Multiaddresses { channel } => {
-				let multiaddresses: Vec<Multiaddr> =
-					self.swarm.listeners().map(|addr| addr.clone()).collect();
+				let multiaddresses: Vec<Multiaddr> = self.swarm.listeners().cloned().collect();
 
 				channel
 					.send(multiaddresses)
 					.map_err(|_| anyhow::anyhow!("Failed to send multiaddresses"))?;
 			},
-			NetworkCommand::RequestFile { file_id, peer_id, multiaddr, channel } => {
+			NetworkCommand::LocalPeerId { channel } => {
+				channel
+					.send(*self.swarm.local_peer_id())
+					.map_err(|_| anyhow::anyhow!("Failed to send local peer id"))?;
+			},
+			NetworkCommand::SignWithPeerKey { message, channel } => {
+				let signature = self.keypair.sign(&message).map_err(Error::from);
+
+				channel
+					.send(signature)
+					.map_err(|_| anyhow::anyhow!("Failed to send signature"))?;
+			},
+			NetworkCommand::RequestFile { request, peer_id, multiaddr, channel } => {
 				let swarm = self.swarm.behaviour_mut();
 
 				// TODO remove this and add address from the `IdentifyEvent::Received` event.
 				swarm.request_response.add_address(&peer_id, multiaddr);
-				let request_id =
-					swarm.request_response.send_request(&peer_id, FileRequest(file_id));
+				let request_id = swarm.request_response.send_request(&peer_id, request);
 
 				self.pending_responses.insert(request_id, channel);
 			},
+			NetworkCommand::SendFile { peer, request, channel } =>
+				self.send_file(peer, request, channel),
 		}
 		Ok(())
 	}
@@ -0,0 +1,262 @@
+//! Resumable downloads of files from their sender.
+//!
+//! Files are requested one byte range of [`FILE_CHUNK_SIZE`] bytes at a time and written to a
+//! partial file next to their destination. The hashes of the chunks written so far are kept in a
+//! progress file, so that a download resumes from the last verified chunk when the connection drops
+//! or the client restarts.
+
+use anyhow::{anyhow, Result};
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use std::{
+	ffi::OsString,
+	fs::{self, File, OpenOptions},
+	io::{Read, Seek, SeekFrom, Write},
+	path::{Path, PathBuf},
+};
+use subxt::ext::{
+	codec::{Decode, Encode},
+	sp_core::blake2_256,
+};
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn};
+
+use super::{
+	commands::NetworkCommand,
+	service::{CommandSender, FileRequest, FileResponse, FILE_CHUNK_SIZE},
+};
+
+/// How many times in a row a range is requested before giving up on the download.
+const MAX_ATTEMPTS: u32 = 3;
+/// Extension appended to the destination of a file while it is downloaded.
+const PARTIAL_EXTENSION: &str = "part";
+/// Extension appended to the destination of a file for the progress of its download.
+const PROGRESS_EXTENSION: &str = "progress";
+
+/// Progress of a download, kept next to the partial file.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+#[codec(crate = subxt::ext::codec)]
+pub(crate) struct DownloadProgress {
+	pub(crate) file_id: String,
+	/// Multiaddress of the sender, ending with its PeerId.
+	pub(crate) sender: String,
+	pub(crate) fingerprint: [u8; 32],
+	/// Size of the file, once the sender sent it.
+	pub(crate) file_size: Option<u64>,
+	/// Hashes of the chunks written to the partial file, each of [`FILE_CHUNK_SIZE`] bytes but the
+	/// last one.
+	pub(crate) chunk_hashes: Vec<[u8; 32]>,
+}
+
+/// Dial `sender`, so that files can be requested from it.
+pub(crate) async fn dial(command_sender: &CommandSender, sender: Multiaddr) -> Result<()> {
+	let (channel, receiver) = oneshot::channel();
+
+	command_sender
+		.send(NetworkCommand::ExternalDial { multiaddr: sender, channel })
+		.map_err(|_| anyhow!("Failed to send dial command"))?;
+
+	receiver.await?
+}
+
+/// Download the file `file_id` with `fingerprint` from `sender` to `path`.
+///
+/// A previous download of the same file to `path` is resumed from its last verified chunk. If the
+/// download fails, the partial file is kept to be resumed later, unless the downloaded file does
+/// not match `fingerprint`.
+pub(crate) async fn download_file(
+	command_sender: &CommandSender,
+	sender: &Multiaddr,
+	file_id: &str,
+	fingerprint: [u8; 32],
+	path: &Path,
+) -> Result<()> {
+	let mut multiaddr = sender.clone();
+	let Some(Protocol::P2p(peer_id)) = multiaddr.pop() else {
+		return Err(anyhow!("Expected peer id in multiaddr"))
+	};
+
+	let mut progress = match resumable_progress(path, file_id, fingerprint) {
+		Some(progress) => {
+			info!(
+				"Resuming download of file {} from chunk {}",
+				file_id,
+				progress.chunk_hashes.len()
+			);
+			progress
+		},
+		None => DownloadProgress {
+			file_id: file_id.to_string(),
+			sender: String::new(),
+			fingerprint,
+			file_size: None,
+			chunk_hashes: Vec::new(),
+		},
+	};
+	progress.sender = sender.to_string();
+
+	// Drop whatever was written after the last verified chunk.
+	let mut offset = progress.chunk_hashes.len() as u64 * FILE_CHUNK_SIZE;
+	let mut file = OpenOptions::new()
+		.create(true)
+		.write(true)
+		.truncate(false)
+		.open(with_extension(path, PARTIAL_EXTENSION))?;
+	file.set_len(offset)?;
+	file.seek(SeekFrom::Start(offset))?;
+
+	let mut failures = 0;
+	while offset < progress.file_size.unwrap_or(u64::MAX) {
+		let response = match request_range(command_sender, peer_id, &multiaddr, file_id, offset)
+			.await
+		{
+			Ok(response) => response,
+			Err(e) if failures + 1 < MAX_ATTEMPTS => {
+				failures += 1;
+				warn!("Failed to request file {} at offset {}, retrying: {}", file_id, offset, e);
+				// Reconnect, in case the connection dropped.
+				let _ = dial(command_sender, sender.clone()).await;
+				continue
+			},
+			Err(e) => return Err(e),
+		};
+		failures = 0;
+
+		if progress.file_size.is_some_and(|file_size| file_size != response.file_size) {
+			discard_download(path);
+			return Err(anyhow!("File {} changed while being downloaded", file_id))
+		}
+		let expected_length = response.file_size.saturating_sub(offset).min(FILE_CHUNK_SIZE);
+		if response.chunk.len() as u64 != expected_length {
+			return Err(anyhow!(
+				"Received {} bytes at offset {} instead of {}",
+				response.chunk.len(),
+				offset,
+				expected_length
+			))
+		}
+
+		// Only record the chunk once it is on disk.
+		file.write_all(&response.chunk)?;
+		file.sync_data()?;
+		if !response.chunk.is_empty() {
+			progress.chunk_hashes.push(blake2_256(&response.chunk));
+		}
+		progress.file_size = Some(response.file_size);
+		fs::write(with_extension(path, PROGRESS_EXTENSION), progress.encode())?;
+
+		offset += response.chunk.len() as u64;
+		debug!("Received {} of {} bytes of file {}", offset, response.file_size, file_id);
+	}
+
+	// Only keep the file if it is the one that was requested.
+	if file_fingerprint(&with_extension(path, PARTIAL_EXTENSION))? != fingerprint {
+		discard_download(path);
+		return Err(anyhow!("File {} does not match its fingerprint", file_id))
+	}
+
+	fs::rename(with_extension(path, PARTIAL_EXTENSION), path)?;
+	let _ = fs::remove_file(with_extension(path, PROGRESS_EXTENSION));
+
+	Ok(())
+}
+
+/// The downloads left unfinished in `directory`, along with the destination of their file.
+pub(crate) fn unfinished_downloads(directory: &Path) -> Vec<(PathBuf, DownloadProgress)> {
+	let Ok(entries) = fs::read_dir(directory) else { return Vec::new() };
+
+	entries
+		.filter_map(|entry| {
+			let progress_path = entry.ok()?.path();
+			if progress_path.extension()? != PROGRESS_EXTENSION {
+				return None
+			}
+			let progress =
+				DownloadProgress::decode(&mut &fs::read(&progress_path).ok()?[..]).ok()?;
+
+			Some((progress_path.with_extension(""), progress))
+		})
+		.collect()
+}
+
+/// The progress of the download of the file `file_id` with `fingerprint` to `path`, if it can be
+/// resumed.
+///
+/// Only the chunks of the partial file that still match their hash are kept.
+pub(crate) fn resumable_progress(
+	path: &Path,
+	file_id: &str,
+	fingerprint: [u8; 32],
+) -> Option<DownloadProgress> {
+	let progress = fs::read(with_extension(path, PROGRESS_EXTENSION)).ok()?;
+	let mut progress = DownloadProgress::decode(&mut &progress[..]).ok()?;
+	if progress.file_id != file_id || progress.fingerprint != fingerprint {
+		return None
+	}
+
+	let mut file = File::open(with_extension(path, PARTIAL_EXTENSION)).ok()?;
+	let verified_chunks = progress
+		.chunk_hashes
+		.iter()
+		.take_while(|hash| {
+			let mut chunk = Vec::new();
+			(&mut file).take(FILE_CHUNK_SIZE).read_to_end(&mut chunk).is_ok() &&
+				!chunk.is_empty() &&
+				blake2_256(&chunk) == **hash
+		})
+		.count();
+	progress.chunk_hashes.truncate(verified_chunks);
+
+	Some(progress)
+}
+
+/// The fingerprint of the file at `path`, its blake2-256 hash, read one chunk at a time.
+pub(crate) fn file_fingerprint(path: &Path) -> Result<[u8; 32]> {
+	let mut file = File::open(path)?;
+	let mut hasher = Blake2b::<U32>::new();
+	let mut chunk = vec![0; FILE_CHUNK_SIZE as usize];
+	loop {
+		let read = file.read(&mut chunk)?;
+		if read == 0 {
+			return Ok(hasher.finalize().into())
+		}
+		hasher.update(&chunk[..read]);
+	}
+}
+
+/// Remove the partial file and the progress of the download to `path`.
+fn discard_download(path: &Path) {
+	let _ = fs::remove_file(with_extension(path, PARTIAL_EXTENSION));
+	let _ = fs::remove_file(with_extension(path, PROGRESS_EXTENSION));
+}
+
+/// Request the range of the file `file_id` starting at `offset`.
+async fn request_range(
+	command_sender: &CommandSender,
+	peer_id: PeerId,
+	multiaddr: &Multiaddr,
+	file_id: &str,
+	offset: u64,
+) -> Result<FileResponse> {
+	let (channel, receiver) = oneshot::channel();
+
+	command_sender
+		.send(NetworkCommand::RequestFile {
+			request: FileRequest { file_id: file_id.to_string(), offset, length: FILE_CHUNK_SIZE },
+			peer_id,
+			multiaddr: multiaddr.clone(),
+			channel,
+		})
+		.map_err(|_| anyhow!("Failed to send request file command"))?;
+
+	receiver.await?
+}
+
+/// `path` with `extension` appended, keeping its own extension if any.
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+	let mut path = OsString::from(path);
+	path.push(".");
+	path.push(extension);
+
+	path.into()
+}
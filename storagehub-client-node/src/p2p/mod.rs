@@ -2,7 +2,9 @@
 //!
 //! Everything related to libp2p configuration and implementation is in this module.
 
+mod codec;
 pub(crate) mod commands;
+pub(crate) mod download;
 mod identify;
 mod request_response;
 pub(crate) mod service;
@@ -1,8 +1,18 @@
 use anyhow::anyhow;
-use libp2p::request_response::{Event as RequestResponseEvent, Message};
+use libp2p::{
+	request_response::{Event as RequestResponseEvent, Message, ResponseChannel},
+	PeerId,
+};
+use std::{
+	fs::File,
+	io::{self, Read, Seek, SeekFrom},
+};
 use tracing::{debug, error, info, warn};
 
-use crate::p2p::service::FileResponse;
+use crate::p2p::{
+	commands::NetworkCommand,
+	service::{FileResponse, FILE_CHUNK_SIZE},
+};
 
 use super::service::{FileRequest, Service};
 
@@ -20,38 +30,43 @@ impl Service {
                             peer
                         );
 
-					let file = match std::fs::read(format!("{}/{}", self.file_path, request.0)) {
-						Ok(file) => FileResponse(file),
-						Err(e) => {
-							error!("[RequestResponseEvent::Message::Request] - failed to read file: {:?}", e);
-							return;
-						},
+					let Some(file_access) = self.file_access.clone() else {
+						self.send_file(peer, request, channel);
+						return;
 					};
 
-					if self
-						.swarm
-						.behaviour_mut()
-						.request_response
-						.send_response(channel, file)
-						.is_err()
-					{
-						error!("[BehaviourEvent::RequestMessage] failed to send response")
-					}
-
-					info!(
-						"[RequestResponseEvent::Message::Request] - sending FileResponse to peer {}.",
-						peer
-					);
+					// Check on-chain that the peer can read the file before sending it.
+					let command_sender = self.command_sender.clone();
+					tokio::spawn(async move {
+						match file_access.is_allowed(&request.file_id, &peer).await {
+							Ok(true) => {
+								let _ = command_sender.send(NetworkCommand::SendFile {
+									peer,
+									request,
+									channel,
+								});
+							},
+							Ok(false) => warn!(
+								"[RequestResponseEvent::Message::Request] - peer {} is not allowed to read file {}.",
+								peer, request.file_id
+							),
+							Err(e) => error!(
+								"[RequestResponseEvent::Message::Request] - failed to check access to file {}: {}",
+								request.file_id, e
+							),
+						}
+					});
 				},
 				Message::Response { request_id, response } => {
 					debug!(
-                        "[RequestResponseEvent::Message::Response] - with request_id {} and response {:?}.",
+                        "[RequestResponseEvent::Message::Response] - with request_id {} and {} bytes of a {} bytes file.",
                         request_id,
-                        response
+                        response.chunk.len(),
+                        response.file_size
                     );
 
 					if let Some(request) = self.pending_responses.remove(&request_id) {
-						if request.send(Ok(response.0)).is_err() {
+						if request.send(Ok(response)).is_err() {
 							warn!("[RequestResponseMessage::Response] - failed to send request: {request_id:?}");
 						}
 
@@ -82,12 +97,60 @@ impl Service {
 					request_id, error
 				);
 
+				// The downloader requests the range again, resuming the download.
 				let _ = self
 					.pending_responses
 					.remove(&request_id)
 					.expect("Request to still be pending.")
-					.send(Err(anyhow!("Outbound failure.")));
+					.send(Err(anyhow!("Outbound failure: {error:?}")));
+			},
+		}
+	}
+	/// Send the byte range of a file requested by `peer`.
+	pub(crate) fn send_file(
+		&mut self,
+		peer: PeerId,
+		request: FileRequest,
+		channel: ResponseChannel<FileResponse>,
+	) {
+		let path = format!("{}/{}", self.file_path, request.file_id);
+		let response = match read_range(&path, request.offset, request.length) {
+			Ok(response) => response,
+			Err(e) => {
+				error!("[RequestResponseEvent::Message::Request] - failed to read file: {:?}", e);
+				return;
 			},
+		};
+
+		let response_length = response.chunk.len();
+		if self
+			.swarm
+			.behaviour_mut()
+			.request_response
+			.send_response(channel, response)
+			.is_err()
+		{
+			error!("[BehaviourEvent::RequestMessage] failed to send response")
 		}
+
+		info!(
+			"[RequestResponseEvent::Message::Request] - sending {} bytes at offset {} of file {} to peer {}.",
+			response_length, request.offset, request.file_id, peer
+		);
 	}
 }
+
+/// Read `length` bytes at `offset` of the file at `path`, up to [`FILE_CHUNK_SIZE`] bytes and
+/// without reading the rest of the file.
+///
+/// Ranges past the end of the file are cut short, down to empty.
+pub(crate) fn read_range(path: &str, offset: u64, length: u64) -> io::Result<FileResponse> {
+	let mut file = File::open(path)?;
+	let file_size = file.metadata()?.len();
+
+	file.seek(SeekFrom::Start(offset))?;
+	let mut data = Vec::new();
+	file.take(length.min(FILE_CHUNK_SIZE)).read_to_end(&mut data)?;
+
+	Ok(FileResponse { file_size, chunk: data })
+}
@@ -1,10 +1,11 @@
-use crate::Role;
+use crate::{lightclient::access::FileAccess, Role};
 use anyhow::{anyhow, Result};
 use libp2p::{
-	futures::StreamExt, request_response::OutboundRequestId, swarm::NetworkBehaviour, Swarm,
+	futures::StreamExt, identity::Keypair, request_response::OutboundRequestId,
+	swarm::NetworkBehaviour, Swarm,
 };
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, error::Error, time::Duration};
+use std::{collections::HashMap, error::Error, sync::Arc, time::Duration};
 use tokio::{
 	select,
 	sync::{
@@ -22,7 +23,7 @@ use libp2p::{
 };
 use tokio::sync::mpsc::unbounded_channel;
 
-use super::commands::NetworkCommand;
+use super::{codec::NodeFileCodec, commands::NetworkCommand};
 
 /// Defines max_negotiating_inbound_streams constant for the swarm.
 /// It must be set for large plots.
@@ -32,6 +33,10 @@ const IDLE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub const IDENTIFY_PROTOCOL: &str = "/storagehub/id/0.0.1";
 pub const REQUEST_RESPONSE_PROTOCOL: &str = "/storagehub/req-res/0.0.1";
+/// Protocol StorageHub nodes running an embedded BSP request files with.
+pub const NODE_FILE_TRANSFER_PROTOCOL: &str = "/storagehub/file/3";
+/// Largest range of a file sent at once, so that neither side holds a whole file in memory.
+pub const FILE_CHUNK_SIZE: u64 = 1024 * 1024;
 
 lazy_static! {
 	pub(crate) static ref REQUEST_RESPONSE_PROTOCOL_NAME: StreamProtocol =
@@ -40,10 +45,20 @@ lazy_static! {
 
 pub(crate) type Port = u16;
 
+/// Request of a byte range of a file, of up to [`FILE_CHUNK_SIZE`] bytes.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub(crate) struct FileRequest(pub(crate) String);
+pub(crate) struct FileRequest {
+	pub(crate) file_id: String,
+	pub(crate) offset: u64,
+	pub(crate) length: u64,
+}
+/// A byte range of a file, along with the size of the whole file so the receiver knows when it has
+/// received all of it.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub(crate) struct FileResponse(pub(crate) Vec<u8>);
+pub(crate) struct FileResponse {
+	pub(crate) file_size: u64,
+	pub(crate) chunk: Vec<u8>,
+}
 
 /// Custom network behaviour that combines request_response and identify.
 ///
@@ -52,6 +67,8 @@ pub(crate) struct FileResponse(pub(crate) Vec<u8>);
 pub(crate) struct Behaviour {
 	identify: identify::Behaviour,
 	pub(crate) request_response: request_response::cbor::Behaviour<FileRequest, FileResponse>,
+	/// Serves files to StorageHub nodes running an embedded BSP.
+	pub(crate) node_request_response: request_response::Behaviour<NodeFileCodec>,
 }
 
 type HashMapStore<K, R> = HashMap<K, Sender<Result<R, anyhow::Error>>>;
@@ -63,14 +80,18 @@ pub(crate) struct Service {
 	/// `NetworkBehaviour` to the `Transport` as well as events from the Transport to the
 	/// `NetworkBehaviour`
 	pub(crate) swarm: Swarm<Behaviour>,
+	/// The ed25519 key the PeerId of the swarm is derived from.
+	pub(crate) keypair: Keypair,
 	/// Handles outbound messages to peers.
 	pub(crate) command_sender: CommandSender,
 	/// Handles inbound messages from peers.
 	pub(crate) command_receiver: UnboundedReceiver<NetworkCommand>,
 	/// Pending `request_response` requests.
-	pub(crate) pending_responses: HashMapStore<OutboundRequestId, Vec<u8>>,
+	pub(crate) pending_responses: HashMapStore<OutboundRequestId, FileResponse>,
 	/// Path to the file to be sent.
 	pub(crate) file_path: String,
+	/// Checks whether peers can read the files they request. Every file is sent if not set.
+	pub(crate) file_access: Option<Arc<FileAccess>>,
 }
 
 impl Service {
@@ -105,12 +126,15 @@ impl Service {
 		port: Port,
 		file_path: String,
 	) -> Result<Service, Box<dyn Error>> {
-		let mut swarm = libp2p::SwarmBuilder::with_new_identity()
+		// An ed25519 key, so that the runtime can check signatures of the PeerId registration.
+		let keypair = Keypair::generate_ed25519();
+		let mut swarm = libp2p::SwarmBuilder::with_existing_identity(keypair.clone())
 			.with_tokio()
 			// Transport protocol: https://docs.rs/libp2p/latest/libp2p/trait.Transport.html
+			// Noise is what StorageHub nodes secure their connections with.
 			.with_tcp(
 				libp2p::tcp::Config::default(),
-				libp2p::tls::Config::new,
+				(libp2p::tls::Config::new, libp2p::noise::Config::new),
 				libp2p::yamux::Config::default,
 			)?
 			// NetworkBehaviours: https://docs.rs/libp2p/latest/libp2p/swarm/trait.NetworkBehaviour.html#
@@ -132,6 +156,13 @@ impl Service {
 						)],
 						request_response::Config::default(),
 					),
+					node_request_response: request_response::Behaviour::new(
+						[(
+							StreamProtocol::new(NODE_FILE_TRANSFER_PROTOCOL),
+							ProtocolSupport::Inbound,
+						)],
+						request_response::Config::default(),
+					),
 				})
 			})?
 			.with_swarm_config(|config| {
@@ -155,10 +186,18 @@ impl Service {
 
 		Ok(Service {
 			swarm,
+			keypair,
 			command_sender,
 			command_receiver,
 			pending_responses: HashMap::new(),
 			file_path,
+			file_access: None,
 		})
 	}
+
+	/// Only send files to the peers that can read them on-chain.
+	pub(crate) fn with_file_access(mut self, file_access: FileAccess) -> Self {
+		self.file_access = Some(Arc::new(file_access));
+		self
+	}
 }
@@ -29,6 +29,8 @@ impl Service {
 			SwarmEvent::Behaviour(event) => match event {
 				BehaviourEvent::Identify(identify_event) => self.handle_identify(identify_event),
 				BehaviourEvent::RequestResponse(event) => self.handle_req_res(event),
+				// Files are served the same way to StorageHub nodes.
+				BehaviourEvent::NodeRequestResponse(event) => self.handle_req_res(event),
 			},
 			SwarmEvent::IncomingConnectionError { local_addr, error, .. } => {
 				debug!(
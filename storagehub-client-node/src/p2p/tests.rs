@@ -30,3 +30,118 @@ async fn test_network_start() {
 		}
 	}
 }
+
+#[tokio::test]
+async fn node_file_transfer_messages_are_length_prefixed() {
+	use libp2p::{futures::io::Cursor, request_response::Codec, StreamProtocol};
+
+	use super::{
+		codec::NodeFileCodec,
+		service::{FileRequest, FileResponse, NODE_FILE_TRANSFER_PROTOCOL},
+	};
+
+	let protocol = StreamProtocol::new(NODE_FILE_TRANSFER_PROTOCOL);
+	let response = FileResponse { file_size: 1000, chunk: vec![7u8; 300] };
+
+	let mut io = Cursor::new(Vec::new());
+	NodeFileCodec
+		.write_response(&protocol, &mut io, response.clone())
+		.await
+		.unwrap();
+
+	// 308 bytes, the file size and the chunk, are encoded as the unsigned varint 0xb4 0x02.
+	let encoded = io.into_inner();
+	assert_eq!(&encoded[..2], &[0xb4, 0x02]);
+	assert_eq!(&encoded[2..10], &1000u64.to_le_bytes());
+
+	let mut io = Cursor::new(encoded);
+	assert_eq!(NodeFileCodec.read_response(&protocol, &mut io).await.unwrap(), response);
+
+	// Requests are the offset and length of the range followed by the file id.
+	let request = FileRequest { file_id: "file".to_string(), offset: 3000, length: 500 };
+	let mut io = Cursor::new(Vec::new());
+	NodeFileCodec.write_request(&protocol, &mut io, request.clone()).await.unwrap();
+
+	let mut io = Cursor::new(io.into_inner());
+	assert_eq!(NodeFileCodec.read_request(&protocol, &mut io).await.unwrap(), request);
+}
+
+#[test]
+fn files_are_read_by_byte_range() {
+	use super::{request_response::read_range, service::FILE_CHUNK_SIZE};
+
+	let path = std::env::temp_dir().join("storagehub-files-are-read-by-byte-range");
+	let file: Vec<u8> = (0..FILE_CHUNK_SIZE + 10).map(|i| i as u8).collect();
+	std::fs::write(&path, &file).unwrap();
+	let path = path.to_str().unwrap();
+
+	let range = read_range(path, 5, 20).unwrap();
+	assert_eq!(range.file_size, FILE_CHUNK_SIZE + 10);
+	assert_eq!(range.chunk, &file[5..25]);
+
+	// Ranges are capped at one chunk, and at the end of the file.
+	let first = read_range(path, 0, u64::MAX).unwrap();
+	assert_eq!(first.chunk, &file[..FILE_CHUNK_SIZE as usize]);
+	let last = read_range(path, FILE_CHUNK_SIZE, FILE_CHUNK_SIZE).unwrap();
+	assert_eq!(last.chunk, &file[FILE_CHUNK_SIZE as usize..]);
+
+	// Ranges past the end of the file are empty.
+	assert!(read_range(path, FILE_CHUNK_SIZE + 10, 1).unwrap().chunk.is_empty());
+
+	std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn downloads_resume_from_the_last_verified_chunk() {
+	use subxt::ext::{codec::Encode, sp_core::blake2_256};
+
+	use super::{
+		download::{resumable_progress, DownloadProgress},
+		service::FILE_CHUNK_SIZE,
+	};
+
+	let directory = std::env::temp_dir().join("storagehub-downloads-resume");
+	std::fs::create_dir_all(&directory).unwrap();
+	let path = directory.join("file");
+	let chunk = FILE_CHUNK_SIZE as usize;
+
+	// Two chunks were recorded, but the second one was corrupted on disk.
+	let mut partial: Vec<u8> = (0..2 * chunk).map(|i| i as u8).collect();
+	let progress = DownloadProgress {
+		file_id: "file".to_string(),
+		sender: "/ip4/127.0.0.1/tcp/23456".to_string(),
+		fingerprint: [1; 32],
+		file_size: Some(3 * FILE_CHUNK_SIZE),
+		chunk_hashes: vec![blake2_256(&partial[..chunk]), blake2_256(&partial[chunk..])],
+	};
+	partial[chunk] ^= 1;
+	std::fs::write(directory.join("file.part"), &partial).unwrap();
+	std::fs::write(directory.join("file.progress"), progress.encode()).unwrap();
+
+	let resumed = resumable_progress(&path, "file", [1; 32]).unwrap();
+	assert_eq!(resumed.chunk_hashes, progress.chunk_hashes[..1]);
+
+	// The download of another version of the file is not resumed.
+	assert!(resumable_progress(&path, "file", [2; 32]).is_none());
+
+	std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn file_fingerprint_hashes_the_whole_file() {
+	use subxt::ext::sp_core::blake2_256;
+
+	use super::{download::file_fingerprint, service::FILE_CHUNK_SIZE};
+
+	let path = std::env::temp_dir().join("storagehub-downloads-fingerprint");
+
+	// The file spans several chunks, the last of them partial.
+	let content: Vec<u8> = (0..2 * FILE_CHUNK_SIZE as usize + 3).map(|i| i as u8).collect();
+	std::fs::write(&path, &content).unwrap();
+	assert_eq!(file_fingerprint(&path).unwrap(), blake2_256(&content));
+
+	std::fs::write(&path, []).unwrap();
+	assert_eq!(file_fingerprint(&path).unwrap(), blake2_256(&[]));
+
+	std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,159 @@
+//! Integration tests for the Storage Hub runtime.
+//!
+//! Unlike the pallets' unit tests, which run against mock runtimes, these tests build the actual
+//! `node_template_runtime` from its `RuntimeGenesisConfig` and drive it block by block through
+//! `Executive`, submitting signed extrinsics just like a node would.
+//!
+//! This crate only provides the helpers to do so, along with a simulated XCM network in
+//! [`xcm_network`], the tests themselves live in `tests/`.
+
+use codec::Encode;
+use frame_support::dispatch::DispatchResult;
+use node_template_runtime::{
+	AccountId, AuraConfig, Balance, BalancesConfig, BlockNumber, Executive, Header,
+	MinProviderStake, PalletIdentityConfig, PalletMspIdentityConfig, PalletReputationConfig,
+	Runtime, RuntimeCall, RuntimeEvent, RuntimeGenesisConfig, SignedExtra, SignedPayload,
+	SudoConfig, System, UncheckedExtrinsic, SLOT_DURATION,
+};
+use sp_consensus_aura::{Slot, AURA_ENGINE_ID};
+use sp_keyring::AccountKeyring;
+use sp_runtime::{
+	generic::{Digest, DigestItem, Era},
+	traits::Header as HeaderT,
+	transaction_validity::{TransactionSource, TransactionValidity},
+	BuildStorage,
+};
+
+pub mod xcm_network;
+
+/// Balance every well-known account is endowed with at genesis.
+pub const INITIAL_BALANCE: Balance = 1 << 60;
+
+/// Build the runtime's genesis storage and start block 1.
+///
+/// All well-known accounts are endowed, Alice is the sudo key and the only Aura authority, and
+/// `bsps` and `msps` are registered in their instance of the Identity Pallet. `bsps` also bond
+/// the minimum stake, so they can volunteer.
+pub fn new_test_ext(
+	bsps: Vec<AccountKeyring>,
+	msps: Vec<AccountKeyring>,
+) -> sp_io::TestExternalities {
+	let storage = RuntimeGenesisConfig {
+		balances: BalancesConfig {
+			balances: AccountKeyring::iter()
+				.map(|k| (k.to_account_id(), INITIAL_BALANCE))
+				.collect(),
+		},
+		aura: AuraConfig { authorities: vec![AccountKeyring::Alice.public().into()] },
+		sudo: SudoConfig { key: Some(AccountKeyring::Alice.to_account_id()) },
+		pallet_reputation: PalletReputationConfig {
+			stakes: bsps.iter().map(|k| (k.to_account_id(), MinProviderStake::get())).collect(),
+		},
+		pallet_identity: PalletIdentityConfig {
+			users: bsps.iter().map(|k| k.to_account_id()).collect(),
+			..Default::default()
+		},
+		pallet_msp_identity: PalletMspIdentityConfig {
+			users: msps.iter().map(|k| k.to_account_id()).collect(),
+			..Default::default()
+		},
+		..Default::default()
+	}
+	.build_storage()
+	.expect("Genesis config should be valid");
+
+	let mut ext = sp_io::TestExternalities::from(storage);
+	ext.execute_with(|| run_to_block(1));
+	ext
+}
+
+/// Finalize the current block and initialize new ones until block `n` is reached.
+pub fn run_to_block(n: BlockNumber) {
+	while System::block_number() < n {
+		let parent_hash = if System::block_number() > 0 {
+			Executive::finalize_block().hash()
+		} else {
+			System::parent_hash()
+		};
+
+		start_block(System::block_number() + 1, parent_hash);
+	}
+}
+
+/// Initialize block `number`, authored by Aura at slot `number`, and set its timestamp.
+fn start_block(number: BlockNumber, parent_hash: <Header as HeaderT>::Hash) {
+	let slot = Slot::from(number as u64);
+	let digest = Digest { logs: vec![DigestItem::PreRuntime(AURA_ENGINE_ID, slot.encode())] };
+	let header = Header::new(number, Default::default(), Default::default(), parent_hash, digest);
+
+	Executive::initialize_block(&header);
+
+	// Timestamp must be set once per block, matching the Aura slot.
+	let now = number as u64 * SLOT_DURATION;
+	let set_timestamp =
+		UncheckedExtrinsic::new_unsigned(RuntimeCall::Timestamp(pallet_timestamp::Call::set {
+			now,
+		}));
+	Executive::apply_extrinsic(set_timestamp)
+		.expect("Timestamp inherent should be valid")
+		.expect("Timestamp inherent should succeed");
+}
+
+/// Build an immortal extrinsic calling `call`, signed by `signer` with its current nonce.
+pub fn sign(signer: AccountKeyring, call: RuntimeCall) -> UncheckedExtrinsic {
+	let account: AccountId = signer.to_account_id();
+	let extra: SignedExtra = (
+		frame_system::CheckNonZeroSender::<Runtime>::new(),
+		frame_system::CheckSpecVersion::<Runtime>::new(),
+		frame_system::CheckTxVersion::<Runtime>::new(),
+		frame_system::CheckGenesis::<Runtime>::new(),
+		frame_system::CheckEra::<Runtime>::from(Era::Immortal),
+		frame_system::CheckNonce::<Runtime>::from(System::account_nonce(&account)),
+		frame_system::CheckWeight::<Runtime>::new(),
+		pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(0),
+		pallet_file_system::CheckBspVolunteer::<Runtime>::new(),
+	);
+
+	let payload = SignedPayload::new(call, extra).expect("Signed extensions should be valid");
+	let signature = payload.using_encoded(|payload| signer.sign(payload));
+	let (call, extra, _) = payload.deconstruct();
+
+	UncheckedExtrinsic::new_signed(call, account.into(), signature.into(), extra)
+}
+
+/// Sign `call` by `signer` and apply it in the current block, returning its dispatch result.
+///
+/// Panics if the extrinsic is not valid, e.g. if `signer` cannot pay for its fees.
+pub fn submit(signer: AccountKeyring, call: RuntimeCall) -> DispatchResult {
+	Executive::apply_extrinsic(sign(signer, call)).expect("Extrinsic should be valid")
+}
+
+/// Validate `call` signed by `signer` the way the transaction pool would, without changing state.
+pub fn validate(signer: AccountKeyring, call: RuntimeCall) -> TransactionValidity {
+	// Validation initializes the next block, which must not leak into the current one.
+	sp_io::storage::start_transaction();
+	let validity = Executive::validate_transaction(
+		TransactionSource::External,
+		sign(signer, call),
+		System::parent_hash(),
+	);
+	sp_io::storage::rollback_transaction();
+
+	validity
+}
+
+/// Submit `call` through Sudo, returning the result of the inner call.
+pub fn submit_as_root(call: RuntimeCall) -> DispatchResult {
+	let sudo = RuntimeCall::Sudo(pallet_sudo::Call::sudo { call: Box::new(call) });
+	submit(AccountKeyring::Alice, sudo)?;
+
+	// Sudo always succeeds, the result of the inner call is in its event.
+	System::events()
+		.into_iter()
+		.rev()
+		.find_map(|record| match record.event {
+			RuntimeEvent::Sudo(pallet_sudo::Event::Sudid { sudo_result }) => Some(sudo_result),
+			_ => None,
+		})
+		.expect("Sudid event should be deposited")
+}
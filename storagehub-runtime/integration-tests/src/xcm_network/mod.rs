@@ -0,0 +1,119 @@
+//! A simulated network where Storage Hub and a sibling parachain exchange XCM messages.
+//!
+//! Storage Hub runs the actual `node_template_runtime`, with its `XcmConfig` routing through the
+//! simulator. Messages between parachains go straight through the simulator's buses, so the relay
+//! chain is only a stub that rejects upward messages.
+
+pub mod sibling;
+
+use codec::Decode;
+use node_template_runtime::xcm_config::{ParachainId, XcmConfig};
+use sp_keyring::AccountKeyring::{Bob, Two};
+use std::marker::PhantomData;
+use xcm::{latest::prelude::*, VersionedXcm};
+use xcm_executor::XcmExecutor;
+use xcm_simulator::{
+	decl_test_network, decl_test_parachain, DmpMessageHandlerT, ParaId, ProcessMessage,
+	ProcessMessageError, RelayBlockNumber, WeightMeter, XcmpMessageFormat, XcmpMessageHandlerT,
+};
+
+/// Storage Hub's XCM executor configuration, routing through the simulated network.
+pub type StorageHubXcmConfig = XcmConfig<ParachainXcmRouter<ParachainId>>;
+
+decl_test_network! {
+	pub struct Network {
+		relay_chain = Relay,
+		parachains = vec![
+			(sibling::PARA_ID, Sibling),
+			(u32::from(ParachainId::get()), StorageHub),
+		],
+	}
+}
+
+decl_test_parachain! {
+	pub struct StorageHub {
+		Runtime = node_template_runtime::Runtime,
+		XcmpMessageHandler = MessageHandler<StorageHubXcmConfig>,
+		DmpMessageHandler = MessageHandler<StorageHubXcmConfig>,
+		new_ext = storage_hub_ext(),
+	}
+}
+
+decl_test_parachain! {
+	pub struct Sibling {
+		Runtime = sibling::Runtime,
+		XcmpMessageHandler = MessageHandler<sibling::XcmConfig>,
+		DmpMessageHandler = MessageHandler<sibling::XcmConfig>,
+		new_ext = sibling::new_ext(),
+	}
+}
+
+/// The relay chain of the network, which does not process any message itself.
+pub struct Relay;
+
+xcm_simulator::__impl_ext!(Relay, sp_io::TestExternalities::default());
+
+impl ProcessMessage for Relay {
+	type Origin = ParaId;
+
+	fn process_message(
+		_message: &[u8],
+		_origin: Self::Origin,
+		_meter: &mut WeightMeter,
+		_id: &mut [u8; 32],
+	) -> Result<bool, ProcessMessageError> {
+		Err(ProcessMessageError::Unsupported)
+	}
+}
+
+/// Storage Hub's genesis, with Bob registered as BSP and Two as MSP.
+fn storage_hub_ext() -> sp_io::TestExternalities {
+	crate::new_test_ext(vec![Bob], vec![Two])
+}
+
+/// Executes the messages a parachain receives with the XCM executor configured by `Config`.
+pub struct MessageHandler<Config>(PhantomData<Config>);
+
+impl<Config: xcm_executor::Config> MessageHandler<Config> {
+	fn execute(origin: MultiLocation, data: &mut &[u8], max_weight: Weight) {
+		let message: Xcm<Config::RuntimeCall> = VersionedXcm::decode(data)
+			.expect("XCM message should be valid")
+			.try_into()
+			.expect("XCM version should be supported");
+		let hash = message.using_encoded(sp_io::hashing::blake2_256);
+
+		XcmExecutor::<Config>::execute_xcm(origin, message, hash, max_weight);
+	}
+}
+
+impl<Config: xcm_executor::Config> XcmpMessageHandlerT for MessageHandler<Config> {
+	fn handle_xcmp_messages<'a, I: Iterator<Item = (ParaId, RelayBlockNumber, &'a [u8])>>(
+		iter: I,
+		max_weight: Weight,
+	) -> Weight {
+		for (sender, _, mut data) in iter {
+			let origin = MultiLocation::new(1, X1(Parachain(sender.into())));
+
+			// A single XCMP blob holds any number of messages, one after the other.
+			XcmpMessageFormat::decode(&mut data).expect("XCMP message format should be valid");
+			while !data.is_empty() {
+				Self::execute(origin, &mut data, max_weight);
+			}
+		}
+
+		max_weight
+	}
+}
+
+impl<Config: xcm_executor::Config> DmpMessageHandlerT for MessageHandler<Config> {
+	fn handle_dmp_messages(
+		iter: impl Iterator<Item = (RelayBlockNumber, Vec<u8>)>,
+		max_weight: Weight,
+	) -> Weight {
+		for (_, data) in iter {
+			Self::execute(MultiLocation::parent(), &mut data.as_slice(), max_weight);
+		}
+
+		max_weight
+	}
+}
@@ -0,0 +1,36 @@
+use frame_support::traits::{GetStorageVersion, StorageVersion};
+use node_template_runtime::{Executive, PalletFileSystem, PalletIdentity, PalletMspIdentity};
+use runtime_integration_tests::new_test_ext;
+use sp_keyring::AccountKeyring::{Bob, Charlie};
+
+#[test]
+fn runtime_upgrade_versions_unversioned_storage() {
+	new_test_ext(vec![Bob], vec![Charlie]).execute_with(|| {
+		// Genesis sets the current storage versions.
+		assert_eq!(
+			PalletIdentity::on_chain_storage_version(),
+			PalletIdentity::current_storage_version()
+		);
+		assert_eq!(
+			PalletMspIdentity::on_chain_storage_version(),
+			PalletMspIdentity::current_storage_version()
+		);
+		assert_eq!(
+			PalletFileSystem::on_chain_storage_version(),
+			PalletFileSystem::current_storage_version()
+		);
+
+		// Chains launched before the storage was versioned are at version 0.
+		StorageVersion::new(0).put::<PalletIdentity>();
+		StorageVersion::new(0).put::<PalletMspIdentity>();
+		StorageVersion::new(0).put::<PalletFileSystem>();
+
+		// The runtime upgrade migrates every pallet to version 1, keeping its storage.
+		Executive::execute_on_runtime_upgrade();
+		assert_eq!(PalletIdentity::on_chain_storage_version(), 1);
+		assert_eq!(PalletMspIdentity::on_chain_storage_version(), 1);
+		assert_eq!(PalletFileSystem::on_chain_storage_version(), 1);
+		assert!(PalletIdentity::users(Bob.to_account_id()).is_some());
+		assert!(PalletMspIdentity::users(Charlie.to_account_id()).is_some());
+	});
+}
@@ -0,0 +1,333 @@
+use node_template_runtime::{
+	AccountId, Balances, Hash, MinProviderStake, PalletFileSystem, PalletIdentity,
+	PalletMspIdentity, ProxyType, Runtime, RuntimeCall, RuntimeEvent, System,
+};
+use pallet_file_system::{FilesMapping, StorageRequests};
+use runtime_integration_tests::{
+	new_test_ext, run_to_block, submit, submit_as_root, validate, INITIAL_BALANCE,
+};
+use sp_keyring::AccountKeyring::{self, Alice, Bob, Charlie, Dave, Eve, Ferdie, One, Two};
+use sp_runtime::{
+	traits::{BlakeTwo256, Hash as HashT},
+	transaction_validity::{InvalidTransaction, TransactionValidityError},
+};
+
+type FileLocation = pallet_file_system::types::FileLocation<Runtime>;
+type MultiAddress = pallet_file_system::types::MultiAddress<Runtime>;
+
+/// The MSP picked for every file.
+const MSP: AccountKeyring = Two;
+
+fn location() -> FileLocation {
+	FileLocation::try_from(b"test".to_vec()).unwrap()
+}
+
+fn fingerprint() -> Hash {
+	BlakeTwo256::hash(b"test")
+}
+
+fn sender_multiaddress() -> MultiAddress {
+	MultiAddress::try_from(
+		b"/ip4/127.0.0.1/tcp/23456/p2p/12D3KooWAWERvhQ4if5aum3FGhdTnTervVpxMSiy99VoHbK4s4ZX"
+			.to_vec(),
+	)
+	.unwrap()
+}
+
+fn bsp_multiaddress() -> MultiAddress {
+	MultiAddress::try_from(
+		b"/ip4/127.0.0.1/tcp/34567/p2p/12D3KooWHENiEV7ArLUMawSVSZX7ynzCXSqtDQyWyR5Uf9oJBR6t"
+			.to_vec(),
+	)
+	.unwrap()
+}
+
+fn request_storage() -> RuntimeCall {
+	RuntimeCall::PalletFileSystem(pallet_file_system::Call::request_storage {
+		location: location(),
+		fingerprint: fingerprint(),
+		size: 4,
+		msp: MSP.to_account_id(),
+		sender_multiaddress: sender_multiaddress(),
+	})
+}
+
+fn bsp_volunteer() -> RuntimeCall {
+	RuntimeCall::PalletFileSystem(pallet_file_system::Call::bsp_volunteer {
+		location: location(),
+		fingerprint: fingerprint(),
+		bsp_multiaddress: bsp_multiaddress(),
+	})
+}
+
+fn proxy(real: AccountKeyring, call: RuntimeCall) -> RuntimeCall {
+	RuntimeCall::Proxy(pallet_proxy::Call::proxy {
+		real: real.to_account_id().into(),
+		force_proxy_type: Some(ProxyType::StorageProvider),
+		call: Box::new(call),
+	})
+}
+
+fn bsps_of(location: &FileLocation) -> Vec<AccountId> {
+	FilesMapping::<Runtime>::get(location)
+		.map(|f| f.bsps.to_vec())
+		.unwrap_or_default()
+}
+
+#[test]
+fn bsp_registers_through_application() {
+	new_test_ext(vec![], vec![]).execute_with(|| {
+		// Bob applies to be a BSP.
+		assert_eq!(
+			submit(Bob, RuntimeCall::PalletIdentity(pallet_identity::Call::apply {})),
+			Ok(())
+		);
+		assert!(PalletIdentity::applications(Bob.to_account_id()).is_some());
+		assert_eq!(
+			Balances::reserved_balance(Bob.to_account_id()),
+			node_template_runtime::ProviderApplicationDeposit::get()
+		);
+		System::assert_has_event(RuntimeEvent::PalletIdentity(
+			pallet_identity::Event::NewApplication { candidate: Bob.to_account_id() },
+		));
+
+		// Root approves the application in a later block.
+		run_to_block(2);
+		assert_eq!(
+			submit_as_root(RuntimeCall::PalletIdentity(pallet_identity::Call::register_user {
+				who: Bob.to_account_id()
+			})),
+			Ok(())
+		);
+
+		// Check that Bob is registered and his application is cleared.
+		assert!(PalletIdentity::users(Bob.to_account_id()).is_some());
+		assert!(PalletIdentity::applications(Bob.to_account_id()).is_none());
+		assert_eq!(Balances::reserved_balance(Bob.to_account_id()), 0);
+		assert_eq!(PalletIdentity::count(), 1);
+		System::assert_has_event(RuntimeEvent::PalletIdentity(pallet_identity::Event::NewUser {
+			user: Bob.to_account_id(),
+		}));
+
+		// Check that the assignment threshold was recomputed for a single BSP.
+		assert_eq!(PalletFileSystem::current_assignment_threshold(), Some(u128::MAX));
+	});
+}
+
+#[test]
+fn storage_request_is_fulfilled_by_max_bsps() {
+	let bsps = vec![Bob, Charlie, Dave, Eve, Ferdie];
+
+	new_test_ext([bsps.clone(), vec![One]].concat(), vec![MSP]).execute_with(|| {
+		// Alice requests to store a file.
+		assert_eq!(submit(Alice, request_storage()), Ok(()));
+		System::assert_has_event(RuntimeEvent::PalletFileSystem(
+			pallet_file_system::Event::NewStorageRequest {
+				who: Alice.to_account_id(),
+				location: location(),
+				fingerprint: fingerprint(),
+				size: 4,
+				msp: MSP.to_account_id(),
+				sender_multiaddress: sender_multiaddress(),
+			},
+		));
+		assert_eq!(StorageRequests::<Runtime>::get(location()).unwrap().requested_at, 1);
+
+		// BSPs volunteer one per block.
+		for (i, bsp) in bsps.iter().enumerate() {
+			run_to_block(i as u32 + 2);
+
+			assert_eq!(submit(*bsp, bsp_volunteer()), Ok(()));
+			System::assert_has_event(RuntimeEvent::PalletFileSystem(
+				pallet_file_system::Event::NewBspVolunteer {
+					who: bsp.to_account_id(),
+					location: location(),
+					fingerprint: fingerprint(),
+					bsp_multiaddress: bsp_multiaddress(),
+				},
+			));
+
+			// Check that the file is stored by every BSP that volunteered so far.
+			let expected: Vec<AccountId> = bsps[..=i].iter().map(|k| k.to_account_id()).collect();
+			assert_eq!(bsps_of(&location()), expected);
+		}
+
+		// Check that the storage request is cleared once MaxBsps is reached.
+		assert!(StorageRequests::<Runtime>::get(location()).is_none());
+		assert_eq!(bsps_of(&location()).len(), 5);
+
+		// One more BSP cannot volunteer anymore.
+		run_to_block(7);
+		assert_eq!(
+			submit(One, bsp_volunteer()),
+			Err(pallet_file_system::Error::<Runtime>::StorageRequestNotRegistered.into())
+		);
+		assert_eq!(bsps_of(&location()).len(), 5);
+	});
+}
+
+#[test]
+fn bsp_volunteer_rejects_invalid_volunteers() {
+	new_test_ext(vec![Bob], vec![MSP]).execute_with(|| {
+		assert_eq!(submit(Alice, request_storage()), Ok(()));
+
+		// Unregistered accounts cannot volunteer.
+		run_to_block(2);
+		assert_eq!(
+			submit(Two, bsp_volunteer()),
+			Err(pallet_file_system::Error::<Runtime>::NotBsp.into())
+		);
+
+		// Registered BSPs cannot volunteer twice.
+		assert_eq!(submit(Bob, bsp_volunteer()), Ok(()));
+		run_to_block(3);
+		assert_eq!(
+			submit(Bob, bsp_volunteer()),
+			Err(pallet_file_system::Error::<Runtime>::BspAlreadyRegistered.into())
+		);
+
+		// Check that the storage request is still open with a single BSP.
+		let storage_request = StorageRequests::<Runtime>::get(location()).unwrap();
+		assert_eq!(storage_request.bsps.to_vec(), vec![Bob.to_account_id()]);
+	});
+}
+
+#[test]
+fn qualifying_bsps_volunteer_for_free() {
+	new_test_ext(vec![Bob], vec![MSP]).execute_with(|| {
+		assert_eq!(submit(Alice, request_storage()), Ok(()));
+
+		// The transaction pool only accepts volunteers that qualify.
+		run_to_block(2);
+		assert!(validate(Bob, bsp_volunteer()).is_ok());
+		assert!(matches!(
+			validate(Two, bsp_volunteer()),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::Custom(_)))
+		));
+
+		// Bob does not pay for volunteering, his balance is only short of the stake he bonded.
+		assert_eq!(submit(Bob, bsp_volunteer()), Ok(()));
+		assert_eq!(
+			Balances::free_balance(Bob.to_account_id()),
+			INITIAL_BALANCE - MinProviderStake::get()
+		);
+
+		// Now that Bob volunteered, the pool rejects him volunteering again.
+		run_to_block(3);
+		assert!(matches!(
+			validate(Bob, bsp_volunteer()),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::Custom(_)))
+		));
+
+		// Volunteers that made it into a block anyway pay for failing.
+		assert_eq!(
+			submit(Two, bsp_volunteer()),
+			Err(pallet_file_system::Error::<Runtime>::NotBsp.into())
+		);
+		assert!(Balances::free_balance(Two.to_account_id()) < INITIAL_BALANCE);
+	});
+}
+
+#[test]
+fn bsp_volunteers_through_storage_provider_proxy() {
+	new_test_ext(vec![Bob], vec![MSP]).execute_with(|| {
+		assert_eq!(submit(Alice, request_storage()), Ok(()));
+
+		// Bob delegates its provider calls to a hot key.
+		assert_eq!(
+			submit(
+				Bob,
+				RuntimeCall::Proxy(pallet_proxy::Call::add_proxy {
+					delegate: Two.to_account_id().into(),
+					proxy_type: ProxyType::StorageProvider,
+					delay: 0,
+				})
+			),
+			Ok(())
+		);
+
+		// The hot key volunteers on behalf of Bob, paying for the proxy call as the pool does not
+		// check wrapped volunteers.
+		run_to_block(2);
+		let hot_key_balance = Balances::free_balance(Two.to_account_id());
+		assert_eq!(submit(Two, proxy(Bob, bsp_volunteer())), Ok(()));
+		System::assert_has_event(RuntimeEvent::Proxy(pallet_proxy::Event::ProxyExecuted {
+			result: Ok(()),
+		}));
+		let storage_request = StorageRequests::<Runtime>::get(location()).unwrap();
+		assert_eq!(storage_request.bsps.to_vec(), vec![Bob.to_account_id()]);
+		assert!(Balances::free_balance(Two.to_account_id()) < hot_key_balance);
+
+		// Failing wrapped volunteers are paid for too.
+		run_to_block(3);
+		let hot_key_balance = Balances::free_balance(Two.to_account_id());
+		assert_eq!(submit(Two, proxy(Bob, bsp_volunteer())), Ok(()));
+		System::assert_has_event(RuntimeEvent::Proxy(pallet_proxy::Event::ProxyExecuted {
+			result: Err(pallet_file_system::Error::<Runtime>::BspAlreadyRegistered.into()),
+		}));
+		assert!(Balances::free_balance(Two.to_account_id()) < hot_key_balance);
+
+		// The hot key cannot make any other call on behalf of Bob.
+		run_to_block(4);
+		let transfer = RuntimeCall::Balances(pallet_balances::Call::transfer_allow_death {
+			dest: Two.to_account_id().into(),
+			value: 1_000,
+		});
+		assert_eq!(submit(Two, proxy(Bob, transfer)), Ok(()));
+		System::assert_has_event(RuntimeEvent::Proxy(pallet_proxy::Event::ProxyExecuted {
+			result: Err(frame_system::Error::<Runtime>::CallFiltered.into()),
+		}));
+	});
+}
+
+#[test]
+fn msp_registers_through_application_and_accepts_files() {
+	new_test_ext(vec![Bob], vec![]).execute_with(|| {
+		// Dave applies to be an MSP, and root approves the application.
+		assert_eq!(
+			submit(Dave, RuntimeCall::PalletMspIdentity(pallet_identity::Call::apply {})),
+			Ok(())
+		);
+		run_to_block(2);
+		assert_eq!(
+			submit_as_root(RuntimeCall::PalletMspIdentity(pallet_identity::Call::register_user {
+				who: Dave.to_account_id()
+			})),
+			Ok(())
+		);
+		assert!(PalletMspIdentity::users(Dave.to_account_id()).is_some());
+
+		// Registering as MSP does not make Dave a BSP.
+		assert!(PalletIdentity::users(Dave.to_account_id()).is_none());
+
+		// Alice picks Dave as the MSP of her file.
+		run_to_block(3);
+		let request_storage =
+			RuntimeCall::PalletFileSystem(pallet_file_system::Call::request_storage {
+				location: location(),
+				fingerprint: fingerprint(),
+				size: 4,
+				msp: Dave.to_account_id(),
+				sender_multiaddress: sender_multiaddress(),
+			});
+		assert_eq!(submit(Alice, request_storage), Ok(()));
+
+		// Dave accepts it, while Bob backs it up.
+		run_to_block(4);
+		assert_eq!(
+			submit(
+				Dave,
+				RuntimeCall::PalletFileSystem(
+					pallet_file_system::Call::msp_accept_storage_request { location: location() }
+				)
+			),
+			Ok(())
+		);
+		assert_eq!(submit(Bob, bsp_volunteer()), Ok(()));
+
+		let file_metadata = FilesMapping::<Runtime>::get(location()).unwrap();
+		assert_eq!(file_metadata.msp, Some(Dave.to_account_id()));
+		assert!(file_metadata.msp_accepted);
+		assert_eq!(file_metadata.bsps.to_vec(), vec![Bob.to_account_id()]);
+	});
+}
@@ -0,0 +1,208 @@
+use codec::Encode;
+use node_template_runtime::{
+	xcm_config::{LocationToAccountId, ParachainId},
+	AccountId, Balance, Hash, Runtime, RuntimeCall, RuntimeEvent, System,
+};
+use pallet_file_system::StorageRequests;
+use runtime_integration_tests::{
+	submit,
+	xcm_network::{sibling, Network, Sibling, StorageHub},
+};
+use sp_keyring::AccountKeyring::{self, Alice, Charlie, Eve, Two};
+use sp_runtime::traits::{BlakeTwo256, Hash as HashT};
+use xcm::{latest::prelude::*, VersionedXcm};
+use xcm_executor::traits::{ConvertLocation, QueryHandler};
+use xcm_simulator::TestExt;
+
+type FileLocation = pallet_file_system::types::FileLocation<Runtime>;
+type MultiAddress = pallet_file_system::types::MultiAddress<Runtime>;
+
+/// The MSP registered on Storage Hub.
+const MSP: AccountKeyring = Two;
+
+/// The fees, in Storage Hub's native token, the sibling's users pay for each message.
+const FEES: Balance = 1_000_000_000_000;
+
+fn location() -> FileLocation {
+	FileLocation::try_from(b"test".to_vec()).unwrap()
+}
+
+fn fingerprint() -> Hash {
+	BlakeTwo256::hash(b"test")
+}
+
+fn sender_multiaddress() -> MultiAddress {
+	MultiAddress::try_from(
+		b"/ip4/127.0.0.1/tcp/23456/p2p/12D3KooWAWERvhQ4if5aum3FGhdTnTervVpxMSiy99VoHbK4s4ZX"
+			.to_vec(),
+	)
+	.unwrap()
+}
+
+fn request_storage(msp: AccountId) -> RuntimeCall {
+	RuntimeCall::PalletFileSystem(pallet_file_system::Call::request_storage {
+		location: location(),
+		fingerprint: fingerprint(),
+		size: 4,
+		msp,
+		sender_multiaddress: sender_multiaddress(),
+	})
+}
+
+/// Storage Hub, as seen from the sibling.
+fn storage_hub() -> MultiLocation {
+	MultiLocation::new(1, X1(Parachain(ParachainId::get().into())))
+}
+
+/// Alice's account on the sibling, as seen from within the sibling.
+fn sibling_alice() -> Junction {
+	AccountId32 { network: None, id: Alice.to_raw_public() }
+}
+
+/// Alice's sovereign sub-account on Storage Hub.
+fn sub_account() -> AccountId {
+	let location = MultiLocation::new(1, X2(Parachain(sibling::PARA_ID), sibling_alice()));
+	LocationToAccountId::convert_location(&location).unwrap()
+}
+
+/// Alice, from the sibling, pays for `call` to be dispatched on Storage Hub by her sub-account,
+/// and asks for its status to be reported back. Returns the id of the status query.
+fn transact_from_sibling(call: RuntimeCall) -> QueryId {
+	Sibling::execute_with(|| {
+		let query_id =
+			sibling::PolkadotXcm::new_query(storage_hub(), 100u32.into(), sibling_alice());
+
+		let fees: MultiAsset = (Here, FEES).into();
+		let message = Xcm(vec![
+			WithdrawAsset(fees.clone().into()),
+			BuyExecution { fees, weight_limit: Unlimited },
+			Transact {
+				origin_kind: OriginKind::SovereignAccount,
+				require_weight_at_most: Weight::from_parts(1_000_000_000, 64 * 1024),
+				call: call.encode().into(),
+			},
+			ReportTransactStatus(QueryResponseInfo {
+				destination: MultiLocation::new(1, X1(Parachain(sibling::PARA_ID))),
+				query_id,
+				max_weight: Weight::zero(),
+			}),
+			RefundSurplus,
+			DepositAsset {
+				assets: All.into(),
+				beneficiary: MultiLocation::new(
+					1,
+					X2(Parachain(sibling::PARA_ID), sibling_alice()),
+				),
+			},
+		]);
+
+		sibling::PolkadotXcm::send(
+			sibling::RuntimeOrigin::signed(Alice.to_account_id()),
+			Box::new(storage_hub().into()),
+			Box::new(VersionedXcm::V3(message)),
+		)
+		.unwrap();
+
+		query_id
+	})
+}
+
+/// Alice funds her sub-account on Storage Hub from her local account.
+fn fund_sub_account() {
+	StorageHub::execute_with(|| {
+		let transfer = RuntimeCall::Balances(pallet_balances::Call::transfer_allow_death {
+			dest: sub_account().into(),
+			value: 10 * FEES,
+		});
+		assert_eq!(submit(Alice, transfer), Ok(()));
+	});
+}
+
+#[test]
+fn sibling_requests_storage_for_its_user() {
+	Network::reset();
+	fund_sub_account();
+
+	let query_id = transact_from_sibling(request_storage(MSP.to_account_id()));
+
+	// Check that the file is requested by Alice's sub-account.
+	StorageHub::execute_with(|| {
+		System::assert_has_event(RuntimeEvent::PalletFileSystem(
+			pallet_file_system::Event::NewStorageRequest {
+				who: sub_account(),
+				location: location(),
+				fingerprint: fingerprint(),
+				size: 4,
+				msp: MSP.to_account_id(),
+				sender_multiaddress: sender_multiaddress(),
+			},
+		));
+		assert_eq!(StorageRequests::<Runtime>::get(location()).unwrap().owner, sub_account());
+	});
+
+	// Check that the sibling got notified of the success.
+	Sibling::execute_with(|| {
+		sibling::System::assert_has_event(sibling::RuntimeEvent::PolkadotXcm(
+			pallet_xcm::Event::ResponseReady {
+				query_id,
+				response: Response::DispatchResult(MaybeErrorCode::Success),
+			},
+		));
+	});
+}
+
+#[test]
+fn sibling_is_notified_of_failed_storage_requests() {
+	Network::reset();
+	fund_sub_account();
+
+	// Charlie is not an MSP.
+	let query_id = transact_from_sibling(request_storage(Charlie.to_account_id()));
+
+	StorageHub::execute_with(|| {
+		assert!(StorageRequests::<Runtime>::get(location()).is_none());
+	});
+
+	// Check that the sibling got notified of the failure.
+	Sibling::execute_with(|| {
+		let response = sibling::System::events()
+			.into_iter()
+			.find_map(|record| match record.event {
+				sibling::RuntimeEvent::PolkadotXcm(pallet_xcm::Event::ResponseReady {
+					query_id: id,
+					response,
+				}) if id == query_id => Some(response),
+				_ => None,
+			})
+			.expect("Status should be reported");
+		assert!(matches!(response, Response::DispatchResult(MaybeErrorCode::Error(_))));
+	});
+}
+
+#[test]
+fn siblings_can_only_transact_storage_requests() {
+	Network::reset();
+	fund_sub_account();
+
+	let transfer = RuntimeCall::Balances(pallet_balances::Call::transfer_allow_death {
+		dest: Eve.to_account_id().into(),
+		value: FEES,
+	});
+	let query_id = transact_from_sibling(transfer);
+
+	// Check that the transfer was not dispatched, nor reported.
+	StorageHub::execute_with(|| {
+		assert!(!System::events().iter().any(|record| matches!(
+			record.event,
+			RuntimeEvent::Balances(pallet_balances::Event::Transfer { ref to, .. })
+				if *to == Eve.to_account_id()
+		)));
+	});
+	Sibling::execute_with(|| {
+		assert!(!sibling::System::events().iter().any(|record| matches!(
+			record.event,
+			sibling::RuntimeEvent::PolkadotXcm(pallet_xcm::Event::ResponseReady { query_id: id, .. })
+				if id == query_id
+		)));
+	});
+}
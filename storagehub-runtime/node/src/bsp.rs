@@ -0,0 +1,336 @@
+//! Backup Storage Provider embedded in the node, instead of running the BSP of the
+//! `storagehub-client` next to it.
+//!
+//! The BSP volunteers for storage requests through the offchain worker of `pallet_file_system`,
+//! which submits the volunteers of the provider key of the node keystore once it is eligible. The
+//! BSP follows the blocks imported by the node through the client API, and fetches the files it
+//! volunteered for from their sender over the node's own network, reading the multiaddress of the
+//! sender that the pallet stores with each storage request.
+
+use crate::service::FullClient;
+use futures::StreamExt;
+use node_template_runtime::{
+	self as runtime, opaque::Block, AccountId, Hash, RuntimeEvent, StorageValue,
+};
+use pallet_file_system::{
+	types::{FileLocation, MultiAddress},
+	BSP_MULTIADDRESS_KEY, KEY_TYPE,
+};
+use sc_client_api::{BlockchainEvents, StorageProvider};
+use sc_network::{
+	config::{MultiaddrWithPeerId, RequestResponseConfig},
+	request_responses::IfDisconnected,
+	NetworkPeers, NetworkRequest, NetworkService, ProtocolName,
+};
+use sc_service::SpawnTaskHandle;
+use sp_api::{Decode, Encode};
+use sp_core::{
+	hashing::blake2_256,
+	hexdisplay::HexDisplay,
+	offchain::{OffchainStorage, STORAGE_PREFIX},
+	sr25519,
+	storage::StorageKey,
+};
+use sp_keystore::KeystorePtr;
+use std::{
+	fs::File,
+	io::Write,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::Duration,
+};
+
+/// Protocol files are requested with, one byte range at a time. Requests are the offset and
+/// length of the range followed by the location of the file, and responses the size of the file
+/// followed by the range, all integers as little endian `u64`.
+pub const FILE_TRANSFER_PROTOCOL: &str = "/storagehub/file/3";
+
+/// Length of the ranges files are requested in, so that they are never held in memory whole.
+const FILE_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// How many times in a row a range is requested before giving up on the file.
+const MAX_REQUEST_ATTEMPTS: u32 = 3;
+
+/// Size of the integer leading requests and responses.
+const HEADER_SIZE: usize = std::mem::size_of::<u64>();
+
+/// How long the sender of a file has to send a range of it.
+const FILE_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Configuration of the file transfer protocol, to register in the node's network.
+///
+/// The BSP only fetches files, so inbound requests are not accepted.
+pub fn file_transfer_protocol_config() -> RequestResponseConfig {
+	RequestResponseConfig {
+		name: FILE_TRANSFER_PROTOCOL.into(),
+		fallback_names: Vec::new(),
+		max_request_size: 1024,
+		max_response_size: HEADER_SIZE as u64 + FILE_CHUNK_SIZE,
+		request_timeout: FILE_REQUEST_TIMEOUT,
+		inbound_queue: None,
+	}
+}
+
+/// The BSP task.
+pub struct Bsp {
+	client: Arc<FullClient>,
+	network: Arc<NetworkService<Block, Hash>>,
+	/// Handle files are fetched with, each in its own task so that blocks keep being followed.
+	spawn_handle: SpawnTaskHandle,
+	/// Account of the provider key the offchain worker volunteers with.
+	account: AccountId,
+	/// Directory the files are stored in.
+	storage_path: PathBuf,
+}
+
+impl Bsp {
+	/// Create the BSP, for the first provider key of `keystore`.
+	///
+	/// `multiaddress` is written to `offchain_storage`, where the offchain worker reads the
+	/// multiaddress it volunteers with.
+	pub fn new(
+		client: Arc<FullClient>,
+		keystore: KeystorePtr,
+		mut offchain_storage: impl OffchainStorage,
+		network: Arc<NetworkService<Block, Hash>>,
+		spawn_handle: SpawnTaskHandle,
+		multiaddress: String,
+		storage_path: PathBuf,
+	) -> Result<Self, String> {
+		let provider: sr25519::Public =
+			*keystore.sr25519_public_keys(KEY_TYPE).first().ok_or(format!(
+				"No BSP key in the keystore, insert one with `key insert --key-type {}`",
+				String::from_utf8_lossy(&KEY_TYPE.0)
+			))?;
+		let multiaddress: MultiAddress<runtime::Runtime> = multiaddress
+			.into_bytes()
+			.try_into()
+			.map_err(|_| "The BSP multiaddress is too long".to_string())?;
+		offchain_storage.set(STORAGE_PREFIX, BSP_MULTIADDRESS_KEY, &multiaddress.encode());
+		std::fs::create_dir_all(&storage_path)
+			.map_err(|e| format!("Failed to create the BSP storage directory: {e}"))?;
+
+		Ok(Self { client, network, spawn_handle, account: provider.into(), storage_path })
+	}
+
+	/// Follow the imported blocks, and fetch the files the BSP volunteered for in them.
+	pub async fn run(self) {
+		log::info!(
+			"💾 Running BSP for account {}, storing files in {}",
+			self.account,
+			self.storage_path.display()
+		);
+
+		let mut imported_blocks = self.client.import_notification_stream();
+		while let Some(block) = imported_blocks.next().await {
+			if !block.is_new_best {
+				continue
+			}
+
+			for event in self.events(block.hash) {
+				match event {
+					RuntimeEvent::PalletFileSystem(
+						pallet_file_system::Event::NewBspVolunteer {
+							who,
+							location,
+							fingerprint,
+							..
+						},
+					) if who == self.account =>
+						self.handle_volunteer(block.header.parent_hash, location, fingerprint),
+					_ => {},
+				}
+			}
+		}
+	}
+
+	/// The events deposited in block `hash`.
+	fn events(&self, hash: Hash) -> Vec<RuntimeEvent> {
+		let key = StorageKey(frame_system::Events::<runtime::Runtime>::hashed_key().to_vec());
+
+		match self.client.storage(hash, &key) {
+			Ok(Some(events)) =>
+				Vec::<frame_system::EventRecord<RuntimeEvent, Hash>>::decode(&mut &events.0[..])
+					.map(|records| records.into_iter().map(|record| record.event).collect())
+					.unwrap_or_else(|e| {
+						log::error!("💾 Failed to decode the events of block {hash}: {e}");
+						Vec::new()
+					}),
+			Ok(None) => Vec::new(),
+			Err(e) => {
+				log::error!("💾 Failed to read the events of block {hash}: {e}");
+				Vec::new()
+			},
+		}
+	}
+
+	/// The multiaddress of the sender of the file at `location`, as stored on chain at block
+	/// `hash`.
+	fn sender_multiaddress(
+		&self,
+		hash: Hash,
+		location: &FileLocation<runtime::Runtime>,
+	) -> Option<MultiAddress<runtime::Runtime>> {
+		let key = StorageKey(
+			pallet_file_system::StorageRequestSenders::<runtime::Runtime>::hashed_key_for(location),
+		);
+
+		match self.client.storage(hash, &key) {
+			Ok(sender) => sender.and_then(|sender| Decode::decode(&mut &sender.0[..]).ok()),
+			Err(e) => {
+				log::error!("💾 Failed to read a storage request sender at block {hash}: {e}");
+				None
+			},
+		}
+	}
+
+	/// Fetch a file the offchain worker volunteered the BSP for from its sender in a new task, and
+	/// store it.
+	///
+	/// The sender is read at `parent_hash`, the block before the volunteer, as the storage request
+	/// and its sender are dropped in the block that fulfils it.
+	fn handle_volunteer(
+		&self,
+		parent_hash: Hash,
+		location: FileLocation<runtime::Runtime>,
+		fingerprint: Hash,
+	) {
+		let file_id = String::from_utf8_lossy(&location).into_owned();
+		log::info!("💾 Volunteered for file {file_id}");
+
+		let Some(sender_multiaddress) = self.sender_multiaddress(parent_hash, &location) else {
+			log::error!("💾 File {file_id} has no sender to fetch it from");
+			return
+		};
+		let Some(sender) = String::from_utf8(sender_multiaddress.into_inner())
+			.ok()
+			.and_then(|multiaddress| multiaddress.parse::<MultiaddrWithPeerId>().ok())
+		else {
+			log::error!("💾 Failed to parse the multiaddress of the sender of file {file_id}");
+			return
+		};
+
+		let network = self.network.clone();
+		let path = file_path(&self.storage_path, &location);
+		self.spawn_handle.spawn("bsp-fetch-file", Some("storagehub"), async move {
+			match fetch_file(&network, &sender, &location, &path, fingerprint).await {
+				Ok(()) => log::info!("💾 File {file_id} stored at {}", path.display()),
+				Err(e) => log::error!("💾 Failed to fetch file {file_id}: {e}"),
+			}
+		});
+	}
+}
+
+/// Fetch the file at `location` from `sender` range by range, dialing it if not connected yet.
+///
+/// Ranges are written to disk as they arrive, next to `path`, and the file is only moved to
+/// `path` once it matches `fingerprint`. A failed request is retried from the same offset,
+/// redialing the sender if the connection dropped.
+async fn fetch_file(
+	network: &NetworkService<Block, Hash>,
+	sender: &MultiaddrWithPeerId,
+	location: &[u8],
+	path: &Path,
+	fingerprint: Hash,
+) -> Result<(), String> {
+	network.add_known_address(sender.peer_id, sender.multiaddr.clone());
+
+	let mut partial_path = path.as_os_str().to_owned();
+	partial_path.push(".part");
+	let fetched = async {
+		let mut file = File::create(&partial_path).map_err(|e| e.to_string())?;
+		// Fingerprints are `BlakeTwo256` hashes, computed as ranges arrive.
+		let mut hasher = blake2b_simd::Params::new().hash_length(32).to_state();
+		let mut received: u64 = 0;
+		let mut failures = 0;
+
+		loop {
+			let request =
+				[&u64::to_le_bytes(received)[..], &u64::to_le_bytes(FILE_CHUNK_SIZE), location]
+					.concat();
+			let response = match network
+				.request(
+					sender.peer_id,
+					ProtocolName::from(FILE_TRANSFER_PROTOCOL),
+					request,
+					IfDisconnected::TryConnect,
+				)
+				.await
+			{
+				Ok(response) => response,
+				Err(e) if failures + 1 < MAX_REQUEST_ATTEMPTS => {
+					failures += 1;
+					log::warn!("💾 Failed to request a range at offset {received}, retrying: {e}");
+					continue
+				},
+				Err(e) => return Err(e.to_string()),
+			};
+			failures = 0;
+			if response.len() < HEADER_SIZE {
+				return Err("Response too short".to_string())
+			}
+			let (file_size, data) = response.split_at(HEADER_SIZE);
+			let file_size =
+				u64::from_le_bytes(file_size.try_into().expect("Split at the header size; qed"));
+
+			file.write_all(data).map_err(|e| e.to_string())?;
+			hasher.update(data);
+			received += data.len() as u64;
+
+			if received >= file_size {
+				break
+			}
+			// Every range but the last one is full, so the file was truncated while being sent.
+			if data.len() as u64 != FILE_CHUNK_SIZE {
+				return Err("Received a partial range before the end of the file".to_string())
+			}
+		}
+
+		if Hash::from_slice(hasher.finalize().as_bytes()) != fingerprint {
+			return Err("The file does not match its fingerprint".to_string())
+		}
+
+		std::fs::rename(&partial_path, path).map_err(|e| e.to_string())
+	}
+	.await;
+
+	if fetched.is_err() {
+		let _ = std::fs::remove_file(&partial_path);
+	}
+
+	fetched
+}
+
+/// Path the file at `location` is stored at in `storage_path`.
+///
+/// Locations are chosen by the users requesting storage, so files are named after the hex encoded
+/// `blake2_256` hash of their location rather than the location itself, which could otherwise
+/// point outside of `storage_path`.
+fn file_path(storage_path: &Path, location: &[u8]) -> PathBuf {
+	storage_path.join(format!("{}", HexDisplay::from(&blake2_256(location))))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn file_path_stays_in_storage_path() {
+		let storage_path = Path::new("/data/bsp");
+
+		for location in [&b"../../etc/passwd"[..], b"/etc/passwd", b"a/../../b", b"..", b""] {
+			let path = file_path(storage_path, location);
+
+			assert_eq!(path.parent(), Some(storage_path));
+			assert_eq!(path.file_name().unwrap().len(), 64);
+		}
+	}
+
+	#[test]
+	fn file_path_is_unique_per_location() {
+		let storage_path = Path::new("/data/bsp");
+
+		assert_eq!(file_path(storage_path, b"file"), file_path(storage_path, b"file"));
+		assert_ne!(file_path(storage_path, b"file"), file_path(storage_path, b"/file"));
+	}
+}
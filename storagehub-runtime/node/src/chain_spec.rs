@@ -1,7 +1,10 @@
 use node_template_runtime::{
-	AccountId, AuraConfig, BalancesConfig, GrandpaConfig, RuntimeGenesisConfig, Signature,
-	SudoConfig, SystemConfig, WASM_BINARY,
+	AccountId, AuraConfig, Balance, BalancesConfig, GrandpaConfig, MinProviderStake,
+	PalletFileSystemConfig, PalletIdentityConfig, PalletMspIdentityConfig, PalletReputationConfig,
+	ProviderCouncilMembershipConfig, Runtime, RuntimeGenesisConfig, Signature, SudoConfig,
+	SystemConfig, WASM_BINARY,
 };
+use pallet_file_system::types::PeerId;
 use sc_service::ChainType;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_consensus_grandpa::AuthorityId as GrandpaId;
@@ -36,6 +39,31 @@ pub fn authority_keys_from_seed(s: &str) -> (AuraId, GrandpaId) {
 	(get_from_seed::<AuraId>(s), get_from_seed::<GrandpaId>(s))
 }
 
+/// Members of the provider council in development chains.
+pub fn default_provider_council() -> Vec<AccountId> {
+	vec![
+		get_account_id_from_seed::<sr25519::Public>("Alice"),
+		get_account_id_from_seed::<sr25519::Public>("Bob"),
+	]
+}
+
+/// BSPs registered from genesis in development chains.
+pub fn default_bsps() -> Vec<AccountId> {
+	vec![
+		get_account_id_from_seed::<sr25519::Public>("Alice"),
+		get_account_id_from_seed::<sr25519::Public>("Bob"),
+		get_account_id_from_seed::<sr25519::Public>("Charlie"),
+	]
+}
+
+/// Balance of the accounts endowed in development chains.
+const DEV_ENDOWMENT: Balance = 1 << 60;
+
+/// MSPs registered from genesis in development chains.
+pub fn default_msps() -> Vec<AccountId> {
+	vec![get_account_id_from_seed::<sr25519::Public>("Dave")]
+}
+
 pub fn development_config() -> Result<ChainSpec, String> {
 	let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?;
 
@@ -60,7 +88,20 @@ pub fn development_config() -> Result<ChainSpec, String> {
 					get_account_id_from_seed::<sr25519::Public>("Dave"),
 					get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
 					get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
-				],
+				]
+				.into_iter()
+				.map(|k| (k, DEV_ENDOWMENT))
+				.collect(),
+				// Provider council members
+				default_provider_council(),
+				// Pre-registered BSPs
+				default_bsps(),
+				// Pre-registered MSPs
+				default_msps(),
+				// PeerIds registered from genesis
+				vec![],
+				// Assignment threshold
+				None,
 				true,
 			)
 		},
@@ -108,7 +149,20 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 					get_account_id_from_seed::<sr25519::Public>("Dave//stash"),
 					get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
 					get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
-				],
+				]
+				.into_iter()
+				.map(|k| (k, DEV_ENDOWMENT))
+				.collect(),
+				// Provider council members
+				default_provider_council(),
+				// Pre-registered BSPs
+				default_bsps(),
+				// Pre-registered MSPs
+				default_msps(),
+				// PeerIds registered from genesis
+				vec![],
+				// Assignment threshold
+				None,
 				true,
 			)
 		},
@@ -127,11 +181,17 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 }
 
 /// Configure initial storage state for FRAME modules.
-fn testnet_genesis(
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn testnet_genesis(
 	wasm_binary: &[u8],
 	initial_authorities: Vec<(AuraId, GrandpaId)>,
 	root_key: AccountId,
-	endowed_accounts: Vec<AccountId>,
+	endowed_accounts: Vec<(AccountId, Balance)>,
+	provider_council: Vec<AccountId>,
+	initial_bsps: Vec<AccountId>,
+	initial_msps: Vec<AccountId>,
+	peers: Vec<(AccountId, PeerId<Runtime>)>,
+	assignment_threshold: Option<u128>,
 	_enable_println: bool,
 ) -> RuntimeGenesisConfig {
 	RuntimeGenesisConfig {
@@ -140,10 +200,7 @@ fn testnet_genesis(
 			code: wasm_binary.to_vec(),
 			..Default::default()
 		},
-		balances: BalancesConfig {
-			// Configure endowed accounts with initial balance of 1 << 60.
-			balances: endowed_accounts.iter().cloned().map(|k| (k, 1 << 60)).collect(),
-		},
+		balances: BalancesConfig { balances: endowed_accounts },
 		aura: AuraConfig {
 			authorities: initial_authorities.iter().map(|x| (x.0.clone())).collect(),
 		},
@@ -156,5 +213,33 @@ fn testnet_genesis(
 			key: Some(root_key),
 		},
 		transaction_payment: Default::default(),
+		// Council members are initialised by the membership pallet.
+		provider_council: Default::default(),
+		provider_council_membership: ProviderCouncilMembershipConfig {
+			members: provider_council.try_into().expect("Too many provider council members"),
+			..Default::default()
+		},
+		pallet_reputation: PalletReputationConfig {
+			// Bond the stake BSPs need to volunteer.
+			stakes: initial_bsps.iter().map(|bsp| (bsp.clone(), MinProviderStake::get())).collect(),
+		},
+		pallet_identity: PalletIdentityConfig {
+			// Register BSPs so they can volunteer right away.
+			users: initial_bsps,
+			..Default::default()
+		},
+		pallet_msp_identity: PalletMspIdentityConfig {
+			// Register MSPs so they can be picked right away.
+			users: initial_msps,
+			..Default::default()
+		},
+		pallet_file_system: PalletFileSystemConfig {
+			// No files stored from genesis.
+			files: vec![],
+			// Falls back to `MinBspsAssignmentThreshold` if not set.
+			assignment_threshold,
+			// Let nodes serving private files identify each other.
+			peers,
+		},
 	}
 }
@@ -0,0 +1,243 @@
+//! Chain specifications built from a TOML or JSON description of the network, so networks can be
+//! spun up without editing [`crate::chain_spec`].
+//!
+//! A description looks like this in TOML:
+//!
+//! ```toml
+//! name = "Staging"
+//! id = "staging"
+//! chain_type = "Live"
+//! sudo = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+//! provider_council = ["5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"]
+//!
+//! [[authorities]]
+//! aura = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+//! grandpa = "5FA9nQDVg267DEd8m1ZypXLBnvN7SFxYwV7ndqSYGiN9TTpu"
+//!
+//! [[endowed_accounts]]
+//! account = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+//! balance = 1_000_000_000_000_000
+//!
+//! [[bsps]]
+//! account = "5FLSigC9HGRKVhB9FiEo4Y3koPsNmBmLJbpXg2mp1hXcS59Y"
+//! balance = 1_000_000_000_000_000
+//! peer_ids = ["12D3KooWEyoppNCUx8Yx66oV9fJnriXwCcXwDDUA2kj6vnc6iDEp"]
+//!
+//! [file_system]
+//! assignment_threshold = 1_000_000_000_000_000
+//! ```
+
+use crate::chain_spec::{testnet_genesis, ChainSpec};
+use node_template_runtime::{AccountId, Balance, Runtime, WASM_BINARY};
+use pallet_file_system::types::PeerId;
+use sc_network::{config::MultiaddrWithPeerId, PeerId as Libp2pPeerId};
+use sc_service::ChainType;
+use serde::Deserialize;
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_consensus_grandpa::AuthorityId as GrandpaId;
+use std::{path::PathBuf, str::FromStr};
+
+/// The `build-custom-spec` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct BuildCustomSpecCmd {
+	/// Description of the network, a TOML file if its extension is `.toml` and JSON otherwise.
+	pub description: PathBuf,
+
+	/// Output the chain spec in raw storage format.
+	#[arg(long)]
+	pub raw: bool,
+
+	/// File to write the chain spec to, instead of the standard output.
+	#[arg(long, short)]
+	pub output: Option<PathBuf>,
+}
+
+impl BuildCustomSpecCmd {
+	/// Run the command.
+	pub fn run(&self) -> sc_cli::Result<()> {
+		let description = std::fs::read_to_string(&self.description)?;
+		let description: ChainDescription =
+			if self.description.extension().map_or(false, |ext| ext == "toml") {
+				toml::from_str(&description).map_err(|e| e.to_string())?
+			} else {
+				serde_json::from_str(&description).map_err(|e| e.to_string())?
+			};
+
+		let json = chain_spec_from_description(description)?.as_json(self.raw)?;
+
+		match &self.output {
+			Some(output) => std::fs::write(output, json)?,
+			None => println!("{json}"),
+		}
+
+		Ok(())
+	}
+}
+
+/// Description of a network to build a chain spec for.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainDescription {
+	/// Name of the chain.
+	pub name: String,
+	/// Identifier of the chain, also used as protocol ID.
+	pub id: String,
+	/// Type of the chain.
+	#[serde(default = "default_chain_type")]
+	pub chain_type: ChainType,
+	/// Initial PoA authorities.
+	pub authorities: Vec<AuthorityDescription>,
+	/// Sudo account.
+	pub sudo: AccountId,
+	/// Pre-funded accounts, besides the providers.
+	#[serde(default)]
+	pub endowed_accounts: Vec<EndowedAccount>,
+	/// Provider council members.
+	#[serde(default)]
+	pub provider_council: Vec<AccountId>,
+	/// Pre-registered BSPs.
+	#[serde(default)]
+	pub bsps: Vec<ProviderProfile>,
+	/// Pre-registered MSPs.
+	#[serde(default)]
+	pub msps: Vec<ProviderProfile>,
+	/// Genesis parameters of the file system.
+	#[serde(default)]
+	pub file_system: FileSystemParameters,
+	/// Bootnodes, as multiaddresses ending with a `/p2p/` component.
+	#[serde(default)]
+	pub boot_nodes: Vec<String>,
+}
+
+/// The session keys of an authority.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthorityDescription {
+	/// Aura key, used to author blocks.
+	pub aura: AuraId,
+	/// GRANDPA key, used to finalize blocks.
+	pub grandpa: GrandpaId,
+}
+
+/// An account funded from genesis.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndowedAccount {
+	/// The funded account.
+	pub account: AccountId,
+	/// Its initial balance.
+	pub balance: Balance,
+}
+
+/// A provider registered from genesis.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProviderProfile {
+	/// Account of the provider.
+	pub account: AccountId,
+	/// Balance the provider is funded with, if any.
+	#[serde(default)]
+	pub balance: Option<Balance>,
+	/// libp2p PeerIds of the nodes operated by the provider, in base58.
+	#[serde(default)]
+	pub peer_ids: Vec<String>,
+}
+
+/// Genesis parameters of the file system.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileSystemParameters {
+	/// Initial BSPs assignment threshold, `MinBspsAssignmentThreshold` if not set.
+	#[serde(default)]
+	pub assignment_threshold: Option<u128>,
+}
+
+/// Chains are local testnets unless described otherwise.
+fn default_chain_type() -> ChainType {
+	ChainType::Local
+}
+
+/// Build the chain spec of the network described by `description`.
+pub fn chain_spec_from_description(description: ChainDescription) -> Result<ChainSpec, String> {
+	let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?;
+
+	let boot_nodes = description
+		.boot_nodes
+		.iter()
+		.map(|node| {
+			node.parse::<MultiaddrWithPeerId>()
+				.map_err(|e| format!("Invalid bootnode {node}: {e}"))
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+
+	// Check the PeerIds up front, as the genesis is only built when the chain spec is output.
+	let mut peers = Vec::new();
+	for provider in description.bsps.iter().chain(&description.msps) {
+		for encoded in &provider.peer_ids {
+			let peer_id = Libp2pPeerId::from_str(encoded)
+				.map_err(|e| format!("Invalid PeerId {encoded}: {e}"))?;
+			let peer_id = PeerId::<Runtime>::try_from(peer_id.to_bytes())
+				.map_err(|_| format!("PeerId {encoded} is too long"))?;
+			peers.push((provider.account.clone(), peer_id));
+		}
+	}
+
+	let endowed_accounts: Vec<_> = description
+		.endowed_accounts
+		.iter()
+		.map(|endowed| (endowed.account.clone(), endowed.balance))
+		.chain(
+			description
+				.bsps
+				.iter()
+				.chain(&description.msps)
+				.filter_map(|provider| Some((provider.account.clone(), provider.balance?))),
+		)
+		.collect();
+
+	let (name, id, chain_type) =
+		(description.name.clone(), description.id.clone(), description.chain_type.clone());
+
+	Ok(ChainSpec::from_genesis(
+		&name,
+		&id,
+		chain_type,
+		move || {
+			testnet_genesis(
+				wasm_binary,
+				// Initial PoA authorities
+				description
+					.authorities
+					.iter()
+					.map(|authority| (authority.aura.clone(), authority.grandpa.clone()))
+					.collect(),
+				// Sudo account
+				description.sudo.clone(),
+				// Pre-funded accounts
+				endowed_accounts.clone(),
+				// Provider council members
+				description.provider_council.clone(),
+				// Pre-registered BSPs
+				description.bsps.iter().map(|bsp| bsp.account.clone()).collect(),
+				// Pre-registered MSPs
+				description.msps.iter().map(|msp| msp.account.clone()).collect(),
+				// PeerIds registered from genesis
+				peers.clone(),
+				// Assignment threshold
+				description.file_system.assignment_threshold,
+				false,
+			)
+		},
+		// Bootnodes
+		boot_nodes,
+		// Telemetry
+		None,
+		// Protocol ID
+		Some(id.as_str()),
+		None,
+		// Properties
+		None,
+		// Extensions
+		None,
+	))
+}
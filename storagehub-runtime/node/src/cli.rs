@@ -1,4 +1,6 @@
+use crate::{chain_spec_builder::BuildCustomSpecCmd, metadata::ExportMetadataCmd};
 use sc_cli::RunCmd;
+use std::path::PathBuf;
 
 #[derive(Debug, clap::Parser)]
 pub struct Cli {
@@ -7,6 +9,42 @@ pub struct Cli {
 
 	#[clap(flatten)]
 	pub run: RunCmd,
+
+	/// Seal blocks as soon as transactions arrive (`instant`), or when requested through the
+	/// `engine_createBlock` RPC (`manual`), instead of authoring them with Aura. Sealed blocks are
+	/// finalized right away, without GRANDPA. Meant for development and tests.
+	#[arg(long, value_enum, ignore_case = true)]
+	pub sealing: Option<Sealing>,
+
+	#[clap(flatten)]
+	pub bsp: BspParams,
+}
+
+/// Parameters of the BSP embedded in the node.
+#[derive(Debug, Clone, clap::Args)]
+pub struct BspParams {
+	/// Run a BSP in the node, volunteering with the `bsp_` sr25519 key of the node keystore through
+	/// the offchain worker, instead of running the BSP of `storagehub-client` next to it.
+	#[arg(long = "bsp")]
+	pub enabled: bool,
+
+	/// Directory the BSP stores files in. Defaults to `bsp` in the chain's data directory.
+	#[arg(long, requires = "enabled")]
+	pub bsp_storage_path: Option<PathBuf>,
+
+	/// Multiaddress users reach the BSP at, without the `/p2p/` component. Defaults to the first
+	/// `--public-addr` of the node, and is required without one.
+	#[arg(long, requires = "enabled")]
+	pub bsp_multiaddress: Option<String>,
+}
+
+/// How blocks are sealed when not authored with Aura.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+pub enum Sealing {
+	/// Seal a block as soon as a transaction enters the pool.
+	Instant,
+	/// Seal a block when requested through the `engine_createBlock` RPC.
+	Manual,
 }
 
 #[derive(Debug, clap::Subcommand)]
@@ -19,6 +57,9 @@ pub enum Subcommand {
 	/// Build a chain specification.
 	BuildSpec(sc_cli::BuildSpecCmd),
 
+	/// Build a chain specification from a TOML or JSON description of the network.
+	BuildCustomSpec(BuildCustomSpecCmd),
+
 	/// Validate blocks.
 	CheckBlock(sc_cli::CheckBlockCmd),
 
@@ -37,6 +78,9 @@ pub enum Subcommand {
 	/// Revert the chain to a previous state.
 	Revert(sc_cli::RevertCmd),
 
+	/// Export the runtime metadata for the client node's `subxt` codegen.
+	ExportMetadata(ExportMetadataCmd),
+
 	/// Sub-commands concerned with benchmarking.
 	#[command(subcommand)]
 	Benchmark(frame_benchmarking_cli::BenchmarkCmd),
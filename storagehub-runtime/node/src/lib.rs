@@ -1,3 +1,7 @@
+pub mod bsp;
 pub mod chain_spec;
+pub mod chain_spec_builder;
+pub mod cli;
+pub mod metadata;
 pub mod rpc;
 pub mod service;
@@ -2,11 +2,14 @@
 #![warn(missing_docs)]
 
 mod chain_spec;
+mod chain_spec_builder;
 #[macro_use]
 mod service;
 mod benchmarking;
+mod bsp;
 mod cli;
 mod command;
+mod metadata;
 mod rpc;
 
 fn main() -> sc_cli::Result<()> {
@@ -0,0 +1,77 @@
+//! Export of the runtime metadata used by the client node's `subxt` codegen.
+
+use sc_executor::WasmExecutor;
+use sp_api::{Decode, Encode};
+use sp_core::{
+	traits::{CallContext, CodeExecutor, RuntimeCode, WrappedRuntimeCode},
+	OpaqueMetadata,
+};
+use std::path::PathBuf;
+
+/// Version of the metadata read by the client node.
+const METADATA_VERSION: u32 = 15;
+
+/// The `export-metadata` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ExportMetadataCmd {
+	/// File to write the SCALE encoded metadata to.
+	#[arg(default_value = "../storagehub-client-node/metadata/local.scale")]
+	pub output: PathBuf,
+
+	/// Fail if the file does not hold the metadata of the runtime, instead of writing it.
+	#[arg(long)]
+	pub check: bool,
+}
+
+impl ExportMetadataCmd {
+	/// Run the command.
+	pub fn run(&self) -> sc_cli::Result<()> {
+		let metadata = runtime_metadata()?;
+
+		if self.check {
+			let current = std::fs::read(&self.output)?;
+			if current != metadata {
+				return Err(format!(
+					"The metadata in {} is stale, run `node-template export-metadata {}` to update it",
+					self.output.display(),
+					self.output.display()
+				)
+				.into())
+			}
+
+			return Ok(())
+		}
+
+		std::fs::write(&self.output, metadata)?;
+
+		Ok(())
+	}
+}
+
+/// The metadata of the runtime WASM embedded in the node, without running a chain.
+fn runtime_metadata() -> sc_cli::Result<Vec<u8>> {
+	let wasm = node_template_runtime::WASM_BINARY
+		.ok_or("Development wasm not available, build the node without `SKIP_WASM_BUILD`")?;
+
+	let executor = WasmExecutor::<sp_io::SubstrateHostFunctions>::builder().build();
+	let runtime_code = RuntimeCode {
+		code_fetcher: &WrappedRuntimeCode(wasm.into()),
+		heap_pages: None,
+		hash: sp_core::blake2_256(wasm).to_vec(),
+	};
+	let mut ext = sp_io::TestExternalities::default();
+
+	let (result, _) = executor.call(
+		&mut ext.ext(),
+		&runtime_code,
+		"Metadata_metadata_at_version",
+		&METADATA_VERSION.encode(),
+		false,
+		CallContext::Offchain,
+	);
+	let metadata = <Option<OpaqueMetadata>>::decode(&mut &result.map_err(|e| e.to_string())?[..])
+		.map_err(|e| format!("Failed to decode the runtime metadata: {e}"))?
+		.ok_or(format!("The runtime does not support metadata version {METADATA_VERSION}"))?;
+
+	Ok(metadata.to_vec())
+}
@@ -1,15 +1,24 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
-use futures::FutureExt;
+use crate::{
+	bsp::{self, Bsp},
+	cli::{BspParams, Sealing},
+};
+use futures::{FutureExt, StreamExt};
 use node_template_runtime::{self, opaque::Block, RuntimeApi};
 use sc_client_api::{Backend, BlockBackend};
 use sc_consensus_aura::{ImportQueueParams, SlotProportion, StartAuraParams};
 use sc_consensus_grandpa::SharedVoterState;
+use sc_consensus_manual_seal::{
+	consensus::aura::AuraConsensusDataProvider, EngineCommand, InstantSealParams, ManualSealParams,
+};
 pub use sc_executor::NativeElseWasmExecutor;
+use sc_network::{config::NetworkConfiguration, multiaddr::Protocol, Multiaddr, NetworkStateInfo};
 use sc_service::{error::Error as ServiceError, Configuration, TaskManager, WarpSyncParams};
 use sc_telemetry::{Telemetry, TelemetryWorker};
 use sc_transaction_pool_api::OffchainTransactionPoolFactory;
-use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
+use sp_blockchain::HeaderBackend;
+use sp_consensus_aura::sr25519::{AuthorityPair as AuraPair, AuthoritySignature as AuraSignature};
 use std::{sync::Arc, time::Duration};
 
 // Our native executor instance.
@@ -37,9 +46,13 @@ pub(crate) type FullClient =
 type FullBackend = sc_service::TFullBackend<Block>;
 type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
 
+/// Builds the components shared by the full node and the subcommands.
+///
+/// Blocks sealed with `sealing` carry no Aura seal, so they are imported without verification.
 #[allow(clippy::type_complexity)]
 pub fn new_partial(
 	config: &Configuration,
+	sealing: Option<Sealing>,
 ) -> Result<
 	sc_service::PartialComponents<
 		FullClient,
@@ -104,7 +117,13 @@ pub fn new_partial(
 
 	let slot_duration = sc_consensus_aura::slot_duration(&*client)?;
 
-	let import_queue =
+	let import_queue = if sealing.is_some() {
+		sc_consensus_manual_seal::import_queue(
+			Box::new(client.clone()),
+			&task_manager.spawn_essential_handle(),
+			config.prometheus_registry(),
+		)
+	} else {
 		sc_consensus_aura::import_queue::<AuraPair, _, _, _, _, _>(ImportQueueParams {
 			block_import: grandpa_block_import.clone(),
 			justification_import: Some(Box::new(grandpa_block_import.clone())),
@@ -125,7 +144,8 @@ pub fn new_partial(
 			check_for_equivocation: Default::default(),
 			telemetry: telemetry.as_ref().map(|x| x.handle()),
 			compatibility_mode: Default::default(),
-		})?;
+		})?
+	};
 
 	Ok(sc_service::PartialComponents {
 		client,
@@ -139,8 +159,56 @@ pub fn new_partial(
 	})
 }
 
+/// The multiaddress users reach the BSP at, without its `/p2p/` component.
+///
+/// Defaults to the first public address of the node. Listen addresses are not used, as they are
+/// usually unspecified, e.g. `/ip4/0.0.0.0`, which users cannot dial.
+fn bsp_multiaddress(
+	bsp: &BspParams,
+	network: &NetworkConfiguration,
+) -> Result<Multiaddr, ServiceError> {
+	let multiaddress = match &bsp.bsp_multiaddress {
+		Some(multiaddress) => multiaddress
+			.parse::<Multiaddr>()
+			.map_err(|e| ServiceError::Other(format!("Invalid BSP multiaddress: {e}")))?,
+		None => network.public_addresses.first().cloned().ok_or_else(|| {
+			ServiceError::Other(
+				"The BSP needs an address users can reach, pass `--bsp-multiaddress` or \
+				 `--public-addr`"
+					.into(),
+			)
+		})?,
+	};
+
+	// The local peer ID is appended once the network is started.
+	let multiaddress: Multiaddr = multiaddress
+		.iter()
+		.filter(|protocol| !matches!(protocol, Protocol::P2p(_)))
+		.collect();
+
+	let is_unspecified = multiaddress.iter().any(|protocol| match protocol {
+		Protocol::Ip4(ip) => ip.is_unspecified(),
+		Protocol::Ip6(ip) => ip.is_unspecified(),
+		_ => false,
+	});
+	if is_unspecified {
+		return Err(ServiceError::Other(format!(
+			"The BSP multiaddress {multiaddress} cannot be dialed, pass a public address instead"
+		)))
+	}
+
+	Ok(multiaddress)
+}
+
 /// Builds a new service for a full client.
-pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
+///
+/// With `sealing`, the node seals and finalizes blocks itself instead of running Aura and GRANDPA.
+/// With `bsp` enabled, it also runs a BSP.
+pub fn new_full(
+	config: Configuration,
+	sealing: Option<Sealing>,
+	bsp: BspParams,
+) -> Result<TaskManager, ServiceError> {
 	let sc_service::PartialComponents {
 		client,
 		backend,
@@ -150,7 +218,7 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		select_chain,
 		transaction_pool,
 		other: (block_import, grandpa_link, mut telemetry),
-	} = new_partial(&config)?;
+	} = new_partial(&config, sealing)?;
 
 	let mut net_config = sc_network::config::FullNetworkConfiguration::new(&config.network);
 
@@ -161,6 +229,9 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 	net_config.add_notification_protocol(sc_consensus_grandpa::grandpa_peers_set_config(
 		grandpa_protocol_name.clone(),
 	));
+	if bsp.enabled {
+		net_config.add_request_response_protocol(bsp::file_transfer_protocol_config());
+	}
 
 	let warp_sync = Arc::new(sc_consensus_grandpa::warp_proof::NetworkProvider::new(
 		backend.clone(),
@@ -207,14 +278,27 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 	let name = config.network.node_name.clone();
 	let enable_grandpa = !config.disable_grandpa;
 	let prometheus_registry = config.prometheus_registry().cloned();
+	let bsp_multiaddress =
+		if bsp.enabled { Some(bsp_multiaddress(&bsp, &config.network)?) } else { None };
+	let bsp_storage_path = bsp.bsp_storage_path.unwrap_or_else(|| config.data_path.join("bsp"));
+	let offchain_worker_enabled = config.offchain_worker.enabled;
+	let offchain_storage = backend.offchain_storage();
+
+	// In manual sealing mode, blocks are sealed when requested through the RPC.
+	let (command_sink, commands_stream) = futures::channel::mpsc::channel(1024);
+	let command_sink = (sealing == Some(Sealing::Manual)).then_some(command_sink);
 
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
 
 		Box::new(move |deny_unsafe, _| {
-			let deps =
-				crate::rpc::FullDeps { client: client.clone(), pool: pool.clone(), deny_unsafe };
+			let deps = crate::rpc::FullDeps {
+				client: client.clone(),
+				pool: pool.clone(),
+				deny_unsafe,
+				command_sink: command_sink.clone(),
+			};
 			crate::rpc::create_full(deps).map_err(Into::into)
 		})
 	};
@@ -234,6 +318,112 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		telemetry: telemetry.as_mut(),
 	})?;
 
+	if let Some(multiaddress) = bsp_multiaddress {
+		// The offchain worker volunteers for storage requests on behalf of the BSP.
+		let offchain_storage =
+			offchain_storage.filter(|_| offchain_worker_enabled).ok_or_else(|| {
+				ServiceError::Other(
+					"The BSP needs offchain workers, run the node with `--offchain-worker always`"
+						.into(),
+				)
+			})?;
+		let bsp = Bsp::new(
+			client.clone(),
+			keystore_container.keystore(),
+			offchain_storage,
+			network.clone(),
+			task_manager.spawn_handle(),
+			format!("{multiaddress}/p2p/{}", network.local_peer_id()),
+			bsp_storage_path,
+		)
+		.map_err(ServiceError::Other)?;
+
+		task_manager.spawn_handle().spawn("bsp", Some("storagehub"), bsp.run());
+	}
+
+	if let Some(sealing) = sealing {
+		let proposer_factory = sc_basic_authorship::ProposerFactory::new(
+			task_manager.spawn_handle(),
+			client.clone(),
+			transaction_pool.clone(),
+			prometheus_registry.as_ref(),
+			telemetry.as_ref().map(|x| x.handle()),
+		);
+
+		let slot_duration = sc_consensus_aura::slot_duration(&*client)?;
+		let create_inherent_data_providers = {
+			let client = client.clone();
+			move |parent, ()| {
+				let client = client.clone();
+				async move {
+					// Blocks can be sealed faster than slots pass, but the runtime still expects
+					// one Aura slot per block, so each block gets at least the timestamp of the
+					// slot after its parent's.
+					let parent_header = client
+						.header(parent)
+						.map_err(|e| e.to_string())?
+						.ok_or("Parent header should exist")?;
+					let parent_slot =
+						sc_consensus_aura::find_pre_digest::<Block, AuraSignature>(&parent_header)
+							.map_err(|e| e.to_string())?;
+					let timestamp = sp_timestamp::Timestamp::current()
+						.max((*parent_slot + 1).saturating_mul(slot_duration.as_millis()).into());
+
+					Ok::<_, Box<dyn std::error::Error + Send + Sync>>(
+						sp_timestamp::InherentDataProvider::new(timestamp),
+					)
+				}
+			}
+		};
+		let consensus_data_provider = AuraConsensusDataProvider::new(client.clone());
+
+		let authorship = match sealing {
+			Sealing::Instant =>
+				sc_consensus_manual_seal::run_instant_seal_and_finalize(InstantSealParams {
+					block_import: client.clone(),
+					env: proposer_factory,
+					client,
+					pool: transaction_pool,
+					select_chain,
+					consensus_data_provider: Some(Box::new(consensus_data_provider)),
+					create_inherent_data_providers,
+				})
+				.boxed(),
+			Sealing::Manual => sc_consensus_manual_seal::run_manual_seal(ManualSealParams {
+				block_import: client.clone(),
+				env: proposer_factory,
+				client,
+				pool: transaction_pool,
+				// Blocks sealed through the RPC are finalized right away.
+				commands_stream: commands_stream.map(|command| match command {
+					EngineCommand::SealNewBlock { create_empty, parent_hash, sender, .. } =>
+						EngineCommand::SealNewBlock {
+							create_empty,
+							finalize: true,
+							parent_hash,
+							sender,
+						},
+					command => command,
+				}),
+				select_chain,
+				consensus_data_provider: Some(Box::new(consensus_data_provider)),
+				create_inherent_data_providers,
+			})
+			.boxed(),
+		};
+
+		// the sealing task is considered essential, i.e. if it
+		// fails we take down the service with it.
+		task_manager.spawn_essential_handle().spawn_blocking(
+			"manual-seal",
+			Some("block-authoring"),
+			authorship,
+		);
+
+		network_starter.start_network();
+		return Ok(task_manager)
+	}
+
 	if role.is_authority() {
 		let proposer_factory = sc_basic_authorship::ProposerFactory::new(
 			task_manager.spawn_handle(),
@@ -1,35 +1,581 @@
-//! Benchmarking setup for pallet-template
+//! Benchmarking setup for pallet-file-system
 #![cfg(feature = "runtime-benchmarks")]
 use super::*;
 
+use crate::types::*;
 #[allow(unused)]
-use crate::Pallet as Template;
+use crate::Pallet as FileSystem;
 use frame_benchmarking::v2::*;
-use frame_system::RawOrigin;
+use frame_support::{
+	sp_runtime::traits::{Bounded, Hash},
+	traits::{Currency, EnsureOrigin, Get, NamedReservableCurrency},
+	BoundedVec,
+};
+use frame_system::{pallet_prelude::BlockNumberFor, RawOrigin};
+use pallet_identity::IdentityInterface;
+use pallet_reputation::ReputationInterface;
+use scale_info::prelude::{format, vec, vec::Vec};
+use sp_core::ed25519;
+
+fn location<T: Config>(i: u32) -> FileLocation<T> {
+	// Longest possible path, to account for the worst case of hashing the storage keys.
+	let mut location = format!("file-{i}-").into_bytes();
+	location.resize(T::MaxFilePathSize::get() as usize, b'x');
+	location.try_into().expect("Location should fit in MaxFilePathSize")
+}
+
+fn fingerprint<T: Config>(i: u32) -> Fingerprint<T> {
+	let hash = <T as frame_system::Config>::Hashing::hash_of(&i);
+	let mut fingerprint = Fingerprint::<T>::default();
+	let len = fingerprint.as_ref().len().min(hash.as_ref().len());
+	fingerprint.as_mut()[..len].copy_from_slice(&hash.as_ref()[..len]);
+	fingerprint
+}
+
+fn multiaddress<T: Config>() -> MultiAddress<T> {
+	// Longest possible multiaddress, to account for the worst case of validating it.
+	let suffix = b"/tcp/30333/p2p/12D3KooWBeY4xRNxeqgiXDgesLyfoRjpyqP1HXwFwyTdY6q9bUTA";
+	let mut multiaddress = b"/dns/".to_vec();
+	multiaddress.resize(T::MaxMultiAddressSize::get() as usize - suffix.len(), b'x');
+	multiaddress.extend_from_slice(suffix);
+	multiaddress.try_into().expect("Multiaddress should fit in MaxMultiAddressSize")
+}
+
+/// A PeerId derived from a new ed25519 key, with the signature of its registration for `who`.
+fn peer_id<T: Config>(who: &T::AccountId) -> (PeerId<T>, ed25519::Signature) {
+	let public = sp_io::crypto::ed25519_generate(KEY_TYPE, None);
+	let signature = sp_io::crypto::ed25519_sign(
+		KEY_TYPE,
+		&public,
+		&FileSystem::<T>::peer_id_registration_payload(who),
+	)
+	.expect("The key was just generated in the keystore");
+
+	let mut peer_id = vec![0x00, 0x24, 0x08, 0x01, 0x12, 0x20];
+	peer_id.extend_from_slice(public.as_ref());
+	(peer_id.try_into().expect("PeerId should fit in MaxPeerIdSize"), signature)
+}
+
+/// Let `who` store as many files as needed, reading its quota from storage.
+fn unlimited_quota<T: Config>(who: &T::AccountId) {
+	UserQuotas::<T>::insert(
+		who,
+		StorageQuota::<T> { max_bytes: StorageCount::<T>::max_value(), max_files: u32::MAX },
+	);
+}
+
+/// Give `who` enough balance to pay any retrieval fee, and return the fee to pay.
+fn retrieval_fee<T: Config>(who: &T::AccountId) -> BalanceOf<T> {
+	T::Currency::make_free_balance_be(who, BalanceOf::<T>::max_value() / 2u32.into());
+	T::Currency::minimum_balance().max(1u32.into()) * 10u32.into()
+}
+
+/// Reserve `fee` from `requester` and register its request to retrieve the file at `location`
+/// from `bsp`, delivered at `delivered_at`.
+fn retrieval_request<T: Config>(
+	location: &FileLocation<T>,
+	requester: &T::AccountId,
+	bsp: T::AccountId,
+	fee: BalanceOf<T>,
+	delivered_at: Option<BlockNumberFor<T>>,
+) {
+	T::Currency::reserve_named(&T::RetrievalReserveId::get(), requester, fee)
+		.expect("Requester should afford the fee");
+	RetrievalRequests::<T>::insert(
+		location,
+		requester,
+		RetrievalRequest::<T> {
+			bsp,
+			fee,
+			requested_at: frame_system::Pallet::<T>::block_number(),
+			delivered_at,
+		},
+	);
+}
+
+/// Register an account as MSP, so it can be picked for files.
+fn msp<T: Config>() -> Result<T::AccountId, BenchmarkError> {
+	let msp: T::AccountId = account("msp", 0, 0);
+	T::MspsRegistry::register_user(msp.clone())
+		.map_err(|_| BenchmarkError::Stop("Failed to register MSP"))?;
+	Ok(msp)
+}
+
+/// Store a file at `location`, owned by `owner` and stored by `bsp`.
+fn stored_file<T: Config>(
+	owner: T::AccountId,
+	location: &FileLocation<T>,
+	bsp: T::AccountId,
+) -> FileMetadata<T> {
+	let file_metadata = FileMetadata::<T> {
+		owner,
+		requested_at: frame_system::Pallet::<T>::block_number(),
+		fingerprint: fingerprint::<T>(0),
+		size: 1u32.into(),
+		bsps: vec![bsp.clone()].try_into().expect("MaxBsps should be at least one"),
+		is_public: true,
+		msp: None,
+		msp_accepted: false,
+	};
+	FilesMapping::<T>::insert(location, file_metadata.clone());
+	BspsFiles::<T>::insert(&bsp, location, ());
+	file_metadata
+}
 
 #[benchmarks]
 mod benchmarks {
 	use super::*;
 
 	#[benchmark]
-	fn do_something() {
-		let value = 100u32.into();
+	fn request_storage() -> Result<(), BenchmarkError> {
+		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+		let msp = msp::<T>()?;
+		unlimited_quota::<T>(&caller);
+
+		#[extrinsic_call]
+		request_storage(
+			RawOrigin::Signed(caller),
+			location.clone(),
+			fingerprint::<T>(0),
+			1u32.into(),
+			msp,
+			multiaddress::<T>(),
+		);
+
+		assert!(StorageRequests::<T>::contains_key(&location));
+
+		Ok(())
+	}
+
+	#[benchmark]
+	fn request_storage_batch(
+		n: Linear<1, { T::MaxBatchSize::get() }>,
+	) -> Result<(), BenchmarkError> {
+		let caller: T::AccountId = whitelisted_caller();
+		let msp = msp::<T>()?;
+		unlimited_quota::<T>(&caller);
+		let requests: StorageRequestBatch<T> = (0..n)
+			.map(|i| (location::<T>(i), fingerprint::<T>(i), 1u32.into()))
+			.collect::<Vec<_>>()
+			.try_into()
+			.expect("Batch should fit in MaxBatchSize");
+
+		#[extrinsic_call]
+		request_storage_batch(RawOrigin::Signed(caller), requests, msp, multiaddress::<T>());
+
+		for i in 0..n {
+			assert!(StorageRequests::<T>::contains_key(location::<T>(i)));
+		}
+
+		Ok(())
+	}
+
+	#[benchmark]
+	fn bsp_volunteer() -> Result<(), BenchmarkError> {
+		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+		let fingerprint = fingerprint::<T>(0);
+
+		// Register the caller as BSP with the minimum stake and make sure it qualifies for any
+		// file.
+		T::BspsRegistry::register_user(caller.clone())
+			.map_err(|_| BenchmarkError::Stop("Failed to register BSP"))?;
+		T::Reputation::bond_min_stake(&caller);
+		CurrentAssignmentThreshold::<T>::put(T::AssignmentThreshold::max_value());
+
+		// Worst case: the caller is the last BSP the storage request needs, so it is cleared.
+		let bsps: BoundedVec<_, MaxBsps<T>> = (1..T::MaxBsps::get())
+			.map(|i| account::<T::AccountId>("bsp", i, 0))
+			.collect::<Vec<_>>()
+			.try_into()
+			.expect("There should be less than MaxBsps BSPs");
+		StorageRequests::<T>::insert(
+			&location,
+			FileMetadata::<T> {
+				owner: account("owner", 0, 0),
+				requested_at: frame_system::Pallet::<T>::block_number(),
+				fingerprint,
+				size: 1u32.into(),
+				bsps,
+				is_public: true,
+				msp: None,
+				msp_accepted: false,
+			},
+		);
+
+		#[extrinsic_call]
+		bsp_volunteer(
+			RawOrigin::Signed(caller.clone()),
+			location.clone(),
+			fingerprint,
+			multiaddress::<T>(),
+		);
+
+		assert!(!StorageRequests::<T>::contains_key(&location));
+		assert!(BspsFiles::<T>::contains_key(&caller, &location));
+
+		Ok(())
+	}
+
+	#[benchmark]
+	fn update_file() {
+		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+		stored_file::<T>(caller.clone(), &location, account("bsp", 0, 0));
+		unlimited_quota::<T>(&caller);
+
+		#[extrinsic_call]
+		update_file(
+			RawOrigin::Signed(caller),
+			location.clone(),
+			fingerprint::<T>(1),
+			1u32.into(),
+			multiaddress::<T>(),
+		);
+
+		assert!(FileUpdates::<T>::contains_key(&location));
+	}
+
+	#[benchmark]
+	fn bsp_confirm_update() {
+		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+		let new_fingerprint = fingerprint::<T>(1);
+
+		// Worst case: the caller is the last BSP of the file to confirm the update, so the
+		// update is completed, with a full version history and an open storage request.
+		let mut file_metadata = stored_file::<T>(account("owner", 0, 0), &location, caller.clone());
+		let confirmed_bsps: BoundedVec<_, MaxBsps<T>> = (1..T::MaxBsps::get())
+			.map(|i| account::<T::AccountId>("bsp", i, 0))
+			.collect::<Vec<_>>()
+			.try_into()
+			.expect("There should be less than MaxBsps BSPs");
+		for bsp in confirmed_bsps.iter() {
+			file_metadata
+				.bsps
+				.try_push(bsp.clone())
+				.expect("There should be less than MaxBsps BSPs");
+		}
+		FilesMapping::<T>::insert(&location, file_metadata.clone());
+		StorageRequests::<T>::insert(&location, file_metadata);
+		FileUpdates::<T>::insert(
+			&location,
+			FileUpdate::<T> {
+				requested_at: frame_system::Pallet::<T>::block_number(),
+				fingerprint: new_fingerprint,
+				size: 1u32.into(),
+				confirmed_bsps,
+			},
+		);
+		let versions = FileVersionHistory::<T>::truncate_from(
+			(0..T::MaxFileVersions::get())
+				.map(|i| FileVersion::<T> {
+					fingerprint: fingerprint::<T>(i + 2),
+					size: 1u32.into(),
+					replaced_at: frame_system::Pallet::<T>::block_number(),
+				})
+				.collect(),
+		);
+		FileVersions::<T>::insert(&location, versions);
+
+		#[extrinsic_call]
+		bsp_confirm_update(RawOrigin::Signed(caller), location.clone(), new_fingerprint);
+
+		assert!(!FileUpdates::<T>::contains_key(&location));
+		assert_eq!(FilesMapping::<T>::get(&location).map(|f| f.fingerprint), Some(new_fingerprint));
+	}
+
+	#[benchmark]
+	fn set_file_visibility() {
+		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+
+		// Worst case: the file is stored and its storage request is still open.
+		let file_metadata = stored_file::<T>(caller.clone(), &location, account("bsp", 0, 0));
+		StorageRequests::<T>::insert(&location, file_metadata);
+
+		#[extrinsic_call]
+		set_file_visibility(RawOrigin::Signed(caller), location.clone(), false);
+
+		assert_eq!(FilesMapping::<T>::get(&location).map(|f| f.is_public), Some(false));
+		assert_eq!(StorageRequests::<T>::get(&location).map(|f| f.is_public), Some(false));
+	}
+
+	#[benchmark]
+	fn grant_read_access() {
+		let caller: T::AccountId = whitelisted_caller();
+		let reader: T::AccountId = account("reader", 0, 0);
+		let location = location::<T>(0);
+
+		// Worst case: the file is only requested, so both maps are read.
+		StorageRequests::<T>::insert(
+			&location,
+			stored_file::<T>(caller.clone(), &location, account("bsp", 0, 0)),
+		);
+		FilesMapping::<T>::remove(&location);
+
+		#[extrinsic_call]
+		grant_read_access(RawOrigin::Signed(caller), location.clone(), reader.clone());
+
+		assert!(FileReaders::<T>::contains_key(&location, &reader));
+	}
+
+	#[benchmark]
+	fn revoke_read_access() {
+		let caller: T::AccountId = whitelisted_caller();
+		let reader: T::AccountId = account("reader", 0, 0);
+		let location = location::<T>(0);
+
+		// Worst case: the file is only requested, so both maps are read.
+		StorageRequests::<T>::insert(
+			&location,
+			stored_file::<T>(caller.clone(), &location, account("bsp", 0, 0)),
+		);
+		FilesMapping::<T>::remove(&location);
+		FileReaders::<T>::insert(&location, &reader, ());
+
+		#[extrinsic_call]
+		revoke_read_access(RawOrigin::Signed(caller), location.clone(), reader.clone());
+
+		assert!(!FileReaders::<T>::contains_key(&location, &reader));
+	}
+
+	#[benchmark]
+	fn register_peer_id() {
+		let caller: T::AccountId = whitelisted_caller();
+		let (peer_id, signature) = peer_id::<T>(&caller);
+
+		#[extrinsic_call]
+		register_peer_id(RawOrigin::Signed(caller.clone()), peer_id.clone(), signature);
+
+		assert_eq!(PeerAccounts::<T>::get(&peer_id), Some(caller));
+	}
+
+	#[benchmark]
+	fn unregister_peer_id() {
+		let caller: T::AccountId = whitelisted_caller();
+		let (peer_id, _) = peer_id::<T>(&caller);
+		PeerAccounts::<T>::insert(&peer_id, &caller);
+
+		#[extrinsic_call]
+		unregister_peer_id(RawOrigin::Signed(caller), peer_id.clone());
+
+		assert!(!PeerAccounts::<T>::contains_key(&peer_id));
+	}
+
+	#[benchmark]
+	fn set_user_quota() -> Result<(), BenchmarkError> {
+		let origin =
+			T::QuotaOrigin::try_successful_origin().map_err(|_| BenchmarkError::Weightless)?;
+		let who: T::AccountId = account("user", 0, 0);
+
+		#[extrinsic_call]
+		_(origin as T::RuntimeOrigin, who.clone(), 1u32.into(), 1);
+
+		assert_eq!(UserQuotas::<T>::get(&who).map(|q| q.max_files), Some(1));
+
+		Ok(())
+	}
+
+	#[benchmark]
+	fn msp_accept_storage_request() {
+		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+
+		// Worst case: the file is stored and its storage request is still open.
+		let mut file_metadata =
+			stored_file::<T>(account("owner", 0, 0), &location, account("bsp", 0, 0));
+		file_metadata.msp = Some(caller.clone());
+		FilesMapping::<T>::insert(&location, file_metadata.clone());
+		StorageRequests::<T>::insert(&location, file_metadata);
+
+		#[extrinsic_call]
+		msp_accept_storage_request(RawOrigin::Signed(caller), location.clone());
+
+		assert_eq!(FilesMapping::<T>::get(&location).map(|f| f.msp_accepted), Some(true));
+		assert_eq!(StorageRequests::<T>::get(&location).map(|f| f.msp_accepted), Some(true));
+	}
+
+	#[benchmark]
+	fn msp_reject_storage_request() {
+		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+
+		// Worst case: the file is stored and its storage request is still open.
+		let mut file_metadata =
+			stored_file::<T>(account("owner", 0, 0), &location, account("bsp", 0, 0));
+		file_metadata.msp = Some(caller.clone());
+		FilesMapping::<T>::insert(&location, file_metadata.clone());
+		StorageRequests::<T>::insert(&location, file_metadata);
+
+		#[extrinsic_call]
+		msp_reject_storage_request(RawOrigin::Signed(caller), location.clone());
+
+		assert_eq!(FilesMapping::<T>::get(&location).map(|f| f.msp), Some(None));
+		assert_eq!(StorageRequests::<T>::get(&location).map(|f| f.msp), Some(None));
+	}
+
+	#[benchmark]
+	fn expire_file_update(n: Linear<1, { T::MaxBsps::get() - 1 }>) {
+		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+		let new_fingerprint = fingerprint::<T>(1);
+
+		// Worst case: a single BSP confirmed the update, so the other `n` BSPs are removed from
+		// the file, which completes the update with a full version history.
+		let confirmed_bsp: T::AccountId = account("bsp", 0, 0);
+		let mut file_metadata =
+			stored_file::<T>(account("owner", 0, 0), &location, confirmed_bsp.clone());
+		for i in 1..=n {
+			let bsp: T::AccountId = account("bsp", i, 0);
+			file_metadata
+				.bsps
+				.try_push(bsp.clone())
+				.expect("There should be less than MaxBsps BSPs");
+			BspsFiles::<T>::insert(&bsp, &location, ());
+		}
+		FilesMapping::<T>::insert(&location, file_metadata);
+		FileUpdates::<T>::insert(
+			&location,
+			FileUpdate::<T> {
+				requested_at: frame_system::Pallet::<T>::block_number(),
+				fingerprint: new_fingerprint,
+				size: 1u32.into(),
+				confirmed_bsps: vec![confirmed_bsp]
+					.try_into()
+					.expect("MaxBsps should be at least one"),
+			},
+		);
+		let versions = FileVersionHistory::<T>::truncate_from(
+			(0..T::MaxFileVersions::get())
+				.map(|i| FileVersion::<T> {
+					fingerprint: fingerprint::<T>(i + 2),
+					size: 1u32.into(),
+					replaced_at: frame_system::Pallet::<T>::block_number(),
+				})
+				.collect(),
+		);
+		FileVersions::<T>::insert(&location, versions);
+		frame_system::Pallet::<T>::set_block_number(
+			frame_system::Pallet::<T>::block_number() +
+				T::UpdateConfirmationPeriod::get() +
+				1u32.into(),
+		);
+
+		#[extrinsic_call]
+		expire_file_update(RawOrigin::Signed(caller), location.clone());
+
+		assert!(!FileUpdates::<T>::contains_key(&location));
+		assert_eq!(FilesMapping::<T>::get(&location).map(|f| f.bsps.len()), Some(1));
+	}
+
+	#[benchmark]
+	fn report_failed_challenge() -> Result<(), BenchmarkError> {
+		let origin =
+			T::ChallengeOrigin::try_successful_origin().map_err(|_| BenchmarkError::Weightless)?;
+		let location = location::<T>(0);
+		let bsp: T::AccountId = account("bsp", 0, 0);
+		stored_file::<T>(account("owner", 0, 0), &location, bsp.clone());
+
+		#[extrinsic_call]
+		_(origin as T::RuntimeOrigin, bsp, location);
+
+		Ok(())
+	}
+
+	#[benchmark]
+	fn request_retrieval() {
+		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+		let bsp: T::AccountId = account("bsp", 0, 0);
+		let fee = retrieval_fee::<T>(&caller);
+
+		// Worst case: the file is private and the caller is in its allow-list.
+		let mut file_metadata = stored_file::<T>(account("owner", 0, 0), &location, bsp.clone());
+		file_metadata.is_public = false;
+		FilesMapping::<T>::insert(&location, file_metadata);
+		FileReaders::<T>::insert(&location, &caller, ());
+
+		#[extrinsic_call]
+		request_retrieval(
+			RawOrigin::Signed(caller.clone()),
+			location.clone(),
+			bsp,
+			fee,
+			multiaddress::<T>(),
+		);
+
+		assert!(RetrievalRequests::<T>::contains_key(&location, &caller));
+	}
+
+	#[benchmark]
+	fn bsp_confirm_delivery() {
+		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+		let requester: T::AccountId = account("requester", 0, 0);
+		let fee = retrieval_fee::<T>(&requester);
+		retrieval_request::<T>(&location, &requester, caller.clone(), fee, None);
+
+		#[extrinsic_call]
+		bsp_confirm_delivery(RawOrigin::Signed(caller), location.clone(), requester.clone());
+
+		assert!(RetrievalRequests::<T>::get(&location, &requester)
+			.is_some_and(|retrieval_request| retrieval_request.delivered_at.is_some()));
+	}
+
+	#[benchmark]
+	fn refund_retrieval() {
 		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+		let requester: T::AccountId = account("requester", 0, 0);
+		let fee = retrieval_fee::<T>(&requester);
+		retrieval_request::<T>(&location, &requester, account("bsp", 0, 0), fee, None);
+		frame_system::Pallet::<T>::set_block_number(
+			frame_system::Pallet::<T>::block_number() + T::RetrievalTimeout::get() + 1u32.into(),
+		);
+
+		#[extrinsic_call]
+		refund_retrieval(RawOrigin::Signed(caller), location.clone(), requester.clone());
+
+		assert!(!RetrievalRequests::<T>::contains_key(&location, &requester));
+	}
+
+	#[benchmark]
+	fn confirm_retrieval() {
+		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+		let requester: T::AccountId = account("requester", 0, 0);
+		let bsp: T::AccountId = account("bsp", 0, 0);
+		let fee = retrieval_fee::<T>(&requester);
+		T::Currency::make_free_balance_be(&bsp, T::Currency::minimum_balance());
+
+		// Worst case: the caller is not the requester, so the dispute period has to be checked.
+		let now = frame_system::Pallet::<T>::block_number();
+		retrieval_request::<T>(&location, &requester, bsp, fee, Some(now));
+		frame_system::Pallet::<T>::set_block_number(
+			now + T::RetrievalDisputePeriod::get() + 1u32.into(),
+		);
+
 		#[extrinsic_call]
-		do_something(RawOrigin::Signed(caller), value);
+		confirm_retrieval(RawOrigin::Signed(caller), location.clone(), requester.clone());
 
-		assert_eq!(Something::<T>::get(), Some(value));
+		assert!(!RetrievalRequests::<T>::contains_key(&location, &requester));
 	}
 
 	#[benchmark]
-	fn cause_error() {
-		Something::<T>::put(100u32);
+	fn dispute_retrieval() {
 		let caller: T::AccountId = whitelisted_caller();
+		let location = location::<T>(0);
+		let fee = retrieval_fee::<T>(&caller);
+		let now = frame_system::Pallet::<T>::block_number();
+		retrieval_request::<T>(&location, &caller, account("bsp", 0, 0), fee, Some(now));
+
 		#[extrinsic_call]
-		cause_error(RawOrigin::Signed(caller));
+		dispute_retrieval(RawOrigin::Signed(caller.clone()), location.clone());
 
-		assert_eq!(Something::<T>::get(), Some(101u32));
+		assert!(!RetrievalRequests::<T>::contains_key(&location, &caller));
 	}
 
-	impl_benchmark_test_suite!(Template, crate::mock::new_test_ext(), crate::mock::Test);
+	impl_benchmark_test_suite!(FileSystem, crate::mock::new_test_ext(), crate::mock::Test);
 }
@@ -16,33 +16,111 @@ mod benchmarking;
 pub mod weights;
 pub use weights::*;
 
-mod types;
+pub mod migrations;
+pub mod multiaddress;
+pub mod signed_extension;
+pub mod types;
 mod utils;
 
+pub use signed_extension::CheckBspVolunteer;
+
+use frame_support::weights::Weight;
+use sp_runtime::KeyTypeId;
+use types::{FileLocation, FileMetadata, StorageCount};
+
+/// Key type of the BSP keys the offchain worker volunteers with.
+///
+/// Keys are sr25519, inserted in the node keystore with `key insert --key-type bsp_`.
+pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"bsp_");
+
+/// Offchain persistent storage key of the SCALE encoded multiaddress the BSPs of the node are
+/// reachable at. The offchain worker only volunteers once it is set, by the node running the BSP.
+pub const BSP_MULTIADDRESS_KEY: &[u8] = b"storagehub::bsp-multiaddress";
+
+/// Crypto of the BSP keys, so the offchain worker can sign transactions with them.
+pub mod crypto {
+	use super::KEY_TYPE;
+	use sp_core::sr25519::{Public as Sr25519Public, Signature as Sr25519Signature};
+	use sp_runtime::{
+		app_crypto::{app_crypto, sr25519},
+		MultiSignature, MultiSigner,
+	};
+
+	app_crypto!(sr25519, KEY_TYPE);
+
+	/// The BSP keys, as signers of transactions submitted by the offchain worker.
+	pub struct BspAuthId;
+
+	impl frame_system::offchain::AppCrypto<MultiSigner, MultiSignature> for BspAuthId {
+		type RuntimeAppPublic = Public;
+		type GenericSignature = Sr25519Signature;
+		type GenericPublic = Sr25519Public;
+	}
+}
+
 #[frame_support::pallet]
 pub mod pallet {
 	use super::{types::*, *};
 	use frame_support::{
-		dispatch::{fmt::Debug, HasCompact},
+		dispatch::{fmt::Debug, DispatchErrorWithPostInfo, HasCompact},
+		pallet_prelude::*,
+		sp_runtime::traits::{
+			AtLeast32Bit, AtLeast32BitUnsigned, CheckEqual, MaybeDisplay, Saturating, SimpleBitOps,
+			Zero,
+		},
+		traits::NamedReservableCurrency,
+	};
+	use frame_system::{
+		offchain::{AppCrypto, CreateSignedTransaction},
 		pallet_prelude::*,
-		sp_runtime::traits::{AtLeast32Bit, CheckEqual, MaybeDisplay, SimpleBitOps},
 	};
-	use frame_system::pallet_prelude::*;
+	use pallet_identity::IdentityInterface;
+	use pallet_reputation::{Failure, ReputationInterface};
+	use scale_info::prelude::vec::Vec;
+	use sp_core::ed25519;
+
+	/// The current storage version.
+	pub const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
 
 	#[pallet::pallet]
+	#[pallet::storage_version(STORAGE_VERSION)]
 	pub struct Pallet<T>(_);
 
 	/// Configure the pallet by specifying the parameters and types on which it depends.
 	#[pallet::config]
-	pub trait Config: frame_system::Config {
+	pub trait Config: CreateSignedTransaction<Call<Self>> + frame_system::Config {
 		/// Because this pallet emits events, it depends on the runtime's definition of an event.
 		type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
 		/// Type representing the weight of this pallet
 		type WeightInfo: WeightInfo;
 
+		/// The keys the offchain worker signs the volunteers of local BSPs with.
+		type AuthorityId: AppCrypto<Self::Public, Self::Signature>;
+
 		/// Type to access the Identity Pallet, where BSPs are registered.
 		type BspsRegistry: pallet_identity::IdentityInterface<AccountId = Self::AccountId>;
 
+		/// Type to access the Identity Pallet where MSPs are registered.
+		type MspsRegistry: pallet_identity::IdentityInterface<AccountId = Self::AccountId>;
+
+		/// Handler for the lifecycle of files, for other pallets to react to it.
+		type FileSystemHooks: FileSystemHooks<Self>;
+
+		/// Type to access the Reputation Pallet, where BSPs are scored for how reliable they are
+		/// and bond the stake they need to volunteer.
+		type Reputation: pallet_reputation::ReputationInterface<AccountId = Self::AccountId>;
+
+		/// The origin allowed to report BSPs that failed to prove they store a file.
+		type ChallengeOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+
+		/// The currency retrieval fees are paid in, which are reserved under
+		/// [`Config::RetrievalReserveId`] until the delivery is settled.
+		type Currency: NamedReservableCurrency<Self::AccountId>;
+
+		/// The identifier retrieval fees are reserved under.
+		#[pallet::constant]
+		type RetrievalReserveId: Get<ReserveIdentifierOf<Self>>;
+
 		/// The type for Content IDs of files, generally a hash.
 		type Fingerprint: Parameter
 			+ Member
@@ -72,13 +150,17 @@ pub mod pallet {
 
 		/// The threshold that the randomness criteria operation result should
 		/// meet, for the caller to instantly be eligible as BSP for that file.
+		///
+		/// That result is decoded from the leading bytes of the XOR of the file's
+		/// [`Config::Fingerprint`] and the BSP's account hash, so the threshold cannot be encoded
+		/// in more bytes than fingerprints are, or no BSP can volunteer.
 		type AssignmentThreshold: Parameter
 			+ Member
 			+ MaybeSerializeDeserialize
 			+ Debug
 			+ Default
 			+ MaybeDisplay
-			+ AtLeast32Bit
+			+ AtLeast32BitUnsigned
 			+ Copy
 			+ MaxEncodedLen
 			+ Decode
@@ -88,6 +170,43 @@ pub mod pallet {
 		#[pallet::constant]
 		type MaxBsps: Get<u32>;
 
+		/// The maximum number of storage requests in a batch.
+		#[pallet::constant]
+		type MaxBatchSize: Get<u32>;
+
+		/// The maximum size of a file.
+		#[pallet::constant]
+		type MaxFileSize: Get<Self::StorageCount>;
+
+		/// The total size of the files a user can store, unless a different quota is set for
+		/// them.
+		#[pallet::constant]
+		type DefaultMaxStoredBytes: Get<Self::StorageCount>;
+
+		/// The number of files a user can store, unless a different quota is set for them.
+		#[pallet::constant]
+		type DefaultMaxStoredFiles: Get<u32>;
+
+		/// The origin allowed to set the storage quota of users.
+		type QuotaOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+
+		/// The maximum number of previous versions kept for each file.
+		#[pallet::constant]
+		type MaxFileVersions: Get<u32>;
+
+		/// The number of blocks the BSPs of a file have to confirm its new version.
+		#[pallet::constant]
+		type UpdateConfirmationPeriod: Get<BlockNumberFor<Self>>;
+
+		/// The number of blocks a BSP has to deliver a file, before its fee can be refunded.
+		#[pallet::constant]
+		type RetrievalTimeout: Get<BlockNumberFor<Self>>;
+
+		/// The number of blocks a requester has to dispute a delivery claimed by a BSP, before
+		/// anyone can pay its fee to the BSP.
+		#[pallet::constant]
+		type RetrievalDisputePeriod: Get<BlockNumberFor<Self>>;
+
 		/// The maximum size of a file path in bytes.
 		#[pallet::constant]
 		type MaxFilePathSize: Get<u32>;
@@ -96,6 +215,15 @@ pub mod pallet {
 		#[pallet::constant]
 		type MaxMultiAddressSize: Get<u32>;
 
+		/// The maximum size of a libp2p PeerId in bytes.
+		#[pallet::constant]
+		type MaxPeerIdSize: Get<u32>;
+
+		/// The maximum number of storage requests the offchain worker checks local BSPs against in
+		/// each run. The next run resumes from where the previous one stopped.
+		#[pallet::constant]
+		type MaxOffchainVolunteers: Get<u32>;
+
 		/// The minimum threshold that the randomness criteria operation result
 		/// should meet, for the caller to instantly be eligible as BSP for that
 		/// file. This minimum threshold should decrease when more BSPs are
@@ -108,6 +236,13 @@ pub mod pallet {
 	pub type StorageRequests<T: Config> =
 		StorageMap<_, Blake2_128Concat, FileLocation<T>, FileMetadata<T>>;
 
+	/// Multiaddress of the peer sending the file of each storage request, which volunteers fetch
+	/// it from. Storage requests reopened after losing a BSP have none.
+	#[pallet::storage]
+	#[pallet::getter(fn storage_request_sender)]
+	pub type StorageRequestSenders<T: Config> =
+		StorageMap<_, Blake2_128Concat, FileLocation<T>, MultiAddress<T>>;
+
 	#[pallet::storage]
 	pub type FilesMapping<T: Config> =
 		StorageMap<_, Blake2_128Concat, FileLocation<T>, FileMetadata<T>>;
@@ -121,6 +256,141 @@ pub mod pallet {
 	pub type CurrentAssignmentThreshold<T: Config> =
 		StorageValue<_, <T as Config>::AssignmentThreshold>;
 
+	/// Files stored by each BSP.
+	#[pallet::storage]
+	pub type BspsFiles<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		StorageProviderId<T>,
+		Blake2_128Concat,
+		FileLocation<T>,
+		(),
+	>;
+
+	/// New versions of stored files, waiting for their BSPs to confirm them.
+	#[pallet::storage]
+	pub type FileUpdates<T: Config> =
+		StorageMap<_, Blake2_128Concat, FileLocation<T>, FileUpdate<T>>;
+
+	/// Previous versions of each file, oldest first.
+	#[pallet::storage]
+	#[pallet::getter(fn file_versions)]
+	pub type FileVersions<T: Config> =
+		StorageMap<_, Blake2_128Concat, FileLocation<T>, FileVersionHistory<T>, ValueQuery>;
+
+	/// Storage quotas of the users that do not have the default one.
+	#[pallet::storage]
+	pub type UserQuotas<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, StorageQuota<T>>;
+
+	/// Total size and number of the files stored by each user.
+	#[pallet::storage]
+	#[pallet::getter(fn user_storage_usage)]
+	pub type UserStorageUsage<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, StorageUsage<T>, ValueQuery>;
+
+	/// Accounts allowed to read each private file, besides its owner and BSPs.
+	#[pallet::storage]
+	pub type FileReaders<T: Config> =
+		StorageDoubleMap<_, Blake2_128Concat, FileLocation<T>, Blake2_128Concat, T::AccountId, ()>;
+
+	/// The account each libp2p PeerId belongs to, so that nodes serving files can tell who is
+	/// requesting them.
+	#[pallet::storage]
+	#[pallet::getter(fn peer_accounts)]
+	pub type PeerAccounts<T: Config> = StorageMap<_, Blake2_128Concat, PeerId<T>, T::AccountId>;
+
+	/// BSPs that were removed from the registry and whose files are yet to be reassigned.
+	#[pallet::storage]
+	pub type LeavingBsps<T: Config> = StorageMap<_, Blake2_128Concat, StorageProviderId<T>, ()>;
+
+	/// Pending requests to retrieve each file, by the account requesting it.
+	#[pallet::storage]
+	pub type RetrievalRequests<T: Config> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		FileLocation<T>,
+		Blake2_128Concat,
+		T::AccountId,
+		RetrievalRequest<T>,
+	>;
+
+	#[pallet::genesis_config]
+	#[derive(frame_support::DefaultNoBound)]
+	pub struct GenesisConfig<T: Config> {
+		/// Files that are already stored from genesis.
+		pub files: Vec<GenesisFile<T>>,
+		/// Initial value for the BSPs assignment threshold. If not set,
+		/// [`Config::MinBspsAssignmentThreshold`] is used.
+		pub assignment_threshold: Option<T::AssignmentThreshold>,
+		/// libp2p PeerIds registered from genesis, with the account operating each node.
+		pub peers: Vec<(T::AccountId, PeerId<T>)>,
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> BuildGenesisConfig for GenesisConfig<T> {
+		fn build(&self) {
+			let mut total_used_storage = StorageCount::<T>::zero();
+
+			for (owner, location, fingerprint, size, bsps) in &self.files {
+				assert!(
+					!FilesMapping::<T>::contains_key(location),
+					"Genesis files must have unique locations"
+				);
+
+				let bsps: BoundedVec<_, MaxBsps<T>> = bsps
+					.clone()
+					.try_into()
+					.expect("Genesis files cannot have more than MaxBsps BSPs");
+				assert!(!bsps.is_empty(), "Genesis files must be stored by at least one BSP");
+				for bsp in bsps.iter() {
+					assert!(
+						T::BspsRegistry::get_user(bsp.clone()).is_some(),
+						"Genesis files must be stored by registered BSPs"
+					);
+				}
+
+				total_used_storage = total_used_storage
+					.saturating_add(size.saturating_mul((bsps.len() as u32).into()));
+
+				UserStorageUsage::<T>::mutate(owner, |usage| {
+					usage.bytes = usage.bytes.saturating_add(*size);
+					usage.files = usage.files.saturating_add(1);
+				});
+
+				let file_metadata = FileMetadata::<T> {
+					owner: owner.clone(),
+					requested_at: Zero::zero(),
+					fingerprint: *fingerprint,
+					size: *size,
+					bsps,
+					is_public: true,
+					msp: None,
+					msp_accepted: false,
+				};
+				for bsp in file_metadata.bsps.iter() {
+					BspsFiles::<T>::insert(bsp, location, ());
+				}
+				FilesMapping::<T>::insert(location, file_metadata);
+			}
+
+			if !self.files.is_empty() {
+				TotalUsedBspStorage::<T>::put(total_used_storage);
+			}
+
+			if let Some(threshold) = self.assignment_threshold {
+				CurrentAssignmentThreshold::<T>::put(threshold);
+			}
+
+			for (who, peer_id) in &self.peers {
+				assert!(
+					!PeerAccounts::<T>::contains_key(peer_id),
+					"Genesis PeerIds must be unique"
+				);
+				PeerAccounts::<T>::insert(peer_id, who);
+			}
+		}
+	}
+
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
 	pub enum Event<T: Config> {
@@ -129,6 +399,7 @@ pub mod pallet {
 			location: FileLocation<T>,
 			fingerprint: Fingerprint<T>,
 			size: StorageCount<T>,
+			msp: T::AccountId,
 			sender_multiaddress: MultiAddress<T>,
 		},
 
@@ -138,6 +409,115 @@ pub mod pallet {
 			fingerprint: Fingerprint<T>,
 			bsp_multiaddress: MultiAddress<T>,
 		},
+
+		/// Several files were requested to be stored at once, all sent from the same peer.
+		NewStorageRequestBatch {
+			who: T::AccountId,
+			requests: StorageRequestBatch<T>,
+			msp: T::AccountId,
+			sender_multiaddress: MultiAddress<T>,
+		},
+
+		/// A stored file lost a BSP, so its storage request is open again for volunteers.
+		StorageRequestReopened {
+			location: FileLocation<T>,
+			fingerprint: Fingerprint<T>,
+			leaving_bsp: T::AccountId,
+		},
+
+		/// The owner of a file uploaded a new version, which its BSPs have to fetch and confirm.
+		FileUpdateRequested {
+			who: T::AccountId,
+			location: FileLocation<T>,
+			fingerprint: Fingerprint<T>,
+			size: StorageCount<T>,
+			sender_multiaddress: MultiAddress<T>,
+			bsps: BoundedVec<StorageProviderId<T>, MaxBsps<T>>,
+		},
+
+		/// A BSP confirmed that it stores the new version of a file.
+		FileUpdateConfirmed {
+			who: T::AccountId,
+			location: FileLocation<T>,
+			fingerprint: Fingerprint<T>,
+		},
+
+		/// The storage quota of a user was set.
+		UserQuotaSet { who: T::AccountId, max_bytes: StorageCount<T>, max_files: u32 },
+
+		/// The owner of a file made it public or private.
+		FileVisibilityChanged { location: FileLocation<T>, is_public: bool },
+
+		/// The owner of a private file allowed an account to read it.
+		ReadAccessGranted { location: FileLocation<T>, reader: T::AccountId },
+
+		/// The owner of a private file no longer allows an account to read it.
+		ReadAccessRevoked { location: FileLocation<T>, reader: T::AccountId },
+
+		/// An account registered a libp2p PeerId as its own.
+		PeerIdRegistered { who: T::AccountId, peer_id: PeerId<T> },
+
+		/// An account unregistered one of its libp2p PeerIds.
+		PeerIdUnregistered { who: T::AccountId, peer_id: PeerId<T> },
+
+		/// Every BSP confirmed the new version of a file, so the previous one can be released.
+		FileUpdated {
+			location: FileLocation<T>,
+			fingerprint: Fingerprint<T>,
+			previous_fingerprint: Fingerprint<T>,
+		},
+
+		/// The MSP picked for a file accepted to store it.
+		MspAcceptedStorageRequest { msp: T::AccountId, location: FileLocation<T> },
+
+		/// The MSP picked for a file rejected to store it, so only its BSPs store it.
+		MspRejectedStorageRequest { msp: T::AccountId, location: FileLocation<T> },
+
+		/// Some BSPs did not confirm the new version of a file in time, so they no longer store
+		/// it.
+		FileUpdateDeadlineMissed {
+			location: FileLocation<T>,
+			fingerprint: Fingerprint<T>,
+			bsps: BoundedVec<StorageProviderId<T>, MaxBsps<T>>,
+		},
+
+		/// No BSP confirmed the new version of a file in time, so it was dropped.
+		FileUpdateDropped { location: FileLocation<T>, fingerprint: Fingerprint<T> },
+
+		/// A BSP failed to prove that it stores a file.
+		ChallengeFailed { bsp: T::AccountId, location: FileLocation<T> },
+
+		/// An account paid to retrieve a file from one of its BSPs, which has to send it to
+		/// `requester_multiaddress`.
+		RetrievalRequested {
+			who: T::AccountId,
+			location: FileLocation<T>,
+			bsp: T::AccountId,
+			fee: BalanceOf<T>,
+			requester_multiaddress: MultiAddress<T>,
+		},
+
+		/// A BSP claimed to have delivered a file, which its requester can dispute.
+		RetrievalDelivered { who: T::AccountId, location: FileLocation<T>, bsp: T::AccountId },
+
+		/// A delivered file was confirmed, and its BSP was paid the retrieval fee.
+		RetrievalPaid {
+			who: T::AccountId,
+			location: FileLocation<T>,
+			bsp: T::AccountId,
+			fee: BalanceOf<T>,
+		},
+
+		/// A requester disputed the delivery of a file, so the retrieval fee was refunded.
+		RetrievalDisputed {
+			who: T::AccountId,
+			location: FileLocation<T>,
+			bsp: T::AccountId,
+			fee: BalanceOf<T>,
+		},
+
+		/// A file was not delivered in time, so the retrieval fee was refunded.
+		RetrievalRefunded { who: T::AccountId, location: FileLocation<T>, fee: BalanceOf<T> },
 	}
 
 	// Errors inform users that something went wrong.
@@ -156,6 +536,90 @@ pub mod pallet {
 		ThresholdTooLow,
 		/// Failed to decode the threshold value.
 		FailedToDecodeThreshold,
+		/// Trying to request storage for a location where a file is already stored.
+		FileAlreadyStored,
+		/// Trying to operate over a file that is not stored.
+		FileNotStored,
+		/// Trying to update a file, when sender is not its owner.
+		NotFileOwner,
+		/// Trying to update a file with the content it already has.
+		FileVersionUnchanged,
+		/// Trying to confirm an update for a file without a pending update.
+		FileUpdateNotRequested,
+		/// Trying to confirm an update for a file, when sender is not one of its BSPs.
+		NotFileBsp,
+		/// Trying to confirm a version of a file that is not the pending one.
+		FileUpdateMismatch,
+		/// Trying to confirm an update for a file, when that BSP already confirmed it.
+		FileUpdateAlreadyConfirmed,
+		/// Trying to store a file larger than `MaxFileSize`.
+		FileTooLarge,
+		/// Trying to store more bytes than the storage quota of the user allows.
+		StorageQuotaExceeded,
+		/// Trying to store more files than the storage quota of the user allows.
+		FileCountQuotaExceeded,
+		/// Trying to register a PeerId that is already registered.
+		PeerIdAlreadyRegistered,
+		/// Trying to unregister a PeerId that is not registered by the sender.
+		PeerIdNotRegistered,
+		/// Trying to register a PeerId that was not derived from an ed25519 key.
+		UnsupportedPeerId,
+		/// Trying to register a PeerId without a valid signature of its key.
+		InvalidPeerIdSignature,
+		/// Trying to submit a multiaddress that is not valid or has no `/p2p/` component.
+		InvalidMultiAddress,
+		/// Trying to pick an MSP for a file, when that account is not a registered MSP.
+		NotMsp,
+		/// Trying to accept or reject a file, when sender is not the MSP picked for it.
+		NotFileMsp,
+		/// Trying to accept or reject a file that the MSP already accepted.
+		MspAlreadyAccepted,
+		/// Trying to expire a file update before its BSPs ran out of time to confirm it.
+		FileUpdateNotExpired,
+		/// Trying to retrieve a file that the sender is not allowed to read.
+		FileAccessDenied,
+		/// Trying to retrieve a file that the sender already requested and is still pending.
+		RetrievalAlreadyRequested,
+		/// Trying to operate over a non-existing retrieval request.
+		RetrievalNotRequested,
+		/// Trying to confirm a delivery, when sender is not the BSP the file was requested from.
+		NotRetrievalBsp,
+		/// Trying to confirm a delivery after the BSP ran out of time to deliver it.
+		RetrievalExpired,
+		/// Trying to refund a retrieval before the BSP ran out of time to deliver it.
+		RetrievalNotExpired,
+		/// Trying to volunteer as BSP without the minimum stake bonded in the Reputation Pallet.
+		InsufficientStake,
+		/// Trying to confirm a delivery twice, or to refund a retrieval that was delivered.
+		RetrievalAlreadyDelivered,
+		/// Trying to pay or dispute a retrieval whose BSP has not claimed to deliver it.
+		RetrievalNotDelivered,
+		/// Trying to dispute a delivery after its dispute period ended.
+		DisputePeriodEnded,
+		/// Trying to pay a delivery on behalf of its requester before its dispute period ended.
+		DisputePeriodNotEnded,
+		/// The reserved retrieval fee could not be fully paid to the BSP.
+		RetrievalFeeShortfall,
+		/// Trying to volunteer as BSP for a file while a new version of it is pending.
+		FileUpdatePending,
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_idle(_n: BlockNumberFor<T>, remaining_weight: Weight) -> Weight {
+			// Reassign files of BSPs that left, with whatever weight is left in the block.
+			Self::do_reassign_leaving_bsps_files(remaining_weight)
+		}
+
+		fn offchain_worker(now: BlockNumberFor<T>) {
+			// Volunteer the local BSP keys for the storage requests they are eligible for.
+			Self::offchain_volunteer_local_bsps(now)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn try_state(_n: BlockNumberFor<T>) -> Result<(), sp_runtime::TryRuntimeError> {
+			Self::do_try_state()
+		}
 	}
 
 	// Dispatchable functions allows users to interact with the pallet and invoke state changes.
@@ -165,19 +629,32 @@ pub mod pallet {
 	impl<T: Config> Pallet<T> {
 		// TODO: Document
 		#[pallet::call_index(0)]
-		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1).ref_time())]
+		#[pallet::weight(
+			T::WeightInfo::request_storage().saturating_add(T::FileSystemHooks::weight())
+		)]
 		pub fn request_storage(
 			origin: OriginFor<T>,
 			location: FileLocation<T>,
 			fingerprint: Fingerprint<T>,
 			size: StorageCount<T>,
+			msp: T::AccountId,
 			sender_multiaddress: MultiAddress<T>,
 		) -> DispatchResult {
 			// Check that the extrinsic was signed and get the signer.
 			let who = ensure_signed(origin)?;
 
+			// Check that the sender multiaddress is valid.
+			Self::ensure_valid_multiaddress(&sender_multiaddress)?;
+
 			// Perform validations and register storage request.
-			Self::do_request_storage(location.clone(), fingerprint)?;
+			Self::do_request_storage(
+				who.clone(),
+				location.clone(),
+				fingerprint,
+				size,
+				msp.clone(),
+				sender_multiaddress.clone(),
+			)?;
 
 			// Emit new storage request event.
 			Self::deposit_event(Event::NewStorageRequest {
@@ -185,26 +662,41 @@ pub mod pallet {
 				location,
 				fingerprint,
 				size,
+				msp,
 				sender_multiaddress,
 			});
 
 			Ok(())
 		}
 
-		// TODO: Document
+		/// Volunteer as BSP for the storage request at `location`.
+		///
+		/// Volunteering is free for BSPs that qualify, while those that do not pay the fees.
+		/// [`CheckBspVolunteer`] keeps the latter out of the transaction pool, but it only sees
+		/// direct calls: volunteers wrapped in another call, e.g. `Proxy::proxy`, skip it and are
+		/// charged for that outer call. Any failing volunteer is charged in full.
 		#[pallet::call_index(1)]
-		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1).ref_time())]
+		#[pallet::weight(
+			T::WeightInfo::bsp_volunteer()
+				.saturating_add(T::FileSystemHooks::weight().saturating_mul(2))
+		)]
 		pub fn bsp_volunteer(
 			origin: OriginFor<T>,
 			location: FileLocation<T>,
 			fingerprint: Fingerprint<T>,
 			bsp_multiaddress: MultiAddress<T>,
-		) -> DispatchResult {
+		) -> DispatchResultWithPostInfo {
 			// Check that the extrinsic was signed and get the signer.
 			let who = ensure_signed(origin)?;
 
-			// Perform validations and register Storage Provider as BSP for file.
-			Self::do_bsp_volunteer(who.clone(), location.clone(), fingerprint)?;
+			// Check that the BSP multiaddress is valid, then perform validations and register
+			// Storage Provider as BSP for file. Failing volunteers pay the fees.
+			Self::ensure_valid_multiaddress(&bsp_multiaddress)
+				.and_then(|_| Self::do_bsp_volunteer(who.clone(), location.clone(), fingerprint))
+				.map_err(|error| DispatchErrorWithPostInfo {
+					post_info: Pays::Yes.into(),
+					error,
+				})?;
 
 			// Emit new BSP volunteer event.
 			Self::deposit_event(Event::NewBspVolunteer {
@@ -214,7 +706,560 @@ pub mod pallet {
 				bsp_multiaddress,
 			});
 
+			Ok(Pays::No.into())
+		}
+
+		/// Request to store several files at once, all sent from the same peer and picking the
+		/// same MSP.
+		///
+		/// Either all the storage requests are registered, or none is.
+		#[pallet::call_index(2)]
+		#[pallet::weight(
+			T::WeightInfo::request_storage_batch(requests.len() as u32)
+				.saturating_add(T::FileSystemHooks::weight().saturating_mul(requests.len() as u64))
+		)]
+		pub fn request_storage_batch(
+			origin: OriginFor<T>,
+			requests: StorageRequestBatch<T>,
+			msp: T::AccountId,
+			sender_multiaddress: MultiAddress<T>,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Check that the sender multiaddress is valid.
+			Self::ensure_valid_multiaddress(&sender_multiaddress)?;
+
+			// Perform validations and register every storage request.
+			for (location, fingerprint, size) in requests.iter() {
+				Self::do_request_storage(
+					who.clone(),
+					location.clone(),
+					*fingerprint,
+					*size,
+					msp.clone(),
+					sender_multiaddress.clone(),
+				)?;
+			}
+
+			// Emit a single event for the whole batch.
+			Self::deposit_event(Event::NewStorageRequestBatch {
+				who,
+				requests,
+				msp,
+				sender_multiaddress,
+			});
+
 			Ok(())
 		}
+
+		/// Upload a new version of a stored file, sent from `sender_multiaddress`.
+		///
+		/// The BSPs storing the file have to fetch it and confirm it with
+		/// [`Pallet::bsp_confirm_update`] before the previous version is released. A new update
+		/// replaces any update that is still pending. While an update is pending, no BSP can
+		/// volunteer for the file, so that only the BSPs that were sent the new version have to
+		/// confirm it.
+		#[pallet::call_index(3)]
+		#[pallet::weight(T::WeightInfo::update_file())]
+		pub fn update_file(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+			fingerprint: Fingerprint<T>,
+			size: StorageCount<T>,
+			sender_multiaddress: MultiAddress<T>,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Check that the sender multiaddress is valid.
+			Self::ensure_valid_multiaddress(&sender_multiaddress)?;
+
+			// Perform validations and register the pending update.
+			let bsps = Self::do_update_file(who.clone(), location.clone(), fingerprint, size)?;
+
+			// Emit file update requested event.
+			Self::deposit_event(Event::FileUpdateRequested {
+				who,
+				location,
+				fingerprint,
+				size,
+				sender_multiaddress,
+				bsps,
+			});
+
+			Ok(())
+		}
+
+		/// Confirm, as one of its BSPs, that the new version of a file is stored.
+		///
+		/// Once every BSP of the file has confirmed it, the new version replaces the current one.
+		#[pallet::call_index(4)]
+		#[pallet::weight(
+			T::WeightInfo::bsp_confirm_update().saturating_add(T::FileSystemHooks::weight())
+		)]
+		pub fn bsp_confirm_update(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+			fingerprint: Fingerprint<T>,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Perform validations and register the BSP confirmation.
+			Self::do_bsp_confirm_update(who.clone(), location.clone(), fingerprint)?;
+
+			// Emit file update confirmed event.
+			Self::deposit_event(Event::FileUpdateConfirmed {
+				who,
+				location: location.clone(),
+				fingerprint,
+			});
+
+			// Replace the current version if every BSP has confirmed it.
+			Self::try_complete_file_update(&location);
+
+			Ok(())
+		}
+
+		/// Make a file public, or private so that only its owner, its BSPs and the accounts in
+		/// its allow-list can read it.
+		#[pallet::call_index(5)]
+		#[pallet::weight(T::WeightInfo::set_file_visibility())]
+		pub fn set_file_visibility(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+			is_public: bool,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Perform validations and update the file visibility.
+			Self::do_set_file_visibility(who, location.clone(), is_public)?;
+
+			// Emit file visibility changed event.
+			Self::deposit_event(Event::FileVisibilityChanged { location, is_public });
+
+			Ok(())
+		}
+
+		/// Allow `reader` to read a private file.
+		#[pallet::call_index(6)]
+		#[pallet::weight(T::WeightInfo::grant_read_access())]
+		pub fn grant_read_access(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+			reader: T::AccountId,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Check that the sender owns the file.
+			Self::ensure_file_owner(&who, &location)?;
+
+			// Add reader to the allow-list.
+			<FileReaders<T>>::insert(&location, &reader, ());
+
+			// Emit read access granted event.
+			Self::deposit_event(Event::ReadAccessGranted { location, reader });
+
+			Ok(())
+		}
+
+		/// Remove `reader` from the allow-list of a private file.
+		#[pallet::call_index(7)]
+		#[pallet::weight(T::WeightInfo::revoke_read_access())]
+		pub fn revoke_read_access(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+			reader: T::AccountId,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Check that the sender owns the file.
+			Self::ensure_file_owner(&who, &location)?;
+
+			// Remove reader from the allow-list.
+			<FileReaders<T>>::remove(&location, &reader);
+
+			// Emit read access revoked event.
+			Self::deposit_event(Event::ReadAccessRevoked { location, reader });
+
+			Ok(())
+		}
+
+		/// Register the libp2p PeerId of a node operated by the sender.
+		///
+		/// Nodes serving private files use it to know which account is requesting them. The
+		/// PeerId has to be derived from an ed25519 key, and `signature` has to be the signature
+		/// of [`Pallet::peer_id_registration_payload`] by that key, so that nobody can claim a
+		/// PeerId they do not control.
+		#[pallet::call_index(8)]
+		#[pallet::weight(T::WeightInfo::register_peer_id())]
+		pub fn register_peer_id(
+			origin: OriginFor<T>,
+			peer_id: PeerId<T>,
+			signature: ed25519::Signature,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Check that the PeerId is not registered yet.
+			ensure!(
+				!<PeerAccounts<T>>::contains_key(&peer_id),
+				Error::<T>::PeerIdAlreadyRegistered
+			);
+
+			// Check that the key of the PeerId signed its registration by the sender.
+			Self::ensure_peer_id_signature(&who, &peer_id, &signature)?;
+
+			// Register PeerId.
+			<PeerAccounts<T>>::insert(&peer_id, &who);
+
+			// Emit PeerId registered event.
+			Self::deposit_event(Event::PeerIdRegistered { who, peer_id });
+
+			Ok(())
+		}
+
+		/// Unregister a libp2p PeerId registered by the sender.
+		#[pallet::call_index(9)]
+		#[pallet::weight(T::WeightInfo::unregister_peer_id())]
+		pub fn unregister_peer_id(origin: OriginFor<T>, peer_id: PeerId<T>) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Check that the PeerId is registered by the sender.
+			ensure!(
+				<PeerAccounts<T>>::get(&peer_id) == Some(who.clone()),
+				Error::<T>::PeerIdNotRegistered
+			);
+
+			// Unregister PeerId.
+			<PeerAccounts<T>>::remove(&peer_id);
+
+			// Emit PeerId unregistered event.
+			Self::deposit_event(Event::PeerIdUnregistered { who, peer_id });
+
+			Ok(())
+		}
+
+		/// Set the storage quota of `who`, replacing the default one.
+		#[pallet::call_index(10)]
+		#[pallet::weight(T::WeightInfo::set_user_quota())]
+		pub fn set_user_quota(
+			origin: OriginFor<T>,
+			who: T::AccountId,
+			max_bytes: StorageCount<T>,
+			max_files: u32,
+		) -> DispatchResult {
+			// Check that the origin is allowed to set quotas.
+			T::QuotaOrigin::ensure_origin(origin)?;
+
+			// Set the quota.
+			<UserQuotas<T>>::insert(&who, StorageQuota::<T> { max_bytes, max_files });
+
+			// Emit user quota set event.
+			Self::deposit_event(Event::UserQuotaSet { who, max_bytes, max_files });
+
+			Ok(())
+		}
+
+		/// Accept to store a file, as the MSP picked for it.
+		///
+		/// The MSP becomes the main point to retrieve the file from, while its BSPs keep backing
+		/// it up.
+		///
+		/// Acceptance is trust-based: nothing checks that the MSP actually received the file,
+		/// neither an acknowledgement of its owner nor a proof of storage. The owner picked the
+		/// MSP and trusts its word, and it is the BSPs volunteering for the file that keep it
+		/// available if the MSP does not hold it.
+		#[pallet::call_index(11)]
+		#[pallet::weight(T::WeightInfo::msp_accept_storage_request())]
+		pub fn msp_accept_storage_request(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Perform validations and record that the MSP stores the file.
+			Self::do_msp_respond_storage_request(&who, &location, true)?;
+
+			// Emit MSP accepted storage request event.
+			Self::deposit_event(Event::MspAcceptedStorageRequest { msp: who, location });
+
+			Ok(())
+		}
+
+		/// Reject to store a file, as the MSP picked for it.
+		///
+		/// The file is then only stored by its BSPs.
+		#[pallet::call_index(12)]
+		#[pallet::weight(T::WeightInfo::msp_reject_storage_request())]
+		pub fn msp_reject_storage_request(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Perform validations and drop the MSP from the file.
+			Self::do_msp_respond_storage_request(&who, &location, false)?;
+
+			// Emit MSP rejected storage request event.
+			Self::deposit_event(Event::MspRejectedStorageRequest { msp: who, location });
+
+			Ok(())
+		}
+
+		/// Expire the pending update of a file, once its BSPs ran out of time to confirm it.
+		///
+		/// The BSPs that did not confirm it are penalized and no longer store the file, which
+		/// completes the update. If no BSP confirmed it, the update is dropped instead, as it was
+		/// likely never served. Anyone can call it.
+		#[pallet::call_index(13)]
+		#[pallet::weight(
+			T::WeightInfo::expire_file_update(T::MaxBsps::get()).saturating_add(
+				T::FileSystemHooks::weight().saturating_mul(T::MaxBsps::get() as u64 + 1)
+			)
+		)]
+		pub fn expire_file_update(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed.
+			ensure_signed(origin)?;
+
+			// Perform validations and expire the update.
+			Self::do_expire_file_update(&location)
+		}
+
+		/// Report that a BSP failed to prove that it stores a file, penalizing it.
+		///
+		/// Can only be called by [`Config::ChallengeOrigin`].
+		#[pallet::call_index(14)]
+		#[pallet::weight(T::WeightInfo::report_failed_challenge())]
+		pub fn report_failed_challenge(
+			origin: OriginFor<T>,
+			bsp: T::AccountId,
+			location: FileLocation<T>,
+		) -> DispatchResult {
+			// Check that the origin is allowed to report failed challenges.
+			T::ChallengeOrigin::ensure_origin(origin)?;
+
+			// Check that the BSP stores the file.
+			ensure!(<BspsFiles<T>>::contains_key(&bsp, &location), Error::<T>::NotFileBsp);
+
+			// Penalize the BSP.
+			T::Reputation::record_failure(&bsp, Failure::FailedChallenge);
+
+			// Emit challenge failed event.
+			Self::deposit_event(Event::ChallengeFailed { bsp, location });
+
+			Ok(())
+		}
+
+		/// Request to retrieve a file from `bsp`, one of the BSPs storing it, which has to send
+		/// it to `requester_multiaddress`.
+		///
+		/// The `fee` is reserved until the delivery is settled. Neither party can prove the
+		/// delivery on chain, so the requester trusts the BSP to send the file and the BSP trusts
+		/// the requester not to dispute it once received:
+		/// - The BSP claims the delivery with [`Pallet::bsp_confirm_delivery`] within
+		///   [`Config::RetrievalTimeout`] blocks, or anyone can refund the fee with
+		///   [`Pallet::refund_retrieval`].
+		/// - The requester then has [`Config::RetrievalDisputePeriod`] blocks to get the fee back
+		///   with [`Pallet::dispute_retrieval`], which is recorded in an event so BSPs can refuse
+		///   to serve requesters that dispute often.
+		/// - The fee is paid to the BSP with [`Pallet::confirm_retrieval`], by the requester at any
+		///   time after the claim, or by anyone once the dispute period ended.
+		#[pallet::call_index(15)]
+		#[pallet::weight(T::WeightInfo::request_retrieval())]
+		pub fn request_retrieval(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+			bsp: T::AccountId,
+			fee: BalanceOf<T>,
+			requester_multiaddress: MultiAddress<T>,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Check that the requester multiaddress is valid.
+			Self::ensure_valid_multiaddress(&requester_multiaddress)?;
+
+			// Perform validations, hold the fee and register the retrieval request.
+			Self::do_request_retrieval(&who, &location, &bsp, fee)?;
+
+			// Emit retrieval requested event.
+			Self::deposit_event(Event::RetrievalRequested {
+				who,
+				location,
+				bsp,
+				fee,
+				requester_multiaddress,
+			});
+
+			Ok(())
+		}
+
+		/// Claim, as the BSP it was requested from, that a file was delivered to `who`.
+		///
+		/// The fee stays reserved, since the claim is not proof of the delivery: `who` can still
+		/// dispute it with [`Pallet::dispute_retrieval`] for [`Config::RetrievalDisputePeriod`]
+		/// blocks.
+		#[pallet::call_index(16)]
+		#[pallet::weight(T::WeightInfo::bsp_confirm_delivery())]
+		pub fn bsp_confirm_delivery(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+			who: T::AccountId,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let bsp = ensure_signed(origin)?;
+
+			// Perform validations and mark the file as delivered.
+			Self::do_bsp_confirm_delivery(&bsp, &location, &who)?;
+
+			// Emit retrieval delivered event.
+			Self::deposit_event(Event::RetrievalDelivered { who, location, bsp });
+
+			Ok(())
+		}
+
+		/// Refund the fee of a retrieval request whose file was not delivered in time.
+		///
+		/// Anyone can call it.
+		#[pallet::call_index(17)]
+		#[pallet::weight(T::WeightInfo::refund_retrieval())]
+		pub fn refund_retrieval(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+			who: T::AccountId,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed.
+			ensure_signed(origin)?;
+
+			// Perform validations and refund the fee.
+			let fee = Self::do_refund_retrieval(&location, &who)?;
+
+			// Emit retrieval refunded event.
+			Self::deposit_event(Event::RetrievalRefunded { who, location, fee });
+
+			Ok(())
+		}
+
+		/// Pay the BSP the fee of a retrieval of the file at `location` requested by `who`, once
+		/// the BSP claimed to have delivered it.
+		///
+		/// `who` can call it at any time after the claim, and anyone else once
+		/// [`Config::RetrievalDisputePeriod`] blocks passed without a dispute.
+		#[pallet::call_index(18)]
+		#[pallet::weight(T::WeightInfo::confirm_retrieval())]
+		pub fn confirm_retrieval(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+			who: T::AccountId,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let sender = ensure_signed(origin)?;
+
+			// Perform validations and pay the fee to the BSP.
+			let (bsp, fee) = Self::do_confirm_retrieval(&sender, &location, &who)?;
+
+			// Emit retrieval paid event.
+			Self::deposit_event(Event::RetrievalPaid { who, location, bsp, fee });
+
+			Ok(())
+		}
+
+		/// Dispute, as its requester, the delivery of the file at `location` claimed by its BSP,
+		/// getting the retrieval fee back.
+		///
+		/// It can only be called within [`Config::RetrievalDisputePeriod`] blocks of the claim.
+		#[pallet::call_index(19)]
+		#[pallet::weight(T::WeightInfo::dispute_retrieval())]
+		pub fn dispute_retrieval(
+			origin: OriginFor<T>,
+			location: FileLocation<T>,
+		) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Perform validations and refund the fee.
+			let (bsp, fee) = Self::do_dispute_retrieval(&location, &who)?;
+
+			// Emit retrieval disputed event.
+			Self::deposit_event(Event::RetrievalDisputed { who, location, bsp, fee });
+
+			Ok(())
+		}
+	}
+}
+
+/// A handler for the lifecycle of files.
+///
+/// It allows dependent pallets, such as billing or reputation, to react to files being requested,
+/// stored and updated, and to BSPs joining or leaving them. Several handlers can be composed in a
+/// tuple.
+pub trait FileSystemHooks<T: Config> {
+	/// Called after `who` requests to store a file of `size` at `location`.
+	fn on_storage_requested(who: &T::AccountId, location: &FileLocation<T>, size: StorageCount<T>);
+
+	/// Called after `bsp` volunteers to store the file at `location`.
+	fn on_bsp_volunteered(bsp: &T::AccountId, location: &FileLocation<T>);
+
+	/// Called after the storage request of the file at `location` is fulfilled, once
+	/// [`Config::MaxBsps`] BSPs store it.
+	fn on_file_stored(location: &FileLocation<T>, file_metadata: &FileMetadata<T>);
+
+	/// Called after the file at `location` is replaced by its new version.
+	fn on_file_updated(location: &FileLocation<T>, file_metadata: &FileMetadata<T>);
+
+	/// Called after `bsp` stops storing the file at `location`, because it left.
+	fn on_bsp_removed(bsp: &T::AccountId, location: &FileLocation<T>);
+
+	/// Called after the last BSP storing the file at `location` stops storing it, so it is no
+	/// longer stored anywhere. Its storage request is reopened, and [`Self::on_file_stored`] is
+	/// called again if new BSPs fulfil it.
+	fn on_file_deleted(location: &FileLocation<T>, file_metadata: &FileMetadata<T>);
+
+	/// The weight of the heaviest of the hooks, accounted for once per hook call in the calls
+	/// that trigger them.
+	fn weight() -> Weight;
+}
+
+#[impl_trait_for_tuples::impl_for_tuples(30)]
+impl<T: Config> FileSystemHooks<T> for Tuple {
+	fn on_storage_requested(who: &T::AccountId, location: &FileLocation<T>, size: StorageCount<T>) {
+		for_tuples!( #( Tuple::on_storage_requested(who, location, size); )* );
+	}
+
+	fn on_bsp_volunteered(bsp: &T::AccountId, location: &FileLocation<T>) {
+		for_tuples!( #( Tuple::on_bsp_volunteered(bsp, location); )* );
+	}
+
+	fn on_file_stored(location: &FileLocation<T>, file_metadata: &FileMetadata<T>) {
+		for_tuples!( #( Tuple::on_file_stored(location, file_metadata); )* );
+	}
+
+	fn on_file_updated(location: &FileLocation<T>, file_metadata: &FileMetadata<T>) {
+		for_tuples!( #( Tuple::on_file_updated(location, file_metadata); )* );
+	}
+
+	fn on_bsp_removed(bsp: &T::AccountId, location: &FileLocation<T>) {
+		for_tuples!( #( Tuple::on_bsp_removed(bsp, location); )* );
+	}
+
+	fn on_file_deleted(location: &FileLocation<T>, file_metadata: &FileMetadata<T>) {
+		for_tuples!( #( Tuple::on_file_deleted(location, file_metadata); )* );
+	}
+
+	fn weight() -> Weight {
+		let mut weight = Weight::zero();
+		for_tuples!( #( weight.saturating_accrue(Tuple::weight()); )* );
+		weight
 	}
 }
@@ -0,0 +1,158 @@
+//! Storage migrations of the File System Pallet, one module per storage version.
+
+use frame_support::{
+	pallet_prelude::*,
+	traits::{GetStorageVersion, OnRuntimeUpgrade},
+};
+#[cfg(feature = "try-runtime")]
+use frame_support::{sp_runtime::TryRuntimeError, sp_std::vec::Vec};
+
+use crate::{Config, Pallet};
+
+/// Version 1, the first versioned storage.
+pub mod v1 {
+	use super::*;
+	use crate::{
+		types::{FileMetadata, Fingerprint, MaxBsps, StorageCount, StorageProviderId},
+		BspsFiles, FilesMapping, StorageRequests, TotalUsedBspStorage, UserStorageUsage,
+	};
+	use frame_support::sp_runtime::Saturating;
+	use frame_system::pallet_prelude::BlockNumberFor;
+
+	/// The metadata of files and storage requests before storage was versioned.
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct OldFileMetadata<T: Config> {
+		pub requested_at: BlockNumberFor<T>,
+		pub fingerprint: Fingerprint<T>,
+		pub bsps: BoundedVec<StorageProviderId<T>, MaxBsps<T>>,
+		pub is_public: bool,
+	}
+
+	impl<T: Config> OldFileMetadata<T> {
+		/// The metadata of version 1, filling in what was not recorded before.
+		fn migrate(self, owner: T::AccountId) -> FileMetadata<T> {
+			FileMetadata {
+				owner,
+				requested_at: self.requested_at,
+				fingerprint: self.fingerprint,
+				size: Default::default(),
+				bsps: self.bsps,
+				is_public: self.is_public,
+				msp: None,
+				msp_accepted: false,
+			}
+		}
+	}
+
+	/// Migrate unversioned storage to version 1.
+	///
+	/// Files and storage requests gain an owner, a size and an MSP. Who requested each file was
+	/// not recorded before, so they are all owned by `LegacyOwner`, and accounted in its storage
+	/// usage. Their size is unknown too and left at zero, and no MSP was picked for them. The
+	/// storage used by BSPs is recomputed from the migrated files, so that it matches the sizes
+	/// that are released when BSPs stop storing them. The files BSPs store are mapped to them, so
+	/// that they are reassigned if their BSPs leave.
+	pub struct MigrateToV1<T, LegacyOwner>(PhantomData<(T, LegacyOwner)>);
+
+	impl<T: Config, LegacyOwner: Get<T::AccountId>> OnRuntimeUpgrade for MigrateToV1<T, LegacyOwner> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T>::on_chain_storage_version() != 0 {
+				return T::DbWeight::get().reads(1)
+			}
+
+			let owner = LegacyOwner::get();
+			let mut files = 0u32;
+			let mut bsps_files = 0u64;
+			let mut used_bsps_storage = StorageCount::<T>::default();
+
+			// Stored files, which may also have an open storage request.
+			<FilesMapping<T>>::translate::<OldFileMetadata<T>, _>(|location, old| {
+				for bsp in old.bsps.iter() {
+					<BspsFiles<T>>::insert(bsp, &location, ());
+				}
+				files.saturating_inc();
+				bsps_files.saturating_accrue(old.bsps.len() as u64);
+
+				let file_metadata = old.migrate(owner.clone());
+				used_bsps_storage.saturating_accrue(
+					file_metadata.size.saturating_mul((file_metadata.bsps.len() as u32).into()),
+				);
+				Some(file_metadata)
+			});
+			let stored_files = files;
+			<TotalUsedBspStorage<T>>::put(used_bsps_storage);
+
+			// Storage requests, counting only the files not stored yet.
+			let mut storage_requests = 0u32;
+			<StorageRequests<T>>::translate::<OldFileMetadata<T>, _>(|location, old| {
+				if !<FilesMapping<T>>::contains_key(&location) {
+					files.saturating_inc();
+				}
+				storage_requests.saturating_inc();
+
+				Some(old.migrate(owner.clone()))
+			});
+
+			if files > 0 {
+				<UserStorageUsage<T>>::mutate(&owner, |usage| {
+					usage.files = usage.files.saturating_add(files)
+				});
+			}
+
+			StorageVersion::new(1).put::<Pallet<T>>();
+
+			let translated = (stored_files as u64).saturating_add(storage_requests as u64);
+			T::DbWeight::get().reads_writes(
+				// The version, every entry, whether each storage request is stored, and the usage.
+				translated.saturating_add(storage_requests as u64).saturating_add(2),
+				// Also the storage used by BSPs.
+				translated.saturating_add(bsps_files).saturating_add(3),
+			)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<Vec<u8>, TryRuntimeError> {
+			ensure!(
+				Pallet::<T>::on_chain_storage_version() <= 1,
+				"Storage version should be at most 1"
+			);
+
+			// The number of entries of each map, counted without decoding them.
+			let stored_files = <FilesMapping<T>>::iter_keys().count() as u32;
+			let storage_requests = <StorageRequests<T>>::iter_keys().count() as u32;
+
+			Ok((stored_files, storage_requests).encode())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(state: Vec<u8>) -> Result<(), TryRuntimeError> {
+			ensure!(Pallet::<T>::on_chain_storage_version() == 1, "Storage version should be 1");
+
+			// Every entry was kept, and can be decoded with the new layout.
+			let (stored_files, storage_requests) = <(u32, u32)>::decode(&mut &state[..])
+				.map_err(|_| "State should be the number of entries of each map")?;
+			ensure!(
+				<FilesMapping<T>>::iter().count() as u32 == stored_files,
+				"Every stored file should be migrated"
+			);
+			ensure!(
+				<StorageRequests<T>>::iter().count() as u32 == storage_requests,
+				"Every storage request should be migrated"
+			);
+
+			// The storage used by BSPs matches the migrated files.
+			let used_bsps_storage = <FilesMapping<T>>::iter_values().fold(
+				StorageCount::<T>::default(),
+				|used, file| {
+					used.saturating_add(file.size.saturating_mul((file.bsps.len() as u32).into()))
+				},
+			);
+			ensure!(
+				<TotalUsedBspStorage<T>>::get() == Some(used_bsps_storage),
+				"Storage used by BSPs should be recomputed from the migrated files"
+			);
+
+			Pallet::<T>::do_try_state()
+		}
+	}
+}
@@ -1,20 +1,38 @@
-use crate as pallet_file_system;
-use frame_support::traits::{ConstU128, ConstU16, ConstU32, ConstU64};
+use crate::{
+	self as pallet_file_system,
+	types::{FileLocation, FileMetadata},
+	FileSystemHooks,
+};
+use frame_support::{
+	parameter_types,
+	traits::{ConstU128, ConstU16, ConstU32, ConstU64},
+	weights::Weight,
+};
+use frame_system::EnsureRoot;
 use sp_core::H256;
+use sp_keystore::{testing::MemoryKeystore, KeystoreExt};
 use sp_runtime::{
+	testing::{TestSignature, TestXt, UintAuthorityId},
 	traits::{BlakeTwo256, IdentityLookup},
-	BuildStorage,
+	BuildStorage, Perbill,
 };
+use std::cell::RefCell;
 
 type Block = frame_system::mocking::MockBlock<Test>;
 
+/// The account registered as MSP from genesis.
+pub const MSP: u64 = 10;
+
 // Configure a mock runtime to test the pallet.
 frame_support::construct_runtime!(
 	pub enum Test
 	{
 		System: frame_system,
+		Balances: pallet_balances,
 		Identity: pallet_identity,
+		MspIdentity: pallet_identity::<Instance1>,
 		FileSystem: pallet_file_system,
+		Reputation: pallet_reputation,
 	}
 );
 
@@ -35,7 +53,7 @@ impl frame_system::Config for Test {
 	type BlockHashCount = ConstU64<250>;
 	type Version = ();
 	type PalletInfo = PalletInfo;
-	type AccountData = ();
+	type AccountData = pallet_balances::AccountData<u128>;
 	type OnNewAccount = ();
 	type OnKilledAccount = ();
 	type SystemWeightInfo = ();
@@ -44,25 +62,469 @@ impl frame_system::Config for Test {
 	type MaxConsumers = frame_support::traits::ConstU32<16>;
 }
 
+impl pallet_balances::Config for Test {
+	type Balance = u128;
+	type DustRemoval = ();
+	type RuntimeEvent = RuntimeEvent;
+	type ExistentialDeposit = ConstU128<1>;
+	type AccountStore = System;
+	type WeightInfo = ();
+	type MaxLocks = ConstU32<10>;
+	type MaxReserves = ConstU32<2>;
+	type ReserveIdentifier = [u8; 8];
+	type RuntimeHoldReason = ();
+	type FreezeIdentifier = ();
+	type MaxHolds = ();
+	type MaxFreezes = ();
+}
+
 impl pallet_identity::Config for Test {
 	type RuntimeEvent = RuntimeEvent;
 	type MaxUsers = ConstU128<1_000u128>;
+	type RegistrationOrigin = EnsureRoot<u64>;
+	type RemovalOrigin = EnsureRoot<u64>;
+	type OnIdentityChange = FileSystem;
+	type Currency = Balances;
+	type ApplicationDeposit = ConstU128<100u128>;
+}
+
+impl pallet_identity::Config<pallet_identity::Instance1> for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type MaxUsers = ConstU128<1_000u128>;
+	type RegistrationOrigin = EnsureRoot<u64>;
+	type RemovalOrigin = EnsureRoot<u64>;
+	type OnIdentityChange = ();
+	type Currency = Balances;
+	type ApplicationDeposit = ConstU128<100u128>;
+}
+
+/// Transactions submitted by the offchain worker.
+pub type Extrinsic = TestXt<RuntimeCall, ()>;
+
+impl frame_system::offchain::SigningTypes for Test {
+	type Public = UintAuthorityId;
+	type Signature = TestSignature;
+}
+
+impl<C> frame_system::offchain::SendTransactionTypes<C> for Test
+where
+	RuntimeCall: From<C>,
+{
+	type OverarchingCall = RuntimeCall;
+	type Extrinsic = Extrinsic;
+}
+
+impl<C> frame_system::offchain::CreateSignedTransaction<C> for Test
+where
+	RuntimeCall: From<C>,
+{
+	fn create_transaction<A: frame_system::offchain::AppCrypto<Self::Public, Self::Signature>>(
+		call: RuntimeCall,
+		_public: UintAuthorityId,
+		account: u64,
+		_nonce: u64,
+	) -> Option<(RuntimeCall, (u64, ()))> {
+		Some((call, (account, ())))
+	}
+}
+
+/// BSP keys of the mock keystore, set with [`UintAuthorityId::set_all_keys`].
+pub struct TestAuthorityId;
+
+impl frame_system::offchain::AppCrypto<UintAuthorityId, TestSignature> for TestAuthorityId {
+	type RuntimeAppPublic = UintAuthorityId;
+	type GenericPublic = UintAuthorityId;
+	type GenericSignature = TestSignature;
 }
 
 impl pallet_file_system::Config for Test {
 	type RuntimeEvent = RuntimeEvent;
 	type WeightInfo = ();
+	type AuthorityId = TestAuthorityId;
 	type BspsRegistry = Identity;
+	type MspsRegistry = MspIdentity;
 	type Fingerprint = H256;
 	type StorageCount = u128;
 	type AssignmentThreshold = u128;
 	type MaxBsps = ConstU32<5u32>;
+	type MaxBatchSize = ConstU32<10u32>;
+	type MaxFileSize = ConstU128<1_000u128>;
+	type DefaultMaxStoredBytes = ConstU128<10_000u128>;
+	type DefaultMaxStoredFiles = ConstU32<20u32>;
+	type QuotaOrigin = EnsureRoot<u64>;
+	type MaxFileVersions = ConstU32<3u32>;
 	type MaxFilePathSize = ConstU32<512u32>;
 	type MaxMultiAddressSize = ConstU32<512u32>;
-	type MinBspsAssignmentThreshold = ConstU128<{ u128::MAX }>;
+	type MaxPeerIdSize = ConstU32<64u32>;
+	type MaxOffchainVolunteers = ConstU32<2u32>;
+	type MinBspsAssignmentThreshold = MinBspsAssignmentThreshold;
+	type FileSystemHooks = RecordingHooks;
+	type Reputation = Reputation;
+	type ChallengeOrigin = EnsureRoot<u64>;
+	type UpdateConfirmationPeriod = ConstU64<10>;
+	type Currency = Balances;
+	type RetrievalTimeout = ConstU64<10>;
+	type RetrievalDisputePeriod = ConstU64<5>;
+	type RetrievalReserveId = RetrievalReserveId;
+}
+
+parameter_types! {
+	/// Every BSP is eligible as soon as a file is requested, unless a test lowers it.
+	pub static MinBspsAssignmentThreshold: u128 = u128::MAX;
+}
+
+parameter_types! {
+	pub const InitialScore: Perbill = Perbill::one();
+	pub const SuccessReward: Perbill = Perbill::from_percent(10);
+	pub const MissedDeadlinePenalty: Perbill = Perbill::from_percent(20);
+	pub const FailedChallengePenalty: Perbill = Perbill::from_percent(50);
+	pub const SlashFraction: Perbill = Perbill::from_percent(50);
+	pub const ReputationReserveId: [u8; 8] = *b"reputatn";
+	pub const RetrievalReserveId: [u8; 8] = *b"retrievl";
+}
+
+parameter_types! {
+	/// BSPs can volunteer without stake, unless a test raises it.
+	pub static MinStake: u128 = 0;
+}
+
+impl pallet_reputation::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type Currency = Balances;
+	type OnSlash = ();
+	type InitialScore = InitialScore;
+	type SuccessReward = SuccessReward;
+	type MissedDeadlinePenalty = MissedDeadlinePenalty;
+	type FailedChallengePenalty = FailedChallengePenalty;
+	type MaxFailures = ConstU32<2>;
+	type SlashFraction = SlashFraction;
+	type ReserveId = ReputationReserveId;
+	type MinStake = MinStake;
+	type UnbondingPeriod = ConstU64<10>;
+	type MaxUnbondingChunks = ConstU32<2>;
+}
+
+/// A call to one of the [`FileSystemHooks`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HookCall {
+	StorageRequested { who: u64, location: FileLocation<Test>, size: u128 },
+	BspVolunteered { bsp: u64, location: FileLocation<Test> },
+	FileStored { location: FileLocation<Test>, bsps: Vec<u64> },
+	FileUpdated { location: FileLocation<Test>, fingerprint: H256 },
+	BspRemoved { bsp: u64, location: FileLocation<Test> },
+	FileDeleted { location: FileLocation<Test>, owner: u64 },
+}
+
+thread_local! {
+	static HOOK_CALLS: RefCell<Vec<HookCall>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The calls made to the [`FileSystemHooks`] so far, in order.
+pub fn hook_calls() -> Vec<HookCall> {
+	HOOK_CALLS.with(|calls| calls.borrow().clone())
+}
+
+/// Hooks that record every call made to them.
+pub struct RecordingHooks;
+
+impl RecordingHooks {
+	fn record(call: HookCall) {
+		HOOK_CALLS.with(|calls| calls.borrow_mut().push(call));
+	}
+}
+
+impl FileSystemHooks<Test> for RecordingHooks {
+	fn on_storage_requested(who: &u64, location: &FileLocation<Test>, size: u128) {
+		Self::record(HookCall::StorageRequested { who: *who, location: location.clone(), size });
+	}
+
+	fn on_bsp_volunteered(bsp: &u64, location: &FileLocation<Test>) {
+		Self::record(HookCall::BspVolunteered { bsp: *bsp, location: location.clone() });
+	}
+
+	fn on_file_stored(location: &FileLocation<Test>, file_metadata: &FileMetadata<Test>) {
+		Self::record(HookCall::FileStored {
+			location: location.clone(),
+			bsps: file_metadata.bsps.to_vec(),
+		});
+	}
+
+	fn on_file_updated(location: &FileLocation<Test>, file_metadata: &FileMetadata<Test>) {
+		Self::record(HookCall::FileUpdated {
+			location: location.clone(),
+			fingerprint: file_metadata.fingerprint,
+		});
+	}
+
+	fn on_bsp_removed(bsp: &u64, location: &FileLocation<Test>) {
+		Self::record(HookCall::BspRemoved { bsp: *bsp, location: location.clone() });
+	}
+
+	fn on_file_deleted(location: &FileLocation<Test>, file_metadata: &FileMetadata<Test>) {
+		Self::record(HookCall::FileDeleted {
+			location: location.clone(),
+			owner: file_metadata.owner,
+		});
+	}
+
+	fn weight() -> Weight {
+		Weight::from_parts(1_000, 0)
+	}
 }
 
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
-	frame_system::GenesisConfig::<Test>::default().build_storage().unwrap().into()
+	let mut ext: sp_io::TestExternalities = RuntimeGenesisConfig {
+		msp_identity: MspIdentityConfig { users: vec![MSP], ..Default::default() },
+		..Default::default()
+	}
+	.build_storage()
+	.unwrap()
+	.into();
+	// The benchmarks generate the keys of the PeerIds they register.
+	ext.register_extension(KeystoreExt::new(MemoryKeystore::new()));
+	ext
+}
+
+/// A mock runtime whose fingerprints are narrower than its assignment threshold, so no BSP can
+/// volunteer for its files.
+pub mod narrow_fingerprint {
+	use super::*;
+	use codec::{Decode, Encode, MaxEncodedLen};
+	use scale_info::TypeInfo;
+	use sp_runtime::{
+		serde::{Deserialize, Serialize},
+		traits::CheckEqual,
+	};
+
+	type Block = frame_system::mocking::MockBlock<Test>;
+
+	/// A fingerprint of 8 bytes, narrower than the `u128` assignment threshold.
+	#[derive(
+		Clone,
+		Copy,
+		Debug,
+		Default,
+		PartialEq,
+		Eq,
+		PartialOrd,
+		Ord,
+		Encode,
+		Decode,
+		MaxEncodedLen,
+		TypeInfo,
+		Serialize,
+		Deserialize,
+	)]
+	#[serde(crate = "sp_runtime::serde")]
+	pub struct NarrowFingerprint(pub [u8; 8]);
+
+	impl core::fmt::Display for NarrowFingerprint {
+		fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+			write!(f, "{:?}", self.0)
+		}
+	}
+
+	impl core::ops::BitOr for NarrowFingerprint {
+		type Output = Self;
+
+		fn bitor(self, rhs: Self) -> Self {
+			Self(core::array::from_fn(|i| self.0[i] | rhs.0[i]))
+		}
+	}
+
+	impl core::ops::BitXor for NarrowFingerprint {
+		type Output = Self;
+
+		fn bitxor(self, rhs: Self) -> Self {
+			Self(core::array::from_fn(|i| self.0[i] ^ rhs.0[i]))
+		}
+	}
+
+	impl core::ops::BitAnd for NarrowFingerprint {
+		type Output = Self;
+
+		fn bitand(self, rhs: Self) -> Self {
+			Self(core::array::from_fn(|i| self.0[i] & rhs.0[i]))
+		}
+	}
+
+	impl CheckEqual for NarrowFingerprint {
+		fn check_equal(&self, other: &Self) {
+			assert_eq!(self, other);
+		}
+	}
+
+	impl AsRef<[u8]> for NarrowFingerprint {
+		fn as_ref(&self) -> &[u8] {
+			&self.0
+		}
+	}
+
+	impl AsMut<[u8]> for NarrowFingerprint {
+		fn as_mut(&mut self) -> &mut [u8] {
+			&mut self.0
+		}
+	}
+
+	frame_support::construct_runtime!(
+		pub enum Test
+		{
+			System: frame_system,
+			Balances: pallet_balances,
+			Identity: pallet_identity,
+			MspIdentity: pallet_identity::<Instance1>,
+			FileSystem: pallet_file_system,
+			Reputation: pallet_reputation,
+		}
+	);
+
+	impl frame_system::Config for Test {
+		type BaseCallFilter = frame_support::traits::Everything;
+		type BlockWeights = ();
+		type BlockLength = ();
+		type DbWeight = ();
+		type RuntimeOrigin = RuntimeOrigin;
+		type RuntimeCall = RuntimeCall;
+		type Nonce = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Block = Block;
+		type RuntimeEvent = RuntimeEvent;
+		type BlockHashCount = ConstU64<250>;
+		type Version = ();
+		type PalletInfo = PalletInfo;
+		type AccountData = pallet_balances::AccountData<u128>;
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+		type SS58Prefix = ConstU16<42>;
+		type OnSetCode = ();
+		type MaxConsumers = frame_support::traits::ConstU32<16>;
+	}
+
+	impl pallet_balances::Config for Test {
+		type Balance = u128;
+		type DustRemoval = ();
+		type RuntimeEvent = RuntimeEvent;
+		type ExistentialDeposit = ConstU128<1>;
+		type AccountStore = System;
+		type WeightInfo = ();
+		type MaxLocks = ConstU32<10>;
+		type MaxReserves = ConstU32<2>;
+		type ReserveIdentifier = [u8; 8];
+		type RuntimeHoldReason = ();
+		type FreezeIdentifier = ();
+		type MaxHolds = ();
+		type MaxFreezes = ();
+	}
+
+	impl pallet_identity::Config for Test {
+		type RuntimeEvent = RuntimeEvent;
+		type MaxUsers = ConstU128<1_000u128>;
+		type RegistrationOrigin = EnsureRoot<u64>;
+		type RemovalOrigin = EnsureRoot<u64>;
+		type OnIdentityChange = FileSystem;
+		type Currency = Balances;
+		type ApplicationDeposit = ConstU128<100u128>;
+	}
+
+	impl pallet_identity::Config<pallet_identity::Instance1> for Test {
+		type RuntimeEvent = RuntimeEvent;
+		type MaxUsers = ConstU128<1_000u128>;
+		type RegistrationOrigin = EnsureRoot<u64>;
+		type RemovalOrigin = EnsureRoot<u64>;
+		type OnIdentityChange = ();
+		type Currency = Balances;
+		type ApplicationDeposit = ConstU128<100u128>;
+	}
+
+	impl frame_system::offchain::SigningTypes for Test {
+		type Public = UintAuthorityId;
+		type Signature = TestSignature;
+	}
+
+	impl<C> frame_system::offchain::SendTransactionTypes<C> for Test
+	where
+		RuntimeCall: From<C>,
+	{
+		type OverarchingCall = RuntimeCall;
+		type Extrinsic = TestXt<RuntimeCall, ()>;
+	}
+
+	impl<C> frame_system::offchain::CreateSignedTransaction<C> for Test
+	where
+		RuntimeCall: From<C>,
+	{
+		fn create_transaction<
+			A: frame_system::offchain::AppCrypto<Self::Public, Self::Signature>,
+		>(
+			call: RuntimeCall,
+			_public: UintAuthorityId,
+			account: u64,
+			_nonce: u64,
+		) -> Option<(RuntimeCall, (u64, ()))> {
+			Some((call, (account, ())))
+		}
+	}
+
+	impl pallet_file_system::Config for Test {
+		type RuntimeEvent = RuntimeEvent;
+		type WeightInfo = ();
+		type AuthorityId = TestAuthorityId;
+		type BspsRegistry = Identity;
+		type MspsRegistry = MspIdentity;
+		type Fingerprint = NarrowFingerprint;
+		type StorageCount = u128;
+		type AssignmentThreshold = u128;
+		type MaxBsps = ConstU32<5u32>;
+		type MaxBatchSize = ConstU32<10u32>;
+		type MaxFileSize = ConstU128<1_000u128>;
+		type DefaultMaxStoredBytes = ConstU128<10_000u128>;
+		type DefaultMaxStoredFiles = ConstU32<20u32>;
+		type QuotaOrigin = EnsureRoot<u64>;
+		type MaxFileVersions = ConstU32<3u32>;
+		type MaxFilePathSize = ConstU32<512u32>;
+		type MaxMultiAddressSize = ConstU32<512u32>;
+		type MaxPeerIdSize = ConstU32<64u32>;
+		type MaxOffchainVolunteers = ConstU32<2u32>;
+		type MinBspsAssignmentThreshold = MinBspsAssignmentThreshold;
+		type FileSystemHooks = ();
+		type Reputation = Reputation;
+		type ChallengeOrigin = EnsureRoot<u64>;
+		type UpdateConfirmationPeriod = ConstU64<10>;
+		type Currency = Balances;
+		type RetrievalTimeout = ConstU64<10>;
+		type RetrievalDisputePeriod = ConstU64<5>;
+		type RetrievalReserveId = RetrievalReserveId;
+	}
+
+	impl pallet_reputation::Config for Test {
+		type RuntimeEvent = RuntimeEvent;
+		type Currency = Balances;
+		type OnSlash = ();
+		type InitialScore = InitialScore;
+		type SuccessReward = SuccessReward;
+		type MissedDeadlinePenalty = MissedDeadlinePenalty;
+		type FailedChallengePenalty = FailedChallengePenalty;
+		type MaxFailures = ConstU32<2>;
+		type SlashFraction = SlashFraction;
+		type ReserveId = ReputationReserveId;
+		type MinStake = ConstU128<0>;
+		type UnbondingPeriod = ConstU64<10>;
+		type MaxUnbondingChunks = ConstU32<2>;
+	}
+
+	// Build genesis storage according to the mock runtime.
+	pub fn new_test_ext() -> sp_io::TestExternalities {
+		RuntimeGenesisConfig {
+			msp_identity: MspIdentityConfig { users: vec![MSP], ..Default::default() },
+			..Default::default()
+		}
+		.build_storage()
+		.unwrap()
+		.into()
+	}
 }
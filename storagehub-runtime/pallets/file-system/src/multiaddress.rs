@@ -0,0 +1,132 @@
+//! Lightweight validation of libp2p multiaddresses, usable from the runtime.
+//!
+//! Multiaddresses are submitted in their text form, which is what the storage providers parse
+//! back from the events, e.g. `/ip4/10.0.0.1/tcp/30333/p2p/12D3KooW...`. Only the protocols a
+//! storage provider can be dialed through are supported, and the multiaddress has to end with
+//! the `/p2p/` component identifying the peer.
+
+use core::{
+	net::{Ipv4Addr, Ipv6Addr},
+	str::FromStr,
+};
+use scale_info::prelude::vec::Vec;
+
+/// The alphabet of the base58btc encoding used for PeerIds.
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Length of the longest base58btc encoded PeerId: a multihash with a 42 bytes digest.
+const MAX_ENCODED_PEER_ID_LENGTH: usize = 61;
+
+/// Multihash code of the identity hash, used by PeerIds that inline their public key.
+const IDENTITY_MULTIHASH_CODE: u8 = 0x00;
+
+/// Longest public key a PeerId can inline.
+const MAX_INLINE_KEY_LENGTH: usize = 42;
+
+/// Multihash code of SHA2-256, used by PeerIds of larger public keys.
+const SHA2_256_MULTIHASH_CODE: u8 = 0x12;
+
+/// Length of a SHA2-256 digest.
+const SHA2_256_LENGTH: usize = 32;
+
+/// Protobuf header of an inlined ed25519 public key: the `Ed25519` key type field, followed by
+/// the tag and length of its 32 bytes data field.
+const ED25519_KEY_HEADER: [u8; 4] = [0x08, 0x01, 0x12, 0x20];
+
+/// Whether `multiaddress` is a valid multiaddress ending with a `/p2p/` component.
+pub fn is_valid(multiaddress: &[u8]) -> bool {
+	let Ok(multiaddress) = core::str::from_utf8(multiaddress) else { return false };
+
+	// Every component starts with a `/`.
+	let Some(multiaddress) = multiaddress.strip_prefix('/') else { return false };
+
+	let mut components = multiaddress.split('/');
+	let mut ends_with_peer_id = false;
+	while let Some(protocol) = components.next() {
+		let is_valid_component = match protocol {
+			"ip4" => components.next().is_some_and(|ip| Ipv4Addr::from_str(ip).is_ok()),
+			"ip6" => components.next().is_some_and(|ip| Ipv6Addr::from_str(ip).is_ok()),
+			"dns" | "dns4" | "dns6" | "dnsaddr" => components.next().is_some_and(is_valid_domain),
+			"tcp" | "udp" => components.next().is_some_and(|port| u16::from_str(port).is_ok()),
+			"p2p" => components.next().is_some_and(is_valid_peer_id),
+			"quic" | "quic-v1" | "tls" | "noise" | "ws" | "wss" | "webrtc-direct" |
+			"p2p-circuit" => true,
+			_ => false,
+		};
+
+		if !is_valid_component {
+			return false
+		}
+
+		ends_with_peer_id = protocol == "p2p";
+	}
+
+	ends_with_peer_id
+}
+
+/// Whether `domain` is a non-empty domain name.
+fn is_valid_domain(domain: &str) -> bool {
+	!domain.is_empty() &&
+		domain.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'.')
+}
+
+/// Whether `peer_id` is a base58btc encoded PeerId.
+///
+/// A PeerId is the multihash of a public key: either the key itself when it is short enough,
+/// or its SHA2-256 digest.
+fn is_valid_peer_id(peer_id: &str) -> bool {
+	if peer_id.len() > MAX_ENCODED_PEER_ID_LENGTH {
+		return false
+	}
+
+	let Some(multihash) = decode_base58(peer_id) else { return false };
+	let [code, length, digest @ ..] = multihash.as_slice() else { return false };
+
+	let is_valid_digest = match *code {
+		IDENTITY_MULTIHASH_CODE => digest.len() <= MAX_INLINE_KEY_LENGTH,
+		SHA2_256_MULTIHASH_CODE => digest.len() == SHA2_256_LENGTH,
+		_ => false,
+	};
+
+	is_valid_digest && digest.len() == *length as usize
+}
+
+/// The ed25519 public key inlined in the binary form of `peer_id`, or `None` if it was not
+/// derived from an ed25519 key.
+pub fn ed25519_public_key(peer_id: &[u8]) -> Option<[u8; 32]> {
+	let [IDENTITY_MULTIHASH_CODE, length, key @ ..] = peer_id else { return None };
+	let key = key.strip_prefix(&ED25519_KEY_HEADER)?;
+
+	if *length as usize != ED25519_KEY_HEADER.len() + key.len() {
+		return None
+	}
+
+	key.try_into().ok()
+}
+
+/// Decode a base58btc string, or `None` if it has characters outside of the alphabet.
+fn decode_base58(encoded: &str) -> Option<Vec<u8>> {
+	// The decoded bytes, least significant first.
+	let mut decoded: Vec<u8> = Vec::with_capacity(encoded.len());
+
+	for c in encoded.bytes() {
+		let mut carry = BASE58_ALPHABET.iter().position(|&digit| digit == c)? as u32;
+
+		for byte in decoded.iter_mut() {
+			carry += (*byte as u32) * 58;
+			*byte = carry as u8;
+			carry >>= 8;
+		}
+
+		while carry > 0 {
+			decoded.push(carry as u8);
+			carry >>= 8;
+		}
+	}
+
+	// Every leading `1` encodes a leading zero byte.
+	decoded.extend(encoded.bytes().take_while(|&c| c == BASE58_ALPHABET[0]).map(|_| 0));
+	decoded.reverse();
+
+	Some(decoded)
+}
@@ -0,0 +1,113 @@
+//! Validation of `bsp_volunteer` transactions before they enter a block.
+//!
+//! Volunteering is free for BSPs that qualify, so the transaction pool has to reject those that
+//! do not, as they would otherwise fill blocks with failed calls nobody pays for.
+
+use codec::{Decode, Encode};
+use core::marker::PhantomData;
+use frame_support::{pallet_prelude::*, traits::IsSubType};
+use scale_info::TypeInfo;
+use sp_runtime::{
+	traits::{DispatchInfoOf, SignedExtension},
+	transaction_validity::{
+		InvalidTransaction, TransactionValidity, TransactionValidityError, ValidTransaction,
+	},
+	ModuleError,
+};
+
+use crate::{Call, Config, Pallet};
+
+/// Reject `bsp_volunteer` transactions from BSPs that do not qualify for the storage request, and
+/// those from BSPs that already have one for the same storage request in the pool.
+///
+/// Only transactions entering the pool are checked. Those already included in a block are
+/// dispatched as usual, so BSPs that stopped qualifying in the meantime pay the fees. Only
+/// top-level `bsp_volunteer` calls are checked, as they are the only feeless ones: wrapped in
+/// another call, e.g. `Proxy::proxy`, a volunteer is charged for the outer call.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, TypeInfo)]
+#[scale_info(skip_type_params(T))]
+pub struct CheckBspVolunteer<T: Config + Send + Sync>(PhantomData<T>);
+
+impl<T: Config + Send + Sync> CheckBspVolunteer<T> {
+	/// Create new `SignedExtension` to check `bsp_volunteer` transactions.
+	pub fn new() -> Self {
+		Self(PhantomData)
+	}
+}
+
+impl<T: Config + Send + Sync> Default for CheckBspVolunteer<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T: Config + Send + Sync> core::fmt::Debug for CheckBspVolunteer<T> {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+		write!(f, "CheckBspVolunteer")
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut core::fmt::Formatter) -> core::fmt::Result {
+		Ok(())
+	}
+}
+
+impl<T: Config + Send + Sync> SignedExtension for CheckBspVolunteer<T>
+where
+	<T as frame_system::Config>::RuntimeCall: IsSubType<Call<T>>,
+{
+	const IDENTIFIER: &'static str = "CheckBspVolunteer";
+	type AccountId = T::AccountId;
+	type Call = <T as frame_system::Config>::RuntimeCall;
+	type AdditionalSigned = ();
+	type Pre = ();
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	fn validate(
+		&self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		_info: &DispatchInfoOf<Self::Call>,
+		_len: usize,
+	) -> TransactionValidity {
+		let Some(Call::bsp_volunteer { location, fingerprint, bsp_multiaddress }) =
+			call.is_sub_type()
+		else {
+			return Ok(ValidTransaction::default())
+		};
+
+		// Check that the BSP multiaddress is valid, and that the BSP qualifies.
+		Pallet::<T>::ensure_valid_multiaddress(bsp_multiaddress)
+			.and_then(|_| Pallet::<T>::check_bsp_volunteer(who, location, fingerprint).map(|_| ()))
+			.map_err(invalid_volunteer)?;
+
+		// Only one volunteer transaction per BSP and storage request can be in the pool.
+		ValidTransaction::with_tag_prefix(Self::IDENTIFIER)
+			.and_provides((who, location))
+			.build()
+	}
+
+	fn pre_dispatch(
+		self,
+		_who: &Self::AccountId,
+		_call: &Self::Call,
+		_info: &DispatchInfoOf<Self::Call>,
+		_len: usize,
+	) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+}
+
+/// The invalid transaction error of a volunteer that does not qualify, with the index of the
+/// pallet error as custom code.
+fn invalid_volunteer(error: DispatchError) -> TransactionValidityError {
+	match error {
+		DispatchError::Module(ModuleError { error, .. }) =>
+			InvalidTransaction::Custom(error[0]).into(),
+		_ => InvalidTransaction::Call.into(),
+	}
+}
@@ -1,10 +1,61 @@
-use crate::{mock::*, types::FileLocation, Event};
-use frame_support::assert_ok;
+use crate::{
+	migrations::v1::{MigrateToV1, OldFileMetadata},
+	mock::*,
+	types::{FileLocation, FileMetadata, FileVersion, MultiAddress, PeerId, StorageRequestBatch},
+	utils::xor_distance,
+	weights::{EstimatedWeight, WeightInfo},
+	BspsFiles, CheckBspVolunteer, CurrentAssignmentThreshold, Error, Event, FileReaders,
+	FileSystemHooks, FileUpdates, FilesMapping, LeavingBsps, PeerAccounts, RetrievalRequests,
+	StorageRequestSenders, StorageRequests, TotalUsedBspStorage, UserStorageUsage,
+	BSP_MULTIADDRESS_KEY, STORAGE_VERSION,
+};
+use codec::{Decode, Encode};
+use frame_support::{
+	assert_noop, assert_ok,
+	dispatch::{GetDispatchInfo, Pays},
+	storage::unhashed,
+	traits::{
+		ConstU64, Currency, GetStorageVersion, Hooks, NamedReservableCurrency, OnRuntimeUpgrade,
+		StorageVersion,
+	},
+	weights::Weight,
+};
+use pallet_reputation::{Failure, ReputationInterface};
+use sp_core::{
+	ed25519,
+	offchain::{
+		testing::{TestOffchainExt, TestTransactionPoolExt},
+		OffchainDbExt, OffchainWorkerExt, TransactionPoolExt,
+	},
+	Pair, H256,
+};
 use sp_runtime::{
-	traits::{BlakeTwo256, Hash},
-	BoundedVec,
+	offchain::storage::StorageValueRef,
+	testing::UintAuthorityId,
+	traits::{BlakeTwo256, Dispatchable, Hash, SignedExtension},
+	transaction_validity::{InvalidTransaction, TransactionValidityError},
+	BoundedVec, BuildStorage, DispatchError, ModuleError, Perbill,
 };
 
+/// The owner of the files migrated from before storage was versioned.
+const LEGACY_OWNER: u64 = 100;
+
+/// The multiaddress of the peer sending the files.
+fn sender_multiaddress() -> MultiAddress<Test> {
+	b"/ip4/127.0.0.1/tcp/23456/p2p/12D3KooWAWERvhQ4if5aum3FGhdTnTervVpxMSiy99VoHbK4s4ZX"
+		.to_vec()
+		.try_into()
+		.unwrap()
+}
+
+/// The multiaddress of the BSP volunteering for the files.
+fn bsp_multiaddress() -> MultiAddress<Test> {
+	b"/ip4/127.0.0.1/tcp/34567/p2p/12D3KooWHENiEV7ArLUMawSVSZX7ynzCXSqtDQyWyR5Uf9oJBR6t"
+		.to_vec()
+		.try_into()
+		.unwrap()
+}
+
 #[test]
 fn request_storage_success() {
 	new_test_ext().execute_with(|| {
@@ -22,7 +73,8 @@ fn request_storage_success() {
 			location.clone(),
 			content_id.clone(),
 			4,
-			BoundedVec::try_from(vec![1]).unwrap(),
+			MSP,
+			sender_multiaddress(),
 		));
 
 		// Assert that the correct event was deposited
@@ -32,13 +84,105 @@ fn request_storage_success() {
 				location,
 				fingerprint: content_id,
 				size: 4,
-				sender_multiaddress: BoundedVec::try_from(vec![1]).unwrap(),
+				msp: MSP,
+				sender_multiaddress: sender_multiaddress(),
+			}
+			.into(),
+		);
+	});
+}
+
+#[test]
+fn request_storage_batch_success() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let user = RuntimeOrigin::signed(1);
+		let requests: StorageRequestBatch<Test> = (0..3u8)
+			.map(|i| {
+				let location = FileLocation::<Test>::try_from(vec![b'a' + i]).unwrap();
+				let fingerprint = BlakeTwo256::hash(&[i]);
+				(location, fingerprint, 4)
+			})
+			.collect::<Vec<_>>()
+			.try_into()
+			.unwrap();
+
+		// Dispatch batch of storage requests.
+		assert_ok!(FileSystem::request_storage_batch(
+			user.clone(),
+			requests.clone(),
+			MSP,
+			sender_multiaddress(),
+		));
+
+		// Check that every storage request is registered.
+		for (location, fingerprint, _) in requests.iter() {
+			let storage_request = StorageRequests::<Test>::get(location).unwrap();
+			assert_eq!(storage_request.fingerprint, *fingerprint);
+			assert_eq!(storage_request.requested_at, 1);
+		}
+
+		// Assert that a single event was deposited for the whole batch
+		System::assert_last_event(
+			Event::NewStorageRequestBatch {
+				who: 1,
+				requests,
+				msp: MSP,
+				sender_multiaddress: sender_multiaddress(),
 			}
 			.into(),
 		);
+		assert_eq!(System::events().len(), 1);
+	});
+}
+
+#[test]
+fn request_storage_batch_is_atomic() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let user = RuntimeOrigin::signed(1);
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let other_location = FileLocation::<Test>::try_from(b"other".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+
+		// Register a storage request for the second file of the batch.
+		assert_ok!(FileSystem::request_storage(
+			user.clone(),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+
+		// Dispatch batch including an already registered storage request.
+		let requests: StorageRequestBatch<Test> =
+			vec![(other_location.clone(), fingerprint, 4), (location, fingerprint, 4)]
+				.try_into()
+				.unwrap();
+		assert_noop!(
+			FileSystem::request_storage_batch(user, requests, MSP, sender_multiaddress(),),
+			Error::<Test>::StorageRequestAlreadyRegistered
+		);
+
+		// Check that the rest of the batch was not registered.
+		assert!(!StorageRequests::<Test>::contains_key(&other_location));
 	});
 }
 
+#[test]
+fn request_storage_batch_weighs_every_request() {
+	// Every request in a batch reads and writes as much as a single storage request.
+	for n in [1, 2, 32] {
+		let single = EstimatedWeight::<Test>::request_storage().saturating_mul(n.into());
+		assert!(EstimatedWeight::<Test>::request_storage_batch(n).all_gte(single));
+	}
+}
+
 #[test]
 fn bsp_volunteer_success() {
 	new_test_ext().execute_with(|| {
@@ -60,7 +204,8 @@ fn bsp_volunteer_success() {
 			location.clone(),
 			fingerprint.clone(),
 			4,
-			BoundedVec::try_from(vec![1]).unwrap(),
+			MSP,
+			sender_multiaddress(),
 		));
 
 		// Dispatch BSP volunteer.
@@ -68,7 +213,7 @@ fn bsp_volunteer_success() {
 			bsp.clone(),
 			location.clone(),
 			fingerprint.clone(),
-			BoundedVec::try_from(vec![2]).unwrap()
+			bsp_multiaddress()
 		));
 
 		// Assert that the correct event was deposited
@@ -77,9 +222,2198 @@ fn bsp_volunteer_success() {
 				who: 2,
 				location,
 				fingerprint,
-				bsp_multiaddress: BoundedVec::try_from(vec![2]).unwrap(),
+				bsp_multiaddress: bsp_multiaddress(),
+			}
+			.into(),
+		);
+
+		// Check that the storage used by BSPs counts the file.
+		assert_eq!(FileSystem::total_used_bsps_storage(), Some(4));
+	});
+}
+
+#[test]
+fn bsp_volunteer_fails_without_min_stake() {
+	MinStake::set(100);
+
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 2));
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+
+		// The BSP cannot volunteer until it bonds the minimum stake.
+		assert_noop!(
+			FileSystem::bsp_volunteer(
+				RuntimeOrigin::signed(2),
+				location.clone(),
+				fingerprint,
+				bsp_multiaddress()
+			),
+			Error::<Test>::InsufficientStake
+		);
+
+		Balances::make_free_balance_be(&2, 1_000);
+		assert_ok!(Reputation::bond(RuntimeOrigin::signed(2), 100));
+		assert_ok!(FileSystem::bsp_volunteer(
+			RuntimeOrigin::signed(2),
+			location,
+			fingerprint,
+			bsp_multiaddress()
+		));
+	});
+}
+
+#[test]
+fn genesis_config_seeds_files_threshold_and_peers() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+	let file_content = b"test".to_vec();
+	let fingerprint = BlakeTwo256::hash(&file_content);
+	let peer_id = PeerId::<Test>::try_from(b"12D3KooW".to_vec()).unwrap();
+
+	let storage = RuntimeGenesisConfig {
+		identity: IdentityConfig { users: vec![2, 3], ..Default::default() },
+		file_system: FileSystemConfig {
+			files: vec![(1, location.clone(), fingerprint, 4, vec![2, 3])],
+			assignment_threshold: Some(1_000),
+			peers: vec![(2, peer_id.clone())],
+		},
+		..Default::default()
+	}
+	.build_storage()
+	.unwrap();
+
+	sp_io::TestExternalities::from(storage).execute_with(|| {
+		// Check that the file is stored by both BSPs.
+		let file_metadata = FilesMapping::<Test>::get(&location).unwrap();
+		assert_eq!(file_metadata.fingerprint, fingerprint);
+		assert_eq!(file_metadata.bsps.to_vec(), vec![2, 3]);
+
+		// Check that the storage used by BSPs and the threshold are set.
+		assert_eq!(FileSystem::total_used_bsps_storage(), Some(8));
+		assert_eq!(FileSystem::current_assignment_threshold(), Some(1_000));
+		assert_eq!(FileSystem::assignment_threshold(), 1_000);
+
+		// Check that the PeerId is registered to its BSP.
+		assert_eq!(FileSystem::peer_accounts(&peer_id), Some(2));
+	});
+}
+
+#[test]
+fn assignment_threshold_follows_registered_bsps() {
+	MinBspsAssignmentThreshold::set(u128::MAX / 8);
+
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Register 10 BSPs in Identity Pallet.
+		for bsp in 1..=10 {
+			assert_ok!(Identity::register_user(RuntimeOrigin::root(), bsp));
+		}
+
+		// Check that the threshold is scaled down by the number of BSPs.
+		assert_eq!(FileSystem::assignment_threshold(), u128::MAX / 10 * 5);
+
+		// Remove one BSP and check that the threshold is scaled up again.
+		assert_ok!(Identity::remove_user(RuntimeOrigin::root(), 10));
+		assert_eq!(FileSystem::assignment_threshold(), u128::MAX / 9 * 5);
+
+		// Register 50 BSPs and check that the threshold does not go below the minimum.
+		for bsp in 10..=50 {
+			assert_ok!(Identity::register_user(RuntimeOrigin::root(), bsp));
+		}
+		assert_eq!(FileSystem::assignment_threshold(), u128::MAX / 8);
+	});
+}
+
+#[test]
+fn genesis_bsps_set_assignment_threshold() {
+	MinBspsAssignmentThreshold::set(u128::MAX / 8);
+
+	let storage = RuntimeGenesisConfig {
+		identity: IdentityConfig { users: (1..=20).collect(), ..Default::default() },
+		..Default::default()
+	}
+	.build_storage()
+	.unwrap();
+
+	sp_io::TestExternalities::from(storage).execute_with(|| {
+		// Check that the threshold is scaled down by the genesis BSPs.
+		assert_eq!(FileSystem::current_assignment_threshold(), Some(u128::MAX / 20 * 5));
+	});
+}
+
+#[test]
+fn removed_bsp_files_are_reassigned_on_idle() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+	let file_content = b"test".to_vec();
+	let fingerprint = BlakeTwo256::hash(&file_content);
+
+	let storage = RuntimeGenesisConfig {
+		identity: IdentityConfig { users: vec![2, 3], ..Default::default() },
+		file_system: FileSystemConfig {
+			files: vec![(1, location.clone(), fingerprint, 4, vec![2, 3])],
+			assignment_threshold: None,
+			peers: vec![],
+		},
+		..Default::default()
+	}
+	.build_storage()
+	.unwrap();
+
+	sp_io::TestExternalities::from(storage).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Remove BSP from Identity Pallet.
+		assert_ok!(Identity::remove_user(RuntimeOrigin::root(), 2));
+		assert!(LeavingBsps::<Test>::contains_key(2));
+		assert_eq!(FileSystem::total_used_bsps_storage(), Some(8));
+
+		// Reassign its files.
+		FileSystem::on_idle(1, Weight::MAX);
+
+		// Check that the file is only stored by the remaining BSP.
+		let file_metadata = FilesMapping::<Test>::get(&location).unwrap();
+		assert_eq!(file_metadata.bsps.to_vec(), vec![3]);
+		assert!(!BspsFiles::<Test>::contains_key(2, &location));
+		assert!(!LeavingBsps::<Test>::contains_key(2));
+
+		// Check that the storage used by BSPs no longer counts the leaving BSP.
+		assert_eq!(FileSystem::total_used_bsps_storage(), Some(4));
+		assert_ok!(FileSystem::do_try_state());
+
+		// Check that the storage request is open again.
+		let storage_request = StorageRequests::<Test>::get(&location).unwrap();
+		assert_eq!(storage_request.requested_at, 1);
+		assert_eq!(storage_request.bsps.to_vec(), vec![3]);
+
+		// Assert that the correct event was deposited
+		System::assert_last_event(
+			Event::StorageRequestReopened { location, fingerprint, leaving_bsp: 2 }.into(),
+		);
+	});
+}
+
+/// Externalities with a file stored at `location` by BSPs 2 and 3, owned by account 1, and
+/// with [`MSP`] registered as MSP.
+fn stored_file_ext(location: &FileLocation<Test>) -> sp_io::TestExternalities {
+	RuntimeGenesisConfig {
+		identity: IdentityConfig { users: vec![2, 3], ..Default::default() },
+		msp_identity: MspIdentityConfig { users: vec![MSP], ..Default::default() },
+		file_system: FileSystemConfig {
+			files: vec![(1, location.clone(), BlakeTwo256::hash(b"v0"), 4, vec![2, 3])],
+			assignment_threshold: None,
+			peers: vec![],
+		},
+		..Default::default()
+	}
+	.build_storage()
+	.unwrap()
+	.into()
+}
+
+#[test]
+fn request_storage_fails_for_stored_file() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// A new version of a stored file cannot be uploaded as a new storage request.
+		assert_noop!(
+			FileSystem::request_storage(
+				RuntimeOrigin::signed(1),
+				location.clone(),
+				BlakeTwo256::hash(b"v1"),
+				4,
+				MSP,
+				sender_multiaddress(),
+			),
+			Error::<Test>::FileAlreadyStored
+		);
+	});
+}
+
+#[test]
+fn update_file_success() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+	let old_fingerprint = BlakeTwo256::hash(b"v0");
+	let new_fingerprint = BlakeTwo256::hash(b"v1");
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Owner uploads a new version.
+		assert_ok!(FileSystem::update_file(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			new_fingerprint,
+			6,
+			sender_multiaddress(),
+		));
+		System::assert_last_event(
+			Event::FileUpdateRequested {
+				who: 1,
+				location: location.clone(),
+				fingerprint: new_fingerprint,
+				size: 6,
+				sender_multiaddress: sender_multiaddress(),
+				bsps: BoundedVec::try_from(vec![2, 3]).unwrap(),
+			}
+			.into(),
+		);
+
+		// First BSP confirms, and the old version is kept until every BSP confirms.
+		assert_ok!(FileSystem::bsp_confirm_update(
+			RuntimeOrigin::signed(2),
+			location.clone(),
+			new_fingerprint
+		));
+		assert_eq!(FilesMapping::<Test>::get(&location).unwrap().fingerprint, old_fingerprint);
+		assert_eq!(FileUpdates::<Test>::get(&location).unwrap().confirmed_bsps.to_vec(), vec![2]);
+
+		// Second BSP confirms, completing the update.
+		System::set_block_number(2);
+		assert_ok!(FileSystem::bsp_confirm_update(
+			RuntimeOrigin::signed(3),
+			location.clone(),
+			new_fingerprint
+		));
+
+		// Check that the new version replaced the old one, which is kept in the history.
+		let file_metadata = FilesMapping::<Test>::get(&location).unwrap();
+		assert_eq!(file_metadata.fingerprint, new_fingerprint);
+		assert_eq!(file_metadata.size, 6);
+		assert!(!FileUpdates::<Test>::contains_key(&location));
+		assert_eq!(
+			FileSystem::file_versions(&location).to_vec(),
+			vec![FileVersion::<Test> { fingerprint: old_fingerprint, size: 4, replaced_at: 2 }]
+		);
+
+		// Check that the storage used by BSPs counts the new version for both of them.
+		assert_eq!(FileSystem::total_used_bsps_storage(), Some(12));
+
+		// Assert that the correct event was deposited
+		System::assert_last_event(
+			Event::FileUpdated {
+				location,
+				fingerprint: new_fingerprint,
+				previous_fingerprint: old_fingerprint,
 			}
 			.into(),
 		);
 	});
 }
+
+#[test]
+fn update_file_validations() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+	let new_fingerprint = BlakeTwo256::hash(b"v1");
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let update = |who: u64, location: &FileLocation<Test>, fingerprint| {
+			FileSystem::update_file(
+				RuntimeOrigin::signed(who),
+				location.clone(),
+				fingerprint,
+				4,
+				sender_multiaddress(),
+			)
+		};
+
+		// Only the owner of a stored file can update it, with a different content.
+		let unknown = FileLocation::<Test>::try_from(b"unknown".to_vec()).unwrap();
+		assert_noop!(update(1, &unknown, new_fingerprint), Error::<Test>::FileNotStored);
+		assert_noop!(update(2, &location, new_fingerprint), Error::<Test>::NotFileOwner);
+		assert_noop!(
+			update(1, &location, BlakeTwo256::hash(b"v0")),
+			Error::<Test>::FileVersionUnchanged
+		);
+
+		// BSPs can only confirm a pending update of their files, once.
+		let confirm = |who: u64, fingerprint| {
+			FileSystem::bsp_confirm_update(
+				RuntimeOrigin::signed(who),
+				location.clone(),
+				fingerprint,
+			)
+		};
+		assert_noop!(confirm(2, new_fingerprint), Error::<Test>::FileUpdateNotRequested);
+
+		assert_ok!(update(1, &location, new_fingerprint));
+		assert_noop!(confirm(4, new_fingerprint), Error::<Test>::NotFileBsp);
+		assert_noop!(confirm(2, BlakeTwo256::hash(b"v2")), Error::<Test>::FileUpdateMismatch);
+		assert_ok!(confirm(2, new_fingerprint));
+		assert_noop!(confirm(2, new_fingerprint), Error::<Test>::FileUpdateAlreadyConfirmed);
+
+		// A newer update replaces the pending one and its confirmations.
+		let newer_fingerprint = BlakeTwo256::hash(b"v2");
+		assert_ok!(update(1, &location, newer_fingerprint));
+		assert_noop!(confirm(2, new_fingerprint), Error::<Test>::FileUpdateMismatch);
+		assert!(FileUpdates::<Test>::get(&location).unwrap().confirmed_bsps.is_empty());
+	});
+}
+
+#[test]
+fn file_version_history_is_bounded() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Upload and confirm more versions than MaxFileVersions.
+		for version in 1..=4u32 {
+			let fingerprint = BlakeTwo256::hash(&version.encode());
+			assert_ok!(FileSystem::update_file(
+				RuntimeOrigin::signed(1),
+				location.clone(),
+				fingerprint,
+				4,
+				sender_multiaddress(),
+			));
+			for bsp in [2, 3] {
+				assert_ok!(FileSystem::bsp_confirm_update(
+					RuntimeOrigin::signed(bsp),
+					location.clone(),
+					fingerprint
+				));
+			}
+		}
+
+		// Check that only the latest previous versions are kept, oldest first.
+		let history: Vec<_> =
+			FileSystem::file_versions(&location).iter().map(|v| v.fingerprint).collect();
+		assert_eq!(
+			history,
+			vec![1u32, 2, 3]
+				.into_iter()
+				.map(|v| BlakeTwo256::hash(&v.encode()))
+				.collect::<Vec<_>>()
+		);
+	});
+}
+
+#[test]
+fn removed_bsp_completes_pending_file_update() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+	let new_fingerprint = BlakeTwo256::hash(b"v1");
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Only one of the BSPs confirms the new version.
+		assert_ok!(FileSystem::update_file(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			new_fingerprint,
+			6,
+			sender_multiaddress(),
+		));
+		assert_ok!(FileSystem::bsp_confirm_update(
+			RuntimeOrigin::signed(2),
+			location.clone(),
+			new_fingerprint
+		));
+
+		// The other BSP leaves, so the update no longer waits for it.
+		assert_ok!(Identity::remove_user(RuntimeOrigin::root(), 3));
+		FileSystem::on_idle(1, Weight::MAX);
+
+		// Check that the update is completed and that new volunteers fetch the new version.
+		assert_eq!(FilesMapping::<Test>::get(&location).unwrap().fingerprint, new_fingerprint);
+		assert_eq!(StorageRequests::<Test>::get(&location).unwrap().fingerprint, new_fingerprint);
+		assert!(!FileUpdates::<Test>::contains_key(&location));
+	});
+}
+
+#[test]
+fn bsps_cannot_volunteer_while_file_update_is_pending() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"v0");
+		let new_fingerprint = BlakeTwo256::hash(b"v1");
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 2));
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 3));
+
+		// A BSP volunteers, and the owner uploads a new version while the request is open.
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+		assert_ok!(FileSystem::bsp_volunteer(
+			RuntimeOrigin::signed(2),
+			location.clone(),
+			fingerprint,
+			bsp_multiaddress()
+		));
+		assert_ok!(FileSystem::update_file(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			new_fingerprint,
+			6,
+			sender_multiaddress(),
+		));
+
+		// Another BSP cannot volunteer until the update is settled, as it was never sent it.
+		assert_noop!(
+			FileSystem::bsp_volunteer(
+				RuntimeOrigin::signed(3),
+				location.clone(),
+				fingerprint,
+				bsp_multiaddress()
+			),
+			Error::<Test>::FileUpdatePending
+		);
+
+		// Once the BSP that was sent the update confirms it, new volunteers store the new
+		// version.
+		assert_ok!(FileSystem::bsp_confirm_update(
+			RuntimeOrigin::signed(2),
+			location.clone(),
+			new_fingerprint
+		));
+		assert_ok!(FileSystem::bsp_volunteer(
+			RuntimeOrigin::signed(3),
+			location.clone(),
+			new_fingerprint,
+			bsp_multiaddress()
+		));
+		let file_metadata = FilesMapping::<Test>::get(&location).unwrap();
+		assert_eq!(file_metadata.fingerprint, new_fingerprint);
+		assert_eq!(file_metadata.bsps.to_vec(), vec![2, 3]);
+		assert!(!FileUpdates::<Test>::contains_key(&location));
+	});
+}
+
+#[test]
+fn private_files_are_only_readable_by_allowed_accounts() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Public files can be read by anyone.
+		assert!(FileSystem::can_read(&4, &location));
+
+		// Owner makes the file private.
+		assert_ok!(FileSystem::set_file_visibility(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			false
+		));
+		System::assert_last_event(
+			Event::FileVisibilityChanged { location: location.clone(), is_public: false }.into(),
+		);
+		assert!(!FilesMapping::<Test>::get(&location).unwrap().is_public);
+
+		// Only the owner and the BSPs can read it.
+		assert!(FileSystem::can_read(&1, &location));
+		assert!(FileSystem::can_read(&2, &location));
+		assert!(!FileSystem::can_read(&4, &location));
+
+		// Owner allows another account to read it.
+		assert_ok!(FileSystem::grant_read_access(RuntimeOrigin::signed(1), location.clone(), 4));
+		System::assert_last_event(
+			Event::ReadAccessGranted { location: location.clone(), reader: 4 }.into(),
+		);
+		assert!(FileReaders::<Test>::contains_key(&location, 4));
+		assert!(FileSystem::can_read(&4, &location));
+
+		// Owner revokes it.
+		assert_ok!(FileSystem::revoke_read_access(RuntimeOrigin::signed(1), location.clone(), 4));
+		System::assert_last_event(
+			Event::ReadAccessRevoked { location: location.clone(), reader: 4 }.into(),
+		);
+		assert!(!FileSystem::can_read(&4, &location));
+	});
+}
+
+#[test]
+fn file_access_can_only_be_changed_by_owner() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+	let unknown = FileLocation::<Test>::try_from(b"unknown".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		assert_noop!(
+			FileSystem::set_file_visibility(RuntimeOrigin::signed(2), location.clone(), false),
+			Error::<Test>::NotFileOwner
+		);
+		assert_noop!(
+			FileSystem::grant_read_access(RuntimeOrigin::signed(2), location.clone(), 4),
+			Error::<Test>::NotFileOwner
+		);
+		assert_noop!(
+			FileSystem::revoke_read_access(RuntimeOrigin::signed(2), location.clone(), 4),
+			Error::<Test>::NotFileOwner
+		);
+		assert_noop!(
+			FileSystem::set_file_visibility(RuntimeOrigin::signed(1), unknown, false),
+			Error::<Test>::FileNotStored
+		);
+	});
+}
+
+#[test]
+fn requested_file_visibility_is_kept_once_stored() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+
+		// Request storage and make the file private before any BSP volunteers.
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 2));
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+		assert_ok!(FileSystem::set_file_visibility(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			false
+		));
+
+		// Check that the stored file is still private.
+		assert_ok!(FileSystem::bsp_volunteer(
+			RuntimeOrigin::signed(2),
+			location.clone(),
+			fingerprint,
+			bsp_multiaddress()
+		));
+		assert!(!FilesMapping::<Test>::get(&location).unwrap().is_public);
+	});
+}
+
+/// The binary PeerId derived from the ed25519 key `pair`.
+fn ed25519_peer_id(pair: &ed25519::Pair) -> PeerId<Test> {
+	let mut peer_id = vec![0x00, 0x24, 0x08, 0x01, 0x12, 0x20];
+	peer_id.extend_from_slice(pair.public().as_ref());
+	peer_id.try_into().unwrap()
+}
+
+#[test]
+fn peer_ids_are_registered_once() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let pair = ed25519::Pair::from_seed(&[1; 32]);
+		let peer_id = ed25519_peer_id(&pair);
+		let signature = pair.sign(&FileSystem::peer_id_registration_payload(&1));
+
+		// Register PeerId.
+		assert_ok!(FileSystem::register_peer_id(
+			RuntimeOrigin::signed(1),
+			peer_id.clone(),
+			signature.clone()
+		));
+		System::assert_last_event(
+			Event::PeerIdRegistered { who: 1, peer_id: peer_id.clone() }.into(),
+		);
+		assert_eq!(PeerAccounts::<Test>::get(&peer_id), Some(1));
+
+		// Nobody else can register or unregister it.
+		let other_signature = pair.sign(&FileSystem::peer_id_registration_payload(&2));
+		assert_noop!(
+			FileSystem::register_peer_id(
+				RuntimeOrigin::signed(2),
+				peer_id.clone(),
+				other_signature
+			),
+			Error::<Test>::PeerIdAlreadyRegistered
+		);
+		assert_noop!(
+			FileSystem::unregister_peer_id(RuntimeOrigin::signed(2), peer_id.clone()),
+			Error::<Test>::PeerIdNotRegistered
+		);
+
+		// Unregister PeerId.
+		assert_ok!(FileSystem::unregister_peer_id(RuntimeOrigin::signed(1), peer_id.clone()));
+		System::assert_last_event(
+			Event::PeerIdUnregistered { who: 1, peer_id: peer_id.clone() }.into(),
+		);
+		assert!(!PeerAccounts::<Test>::contains_key(&peer_id));
+	});
+}
+
+#[test]
+fn peer_ids_can_only_be_registered_with_their_key() {
+	new_test_ext().execute_with(|| {
+		let pair = ed25519::Pair::from_seed(&[1; 32]);
+		let peer_id = ed25519_peer_id(&pair);
+
+		// The signature of another key is rejected.
+		let other_pair = ed25519::Pair::from_seed(&[2; 32]);
+		assert_noop!(
+			FileSystem::register_peer_id(
+				RuntimeOrigin::signed(1),
+				peer_id.clone(),
+				other_pair.sign(&FileSystem::peer_id_registration_payload(&1))
+			),
+			Error::<Test>::InvalidPeerIdSignature
+		);
+
+		// A signature for another account can't be replayed.
+		assert_noop!(
+			FileSystem::register_peer_id(
+				RuntimeOrigin::signed(2),
+				peer_id.clone(),
+				pair.sign(&FileSystem::peer_id_registration_payload(&1))
+			),
+			Error::<Test>::InvalidPeerIdSignature
+		);
+
+		// PeerIds that don't inline an ed25519 key can't be registered.
+		let sha256_peer_id: PeerId<Test> =
+			[vec![0x12, 0x20], vec![0; 32]].concat().try_into().unwrap();
+		assert_noop!(
+			FileSystem::register_peer_id(
+				RuntimeOrigin::signed(1),
+				sha256_peer_id,
+				pair.sign(&FileSystem::peer_id_registration_payload(&1))
+			),
+			Error::<Test>::UnsupportedPeerId
+		);
+	});
+}
+
+#[test]
+fn request_storage_enforces_max_file_size() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+		assert_noop!(
+			FileSystem::request_storage(
+				RuntimeOrigin::signed(1),
+				location,
+				BlakeTwo256::hash(b"test"),
+				1_001,
+				MSP,
+				sender_multiaddress(),
+			),
+			Error::<Test>::FileTooLarge
+		);
+	});
+}
+
+#[test]
+fn request_storage_enforces_user_quota() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let request = |i: u32, size: u128| {
+			FileSystem::request_storage(
+				RuntimeOrigin::signed(1),
+				FileLocation::<Test>::try_from(i.encode()).unwrap(),
+				BlakeTwo256::hash(&i.encode()),
+				size,
+				MSP,
+				sender_multiaddress(),
+			)
+		};
+
+		// Root lowers the quota of the user.
+		assert_ok!(FileSystem::set_user_quota(RuntimeOrigin::root(), 1, 1_500, 2));
+		System::assert_last_event(
+			Event::UserQuotaSet { who: 1, max_bytes: 1_500, max_files: 2 }.into(),
+		);
+
+		// Store files up to the byte quota.
+		assert_ok!(request(0, 1_000));
+		assert_noop!(request(1, 501), Error::<Test>::StorageQuotaExceeded);
+		assert_ok!(request(1, 500));
+
+		// Check that the usage is tracked.
+		let usage = FileSystem::user_storage_usage(1);
+		assert_eq!((usage.bytes, usage.files), (1_500, 2));
+
+		// Root raises the byte quota, but the file count quota is reached.
+		assert_ok!(FileSystem::set_user_quota(RuntimeOrigin::root(), 1, 10_000, 2));
+		assert_noop!(request(2, 1), Error::<Test>::FileCountQuotaExceeded);
+
+		// Only root can set quotas.
+		assert_noop!(
+			FileSystem::set_user_quota(RuntimeOrigin::signed(1), 1, 10_000, 10),
+			sp_runtime::DispatchError::BadOrigin
+		);
+	});
+}
+
+#[test]
+fn file_updates_count_towards_user_quota() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+	let new_fingerprint = BlakeTwo256::hash(b"v1");
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Check that the genesis file counts towards its owner's usage.
+		assert_eq!(UserStorageUsage::<Test>::get(1).bytes, 4);
+
+		// A new version cannot exceed the user quota.
+		assert_ok!(FileSystem::set_user_quota(RuntimeOrigin::root(), 1, 10, 1));
+		assert_noop!(
+			FileSystem::update_file(
+				RuntimeOrigin::signed(1),
+				location.clone(),
+				new_fingerprint,
+				11,
+				sender_multiaddress(),
+			),
+			Error::<Test>::StorageQuotaExceeded
+		);
+
+		// Once every BSP confirms it, the usage reflects the size of the new version.
+		assert_ok!(FileSystem::update_file(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			new_fingerprint,
+			10,
+			sender_multiaddress(),
+		));
+		assert_eq!(UserStorageUsage::<Test>::get(1).bytes, 4);
+		for bsp in [2, 3] {
+			assert_ok!(FileSystem::bsp_confirm_update(
+				RuntimeOrigin::signed(bsp),
+				location.clone(),
+				new_fingerprint
+			));
+		}
+		assert_eq!(UserStorageUsage::<Test>::get(1).bytes, 10);
+	});
+}
+
+#[test]
+fn invalid_multiaddresses_are_rejected() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+
+		for invalid in [
+			// Not a multiaddress.
+			&b"garbage"[..],
+			// Binary encoded multiaddress.
+			&[4, 127, 0, 0, 1, 6, 0x5b, 0xa0][..],
+			// No PeerId.
+			b"/ip4/127.0.0.1/tcp/23456",
+			// PeerId not at the end.
+			b"/p2p/12D3KooWAWERvhQ4if5aum3FGhdTnTervVpxMSiy99VoHbK4s4ZX/tcp/23456",
+			// Invalid IP address.
+			b"/ip4/127.0.0.256/tcp/23456/p2p/12D3KooWAWERvhQ4if5aum3FGhdTnTervVpxMSiy99VoHbK4s4ZX",
+			// Invalid port.
+			b"/ip4/127.0.0.1/tcp/65536/p2p/12D3KooWAWERvhQ4if5aum3FGhdTnTervVpxMSiy99VoHbK4s4ZX",
+			// Unsupported protocol.
+			b"/ip4/127.0.0.1/sctp/23456/p2p/12D3KooWAWERvhQ4if5aum3FGhdTnTervVpxMSiy99VoHbK4s4ZX",
+			// PeerId that is not base58 encoded.
+			b"/ip4/127.0.0.1/tcp/23456/p2p/12D3KooWAWERvhQ4if5aum3FGhdTnTervVpxMSiy99VoHbK4s4Z0",
+			// PeerId that is not a multihash.
+			b"/ip4/127.0.0.1/tcp/23456/p2p/12D3KooWAWERvhQ4if5aum3FGhdTnTervVpxMSiy99VoHbK4s4",
+		] {
+			let invalid = MultiAddress::<Test>::try_from(invalid.to_vec()).unwrap();
+
+			assert_noop!(
+				FileSystem::request_storage(
+					RuntimeOrigin::signed(1),
+					location.clone(),
+					fingerprint,
+					4,
+					MSP,
+					invalid.clone(),
+				),
+				Error::<Test>::InvalidMultiAddress
+			);
+			assert_noop!(
+				FileSystem::request_storage_batch(
+					RuntimeOrigin::signed(1),
+					StorageRequestBatch::<Test>::try_from(vec![(location.clone(), fingerprint, 4)])
+						.unwrap(),
+					MSP,
+					invalid,
+				),
+				Error::<Test>::InvalidMultiAddress
+			);
+		}
+
+		// Other transports and SHA2-256 PeerIds are supported.
+		let multiaddress = MultiAddress::<Test>::try_from(
+			b"/dns4/bsp.example.com/udp/30333/quic-v1/p2p/QmRQ353oFNqt8zfZ9X1HgRUszwv9RkEEwmMZZkbkYEsybn"
+				.to_vec(),
+		)
+		.unwrap();
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			multiaddress,
+		));
+
+		// BSPs have to submit a valid multiaddress too.
+		assert_noop!(
+			FileSystem::bsp_volunteer(
+				RuntimeOrigin::signed(2),
+				location,
+				fingerprint,
+				MultiAddress::<Test>::try_from(b"/ip6/::1/tcp/34567".to_vec()).unwrap(),
+			),
+			Error::<Test>::InvalidMultiAddress
+		);
+	});
+}
+
+#[test]
+fn msp_accepts_storage_request() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+
+		// Only registered MSPs can be picked.
+		assert_noop!(
+			FileSystem::request_storage(
+				RuntimeOrigin::signed(1),
+				location.clone(),
+				fingerprint,
+				4,
+				2,
+				sender_multiaddress(),
+			),
+			Error::<Test>::NotMsp
+		);
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+		assert_eq!(StorageRequests::<Test>::get(&location).unwrap().msp, Some(MSP));
+
+		// Only the picked MSP can accept the file.
+		assert_noop!(
+			FileSystem::msp_accept_storage_request(RuntimeOrigin::signed(2), location.clone()),
+			Error::<Test>::NotFileMsp
+		);
+		assert_ok!(FileSystem::msp_accept_storage_request(
+			RuntimeOrigin::signed(MSP),
+			location.clone()
+		));
+		System::assert_last_event(
+			Event::MspAcceptedStorageRequest { msp: MSP, location: location.clone() }.into(),
+		);
+		assert!(StorageRequests::<Test>::get(&location).unwrap().msp_accepted);
+
+		// Once accepted, the MSP cannot accept nor reject it again.
+		assert_noop!(
+			FileSystem::msp_accept_storage_request(RuntimeOrigin::signed(MSP), location.clone()),
+			Error::<Test>::MspAlreadyAccepted
+		);
+		assert_noop!(
+			FileSystem::msp_reject_storage_request(RuntimeOrigin::signed(MSP), location.clone()),
+			Error::<Test>::MspAlreadyAccepted
+		);
+
+		// The MSP is kept once BSPs store the file, and can read it even if private.
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 2));
+		assert_ok!(FileSystem::bsp_volunteer(
+			RuntimeOrigin::signed(2),
+			location.clone(),
+			fingerprint,
+			bsp_multiaddress()
+		));
+		let file_metadata = FilesMapping::<Test>::get(&location).unwrap();
+		assert_eq!(file_metadata.msp, Some(MSP));
+		assert!(file_metadata.msp_accepted);
+
+		assert_ok!(FileSystem::set_file_visibility(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			false
+		));
+		assert!(FileSystem::can_read(&MSP, &location));
+	});
+}
+
+#[test]
+fn msp_rejects_storage_request() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+
+		// Only the picked MSP can reject the file.
+		assert_noop!(
+			FileSystem::msp_reject_storage_request(RuntimeOrigin::signed(2), location.clone()),
+			Error::<Test>::NotFileMsp
+		);
+		assert_ok!(FileSystem::msp_reject_storage_request(
+			RuntimeOrigin::signed(MSP),
+			location.clone()
+		));
+		System::assert_last_event(
+			Event::MspRejectedStorageRequest { msp: MSP, location: location.clone() }.into(),
+		);
+
+		// The file is left to its BSPs, so the MSP can no longer accept it.
+		let storage_request = StorageRequests::<Test>::get(&location).unwrap();
+		assert_eq!(storage_request.msp, None);
+		assert!(!storage_request.msp_accepted);
+		assert_noop!(
+			FileSystem::msp_accept_storage_request(RuntimeOrigin::signed(MSP), location.clone()),
+			Error::<Test>::NotFileMsp
+		);
+
+		// Files that are neither stored nor requested have no MSP.
+		assert_noop!(
+			FileSystem::msp_accept_storage_request(
+				RuntimeOrigin::signed(MSP),
+				FileLocation::<Test>::try_from(b"unknown".to_vec()).unwrap()
+			),
+			Error::<Test>::FileNotStored
+		);
+	});
+}
+
+#[test]
+fn hooks_follow_storage_request_until_stored() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+		let bsps = vec![2, 3, 4, 5, 6];
+
+		// Register enough BSPs to fulfil the storage request.
+		for bsp in &bsps {
+			assert_ok!(Identity::register_user(RuntimeOrigin::root(), *bsp));
+		}
+
+		// Dispatch storage request, and have every BSP volunteer for it.
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+		for bsp in &bsps {
+			assert_ok!(FileSystem::bsp_volunteer(
+				RuntimeOrigin::signed(*bsp),
+				location.clone(),
+				fingerprint,
+				bsp_multiaddress()
+			));
+		}
+
+		// Check that the hooks were called in order, with the file stored once fulfilled.
+		let mut expected_calls =
+			vec![HookCall::StorageRequested { who: 1, location: location.clone(), size: 4 }];
+		expected_calls.extend(
+			bsps.iter()
+				.map(|bsp| HookCall::BspVolunteered { bsp: *bsp, location: location.clone() }),
+		);
+		expected_calls.push(HookCall::FileStored { location, bsps });
+		assert_eq!(hook_calls(), expected_calls);
+	});
+}
+
+#[test]
+fn hooks_follow_file_updates_and_leaving_bsps() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+	let new_fingerprint = BlakeTwo256::hash(b"v1");
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Owner uploads a new version, and both BSPs confirm it.
+		assert_ok!(FileSystem::update_file(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			new_fingerprint,
+			6,
+			sender_multiaddress(),
+		));
+		for bsp in [2, 3] {
+			assert_ok!(FileSystem::bsp_confirm_update(
+				RuntimeOrigin::signed(bsp),
+				location.clone(),
+				new_fingerprint
+			));
+		}
+
+		// Both BSPs leave, and their files are reassigned.
+		assert_ok!(Identity::remove_user(RuntimeOrigin::root(), 2));
+		FileSystem::on_idle(1, Weight::MAX);
+		assert_ok!(Identity::remove_user(RuntimeOrigin::root(), 3));
+		FileSystem::on_idle(1, Weight::MAX);
+
+		// Check that the hooks were called in order, with the file deleted once no BSP stores
+		// it.
+		assert_eq!(
+			hook_calls(),
+			vec![
+				HookCall::FileUpdated { location: location.clone(), fingerprint: new_fingerprint },
+				HookCall::BspRemoved { bsp: 2, location: location.clone() },
+				HookCall::BspRemoved { bsp: 3, location: location.clone() },
+				HookCall::FileDeleted { location, owner: 1 },
+			]
+		);
+	});
+}
+
+#[test]
+fn hooks_weight_is_included_in_call_weights() {
+	new_test_ext().execute_with(|| {
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+		let hooks_weight = <RecordingHooks as FileSystemHooks<Test>>::weight();
+
+		// Composed hooks weigh as much as all of them.
+		assert_eq!(
+			<(RecordingHooks, RecordingHooks) as FileSystemHooks<Test>>::weight(),
+			hooks_weight.saturating_mul(2)
+		);
+
+		// A storage request calls one hook, and volunteering up to two.
+		let call = crate::Call::<Test>::request_storage {
+			location: location.clone(),
+			fingerprint,
+			size: 4,
+			msp: MSP,
+			sender_multiaddress: sender_multiaddress(),
+		};
+		assert_eq!(
+			call.get_dispatch_info().weight,
+			<() as WeightInfo>::request_storage().saturating_add(hooks_weight)
+		);
+		let call = crate::Call::<Test>::bsp_volunteer {
+			location,
+			fingerprint,
+			bsp_multiaddress: bsp_multiaddress(),
+		};
+		assert_eq!(
+			call.get_dispatch_info().weight,
+			<() as WeightInfo>::bsp_volunteer().saturating_add(hooks_weight.saturating_mul(2))
+		);
+	});
+}
+
+#[test]
+fn bsps_missing_update_deadline_are_penalized_and_removed() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+	let new_fingerprint = BlakeTwo256::hash(b"v1");
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Owner uploads a new version, which only one of the BSPs confirms.
+		assert_ok!(FileSystem::update_file(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			new_fingerprint,
+			6,
+			sender_multiaddress(),
+		));
+		assert_ok!(FileSystem::bsp_confirm_update(
+			RuntimeOrigin::signed(2),
+			location.clone(),
+			new_fingerprint
+		));
+
+		// The update cannot be expired until the BSPs run out of time to confirm it.
+		System::set_block_number(11);
+		assert_noop!(
+			FileSystem::expire_file_update(RuntimeOrigin::signed(4), location.clone()),
+			Error::<Test>::FileUpdateNotExpired
+		);
+
+		// Anyone can expire it afterwards.
+		System::set_block_number(12);
+		assert_ok!(FileSystem::expire_file_update(RuntimeOrigin::signed(4), location.clone()));
+		System::assert_has_event(
+			Event::FileUpdateDeadlineMissed {
+				location: location.clone(),
+				fingerprint: new_fingerprint,
+				bsps: BoundedVec::try_from(vec![3]).unwrap(),
+			}
+			.into(),
+		);
+
+		// Check that the late BSP no longer stores the file, which has its new version.
+		let file_metadata = FilesMapping::<Test>::get(&location).unwrap();
+		assert_eq!(file_metadata.bsps.to_vec(), vec![2]);
+		assert_eq!(file_metadata.fingerprint, new_fingerprint);
+		assert!(!FileUpdates::<Test>::contains_key(&location));
+		assert!(!BspsFiles::<Test>::contains_key(3, &location));
+		assert_eq!(StorageRequests::<Test>::get(&location).unwrap().fingerprint, new_fingerprint);
+
+		// Check that only the late BSP was penalized.
+		assert_eq!(Reputation::score(&2), Perbill::one());
+		assert_eq!(Reputation::score(&3), Perbill::from_percent(80));
+	});
+}
+
+#[test]
+fn expired_update_without_confirmations_is_dropped() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+	let old_fingerprint = BlakeTwo256::hash(b"v0");
+	let new_fingerprint = BlakeTwo256::hash(b"v1");
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Owner uploads a new version, which no BSP confirms in time.
+		assert_ok!(FileSystem::update_file(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			new_fingerprint,
+			6,
+			sender_multiaddress(),
+		));
+		System::set_block_number(12);
+		assert_ok!(FileSystem::expire_file_update(RuntimeOrigin::signed(4), location.clone()));
+
+		// Assert that the correct event was deposited
+		System::assert_last_event(
+			Event::FileUpdateDropped { location: location.clone(), fingerprint: new_fingerprint }
+				.into(),
+		);
+
+		// Check that the file is left as it was, and that no BSP was penalized.
+		let file_metadata = FilesMapping::<Test>::get(&location).unwrap();
+		assert_eq!(file_metadata.bsps.to_vec(), vec![2, 3]);
+		assert_eq!(file_metadata.fingerprint, old_fingerprint);
+		assert!(!FileUpdates::<Test>::contains_key(&location));
+		assert_eq!(Reputation::score(&2), Perbill::one());
+		assert_eq!(Reputation::score(&3), Perbill::one());
+	});
+}
+
+#[test]
+fn failed_challenges_slash_stake_and_lower_eligibility() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// BSP bonds stake.
+		assert_ok!(Balances::force_set_balance(RuntimeOrigin::root(), 2, 1_000));
+		assert_ok!(Reputation::bond(RuntimeOrigin::signed(2), 100));
+
+		// Only the challenge origin can report failures, and only for files the BSP stores.
+		assert_noop!(
+			FileSystem::report_failed_challenge(RuntimeOrigin::signed(1), 2, location.clone()),
+			DispatchError::BadOrigin
+		);
+		assert_noop!(
+			FileSystem::report_failed_challenge(RuntimeOrigin::root(), 4, location.clone()),
+			Error::<Test>::NotFileBsp
+		);
+
+		// Each failure lowers the score of the BSP.
+		assert_ok!(FileSystem::report_failed_challenge(RuntimeOrigin::root(), 2, location.clone()));
+		System::assert_last_event(
+			Event::ChallengeFailed { bsp: 2, location: location.clone() }.into(),
+		);
+		assert_eq!(Reputation::score(&2), Perbill::from_percent(50));
+		assert_eq!(Reputation::stake(2), 100);
+
+		// Failing again slashes half of its stake.
+		assert_ok!(FileSystem::report_failed_challenge(RuntimeOrigin::root(), 2, location.clone()));
+		System::assert_has_event(pallet_reputation::Event::Slashed { who: 2, amount: 50 }.into());
+		assert_eq!(Reputation::score(&2), Perbill::zero());
+		assert_eq!(Reputation::stake(2), 50);
+
+		// Without any reputation left, the BSP is no longer eligible for new files.
+		let new_location = FileLocation::<Test>::try_from(b"new".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"new");
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			new_location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+		assert_noop!(
+			FileSystem::bsp_volunteer(
+				RuntimeOrigin::signed(2),
+				new_location.clone(),
+				fingerprint,
+				bsp_multiaddress()
+			),
+			Error::<Test>::ThresholdTooLow
+		);
+		assert_ok!(FileSystem::bsp_volunteer(
+			RuntimeOrigin::signed(3),
+			new_location,
+			fingerprint,
+			bsp_multiaddress()
+		));
+	});
+}
+
+#[test]
+fn retrieval_fee_is_paid_on_delivery() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// A user pays to retrieve the file from one of its BSPs.
+		Balances::make_free_balance_be(&4, 1_000);
+		Balances::make_free_balance_be(&2, 1_000);
+		assert_ok!(FileSystem::request_retrieval(
+			RuntimeOrigin::signed(4),
+			location.clone(),
+			2,
+			100,
+			sender_multiaddress(),
+		));
+		System::assert_last_event(
+			Event::RetrievalRequested {
+				who: 4,
+				location: location.clone(),
+				bsp: 2,
+				fee: 100,
+				requester_multiaddress: sender_multiaddress(),
+			}
+			.into(),
+		);
+		assert_eq!(Balances::reserved_balance(4), 100);
+
+		// The same file cannot be requested again while the request is pending.
+		assert_noop!(
+			FileSystem::request_retrieval(
+				RuntimeOrigin::signed(4),
+				location.clone(),
+				3,
+				100,
+				sender_multiaddress(),
+			),
+			Error::<Test>::RetrievalAlreadyRequested
+		);
+
+		// Only the BSP the file was requested from can confirm the delivery.
+		assert_noop!(
+			FileSystem::bsp_confirm_delivery(RuntimeOrigin::signed(3), location.clone(), 4),
+			Error::<Test>::NotRetrievalBsp
+		);
+		assert_ok!(FileSystem::bsp_confirm_delivery(RuntimeOrigin::signed(2), location.clone(), 4));
+
+		// Assert that the correct event was deposited
+		System::assert_last_event(
+			Event::RetrievalDelivered { who: 4, location: location.clone(), bsp: 2 }.into(),
+		);
+
+		// The fee stays held while the requester can dispute the delivery, and only the
+		// requester can pay it before then.
+		assert_eq!(Balances::reserved_balance(4), 100);
+		assert_noop!(
+			FileSystem::bsp_confirm_delivery(RuntimeOrigin::signed(2), location.clone(), 4),
+			Error::<Test>::RetrievalAlreadyDelivered
+		);
+		assert_noop!(
+			FileSystem::refund_retrieval(RuntimeOrigin::signed(5), location.clone(), 4),
+			Error::<Test>::RetrievalAlreadyDelivered
+		);
+		assert_noop!(
+			FileSystem::confirm_retrieval(RuntimeOrigin::signed(2), location.clone(), 4),
+			Error::<Test>::DisputePeriodNotEnded
+		);
+		assert_ok!(FileSystem::confirm_retrieval(RuntimeOrigin::signed(4), location.clone(), 4));
+
+		// Assert that the correct event was deposited
+		System::assert_last_event(
+			Event::RetrievalPaid { who: 4, location: location.clone(), bsp: 2, fee: 100 }.into(),
+		);
+
+		// Check that the BSP was paid the fee.
+		assert_eq!(Balances::free_balance(4), 900);
+		assert_eq!(Balances::reserved_balance(4), 0);
+		assert_eq!(Balances::free_balance(2), 1_100);
+		assert!(!RetrievalRequests::<Test>::contains_key(&location, 4));
+	});
+}
+
+#[test]
+fn retrieval_fee_is_paid_after_dispute_period() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		Balances::make_free_balance_be(&4, 1_000);
+		Balances::make_free_balance_be(&2, 1_000);
+		assert_ok!(FileSystem::request_retrieval(
+			RuntimeOrigin::signed(4),
+			location.clone(),
+			2,
+			100,
+			sender_multiaddress(),
+		));
+		System::set_block_number(3);
+		assert_ok!(FileSystem::bsp_confirm_delivery(RuntimeOrigin::signed(2), location.clone(), 4));
+
+		// Anyone can pay the BSP once the requester ran out of time to dispute the delivery.
+		System::set_block_number(8);
+		assert_noop!(
+			FileSystem::confirm_retrieval(RuntimeOrigin::signed(5), location.clone(), 4),
+			Error::<Test>::DisputePeriodNotEnded
+		);
+		System::set_block_number(9);
+		assert_noop!(
+			FileSystem::dispute_retrieval(RuntimeOrigin::signed(4), location.clone()),
+			Error::<Test>::DisputePeriodEnded
+		);
+		assert_ok!(FileSystem::confirm_retrieval(RuntimeOrigin::signed(5), location.clone(), 4));
+
+		// Assert that the correct event was deposited
+		System::assert_last_event(
+			Event::RetrievalPaid { who: 4, location: location.clone(), bsp: 2, fee: 100 }.into(),
+		);
+
+		// Check that the BSP was paid the fee.
+		assert_eq!(Balances::free_balance(4), 900);
+		assert_eq!(Balances::reserved_balance(4), 0);
+		assert_eq!(Balances::free_balance(2), 1_100);
+	});
+}
+
+#[test]
+fn retrieval_fee_is_refunded_on_dispute() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		Balances::make_free_balance_be(&4, 1_000);
+		assert_ok!(FileSystem::request_retrieval(
+			RuntimeOrigin::signed(4),
+			location.clone(),
+			2,
+			100,
+			sender_multiaddress(),
+		));
+
+		// A delivery cannot be disputed before the BSP claims it.
+		assert_noop!(
+			FileSystem::dispute_retrieval(RuntimeOrigin::signed(4), location.clone()),
+			Error::<Test>::RetrievalNotDelivered
+		);
+		assert_noop!(
+			FileSystem::confirm_retrieval(RuntimeOrigin::signed(4), location.clone(), 4),
+			Error::<Test>::RetrievalNotDelivered
+		);
+
+		// The requester disputes the delivery claimed by the BSP, getting the fee back.
+		assert_ok!(FileSystem::bsp_confirm_delivery(RuntimeOrigin::signed(2), location.clone(), 4));
+		System::set_block_number(6);
+		assert_ok!(FileSystem::dispute_retrieval(RuntimeOrigin::signed(4), location.clone()));
+
+		// Assert that the correct event was deposited
+		System::assert_last_event(
+			Event::RetrievalDisputed { who: 4, location: location.clone(), bsp: 2, fee: 100 }
+				.into(),
+		);
+
+		// Check that the user got the fee back.
+		assert_eq!(Balances::free_balance(4), 1_000);
+		assert_eq!(Balances::reserved_balance(4), 0);
+		assert!(!RetrievalRequests::<Test>::contains_key(&location, 4));
+	});
+}
+
+#[test]
+fn retrieval_fee_is_held_apart_from_other_reserves() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// The requester also has stake bonded, reserved under another identifier.
+		Balances::make_free_balance_be(&4, 1_000);
+		Balances::make_free_balance_be(&2, 1_000);
+		assert_ok!(Reputation::bond(RuntimeOrigin::signed(4), 300));
+		assert_ok!(FileSystem::request_retrieval(
+			RuntimeOrigin::signed(4),
+			location.clone(),
+			2,
+			100,
+			sender_multiaddress(),
+		));
+		assert_eq!(Balances::reserved_balance_named(&RetrievalReserveId::get(), &4), 100);
+		assert_ok!(FileSystem::bsp_confirm_delivery(RuntimeOrigin::signed(2), location.clone(), 4));
+
+		// If the held fee shrank, the BSP is not paid out of the stake, nor paid less.
+		assert_eq!(Balances::unreserve_named(&RetrievalReserveId::get(), &4, 40), 0);
+		assert_noop!(
+			FileSystem::confirm_retrieval(RuntimeOrigin::signed(4), location.clone(), 4),
+			Error::<Test>::RetrievalFeeShortfall
+		);
+		assert_eq!(Balances::reserved_balance(4), 360);
+		assert_eq!(Balances::free_balance(2), 1_000);
+	});
+}
+
+#[test]
+fn retrieval_fee_is_refunded_after_timeout() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		Balances::make_free_balance_be(&4, 1_000);
+		assert_ok!(FileSystem::request_retrieval(
+			RuntimeOrigin::signed(4),
+			location.clone(),
+			2,
+			100,
+			sender_multiaddress(),
+		));
+
+		// The fee cannot be refunded while the BSP still has time to deliver the file.
+		System::set_block_number(11);
+		assert_noop!(
+			FileSystem::refund_retrieval(RuntimeOrigin::signed(5), location.clone(), 4),
+			Error::<Test>::RetrievalNotExpired
+		);
+
+		// Once it runs out of time, it can no longer claim the delivery, and anyone can refund
+		// the fee.
+		System::set_block_number(12);
+		assert_noop!(
+			FileSystem::bsp_confirm_delivery(RuntimeOrigin::signed(2), location.clone(), 4),
+			Error::<Test>::RetrievalExpired
+		);
+		assert_ok!(FileSystem::refund_retrieval(RuntimeOrigin::signed(5), location.clone(), 4));
+
+		// Assert that the correct event was deposited
+		System::assert_last_event(
+			Event::RetrievalRefunded { who: 4, location: location.clone(), fee: 100 }.into(),
+		);
+
+		// Check that the user got the fee back.
+		assert_eq!(Balances::free_balance(4), 1_000);
+		assert_eq!(Balances::reserved_balance(4), 0);
+		assert!(!RetrievalRequests::<Test>::contains_key(&location, 4));
+	});
+}
+
+#[test]
+fn request_retrieval_validations() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// The file has to be stored.
+		let other_location = FileLocation::<Test>::try_from(b"other".to_vec()).unwrap();
+		assert_noop!(
+			FileSystem::request_retrieval(
+				RuntimeOrigin::signed(4),
+				other_location,
+				2,
+				100,
+				sender_multiaddress(),
+			),
+			Error::<Test>::FileNotStored
+		);
+
+		// The file has to be requested from one of its BSPs.
+		assert_noop!(
+			FileSystem::request_retrieval(
+				RuntimeOrigin::signed(4),
+				location.clone(),
+				5,
+				100,
+				sender_multiaddress(),
+			),
+			Error::<Test>::NotFileBsp
+		);
+
+		// Private files can only be retrieved by accounts allowed to read them.
+		assert_ok!(FileSystem::set_file_visibility(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			false
+		));
+		assert_noop!(
+			FileSystem::request_retrieval(
+				RuntimeOrigin::signed(4),
+				location.clone(),
+				2,
+				100,
+				sender_multiaddress(),
+			),
+			Error::<Test>::FileAccessDenied
+		);
+
+		// The requester has to afford the fee.
+		assert_noop!(
+			FileSystem::request_retrieval(
+				RuntimeOrigin::signed(1),
+				location,
+				2,
+				100,
+				sender_multiaddress(),
+			),
+			pallet_balances::Error::<Test>::InsufficientBalance
+		);
+	});
+}
+
+#[test]
+fn bsp_volunteer_is_free_and_checked_before_inclusion() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+		let bsp_volunteer = |bsp_multiaddress| {
+			RuntimeCall::FileSystem(crate::Call::bsp_volunteer {
+				location: location.clone(),
+				fingerprint,
+				bsp_multiaddress,
+			})
+		};
+		let validate = |who, call: &RuntimeCall| {
+			CheckBspVolunteer::<Test>::new().validate(&who, call, &call.get_dispatch_info(), 0)
+		};
+		let invalid = |error: Error<Test>| {
+			let DispatchError::Module(ModuleError { error, .. }) = error.into() else {
+				unreachable!("Pallet errors are module errors")
+			};
+			Err(TransactionValidityError::from(InvalidTransaction::Custom(error[0])))
+		};
+
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 2));
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+
+		// Other calls are not checked.
+		let request_storage = RuntimeCall::FileSystem(crate::Call::request_storage {
+			location: location.clone(),
+			fingerprint,
+			size: 4,
+			msp: MSP,
+			sender_multiaddress: sender_multiaddress(),
+		});
+		assert_ok!(validate(4, &request_storage));
+
+		// Volunteers that do not qualify are rejected.
+		assert_eq!(validate(4, &bsp_volunteer(bsp_multiaddress())), invalid(Error::<Test>::NotBsp));
+		let invalid_multiaddress = MultiAddress::<Test>::try_from(b"/ip4".to_vec()).unwrap();
+		assert_eq!(
+			validate(2, &bsp_volunteer(invalid_multiaddress)),
+			invalid(Error::<Test>::InvalidMultiAddress)
+		);
+		CurrentAssignmentThreshold::<Test>::put(0);
+		assert_eq!(
+			validate(2, &bsp_volunteer(bsp_multiaddress())),
+			invalid(Error::<Test>::ThresholdTooLow)
+		);
+		CurrentAssignmentThreshold::<Test>::kill();
+
+		// A qualifying volunteer provides a tag, so that the BSP cannot have two of them in the
+		// pool.
+		let valid = validate(2, &bsp_volunteer(bsp_multiaddress())).unwrap();
+		assert_eq!(valid.provides, vec![("CheckBspVolunteer", (2u64, &location)).encode()]);
+
+		// The BSP does not pay for volunteering.
+		let post_info = FileSystem::bsp_volunteer(
+			RuntimeOrigin::signed(2),
+			location.clone(),
+			fingerprint,
+			bsp_multiaddress(),
+		)
+		.unwrap();
+		assert_eq!(post_info.pays_fee, Pays::No);
+
+		// Once it volunteered, it cannot volunteer again.
+		assert_eq!(
+			validate(2, &bsp_volunteer(bsp_multiaddress())),
+			invalid(Error::<Test>::BspAlreadyRegistered)
+		);
+
+		// Volunteers that fail anyway, e.g. wrapped in another call, pay the fees.
+		let error = FileSystem::bsp_volunteer(
+			RuntimeOrigin::signed(2),
+			location.clone(),
+			fingerprint,
+			bsp_multiaddress(),
+		)
+		.unwrap_err();
+		assert_eq!(error.error, Error::<Test>::BspAlreadyRegistered.into());
+		assert_eq!(error.post_info.pays_fee, Pays::Yes);
+	});
+}
+
+#[test]
+fn try_state_checks_stored_files_have_bsps() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+
+	stored_file_ext(&location).execute_with(|| {
+		assert_ok!(FileSystem::do_try_state());
+
+		// Every BSP of a file has to be mapped to it.
+		BspsFiles::<Test>::remove(2, &location);
+		assert!(FileSystem::do_try_state().is_err());
+
+		// And every file has to have at least one BSP.
+		FilesMapping::<Test>::mutate(&location, |file_metadata| {
+			file_metadata.as_mut().unwrap().bsps.clear()
+		});
+		assert!(FileSystem::do_try_state().is_err());
+	});
+
+	stored_file_ext(&location).execute_with(|| {
+		// The storage used by BSPs has to match the files they store.
+		TotalUsedBspStorage::<Test>::put(12);
+		assert!(FileSystem::do_try_state().is_err());
+	});
+}
+
+#[test]
+fn migration_to_v1_translates_files_and_storage_requests() {
+	new_test_ext().execute_with(|| {
+		let stored = FileLocation::<Test>::try_from(b"stored".to_vec()).unwrap();
+		let requested = FileLocation::<Test>::try_from(b"requested".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+		let old_file_metadata = |bsps: Vec<u64>| OldFileMetadata::<Test> {
+			requested_at: 1,
+			fingerprint,
+			bsps: bsps.try_into().unwrap(),
+			is_public: true,
+		};
+
+		// Storage that is not versioned yet has the layout from before files had an owner, a size
+		// and an MSP: one file stored by BSP 2 and still open to volunteers, and one requested.
+		StorageVersion::new(0).put::<FileSystem>();
+		let put_old = |key: Vec<u8>, bsps: Vec<u64>| {
+			unhashed::put_raw(&key, &old_file_metadata(bsps).encode())
+		};
+		put_old(FilesMapping::<Test>::hashed_key_for(&stored), vec![2]);
+		put_old(StorageRequests::<Test>::hashed_key_for(&stored), vec![2]);
+		put_old(StorageRequests::<Test>::hashed_key_for(&requested), vec![]);
+		assert!(FilesMapping::<Test>::get(&stored).is_none());
+
+		// The storage used by BSPs counts the sizes the files were requested with.
+		TotalUsedBspStorage::<Test>::put(12);
+
+		#[cfg(feature = "try-runtime")]
+		let state = MigrateToV1::<Test, ConstU64<LEGACY_OWNER>>::pre_upgrade().unwrap();
+		MigrateToV1::<Test, ConstU64<LEGACY_OWNER>>::on_runtime_upgrade();
+		#[cfg(feature = "try-runtime")]
+		assert_ok!(MigrateToV1::<Test, ConstU64<LEGACY_OWNER>>::post_upgrade(state));
+		assert_eq!(FileSystem::on_chain_storage_version(), STORAGE_VERSION);
+
+		// Every entry is owned by the legacy owner, with no size nor MSP.
+		let file_metadata = |bsps: Vec<u64>| FileMetadata::<Test> {
+			owner: LEGACY_OWNER,
+			requested_at: 1,
+			fingerprint,
+			size: 0,
+			bsps: bsps.try_into().unwrap(),
+			is_public: true,
+			msp: None,
+			msp_accepted: false,
+		};
+		assert_eq!(FilesMapping::<Test>::get(&stored), Some(file_metadata(vec![2])));
+		assert_eq!(StorageRequests::<Test>::get(&stored), Some(file_metadata(vec![2])));
+		assert_eq!(StorageRequests::<Test>::get(&requested), Some(file_metadata(vec![])));
+		assert_eq!(UserStorageUsage::<Test>::get(LEGACY_OWNER).files, 2);
+
+		// The storage used by BSPs matches the sizes of the migrated files.
+		assert_eq!(TotalUsedBspStorage::<Test>::get(), Some(0));
+
+		// The stored file is mapped to its BSP.
+		assert!(BspsFiles::<Test>::contains_key(2, &stored));
+		assert_ok!(FileSystem::do_try_state());
+
+		// Versioned storage is not migrated again.
+		MigrateToV1::<Test, ConstU64<LEGACY_OWNER>>::on_runtime_upgrade();
+		assert_eq!(UserStorageUsage::<Test>::get(LEGACY_OWNER).files, 2);
+	});
+}
+
+#[test]
+fn xor_distance_supports_any_fingerprint_and_threshold_width() {
+	let bsp = BlakeTwo256::hash(b"bsp").0;
+	let not_bsp = bsp.map(|b| !b);
+
+	// The distance is decoded from the leading bytes of the XOR of the fingerprint and the BSP.
+	let fingerprint = [u8::MAX; 32];
+	assert_eq!(
+		xor_distance::<u128>(&fingerprint, &bsp),
+		Some(u128::from_le_bytes(not_bsp[..16].try_into().unwrap()))
+	);
+
+	// Narrower thresholds are decoded from fewer bytes.
+	assert_eq!(
+		xor_distance::<u32>(&fingerprint, &bsp),
+		Some(u32::from_le_bytes(not_bsp[..4].try_into().unwrap()))
+	);
+
+	// Fingerprints wider than the BSP are XORed with the BSP repeated.
+	let fingerprint = [u8::MAX; 64];
+	let expected: Vec<u8> = [not_bsp, not_bsp].concat();
+	assert_eq!(xor_distance::<[u8; 64]>(&fingerprint, &bsp).map(|d| d.to_vec()), Some(expected));
+
+	// Fingerprints narrower than the threshold cannot be decoded.
+	assert_eq!(xor_distance::<u128>(&[u8::MAX; 8], &bsp), None);
+}
+
+#[test]
+fn bsp_volunteer_fails_if_fingerprint_is_narrower_than_threshold() {
+	use crate::mock::narrow_fingerprint::{
+		new_test_ext, FileSystem, Identity, NarrowFingerprint, RuntimeOrigin, System, Test,
+	};
+
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = NarrowFingerprint([u8::MAX; 8]);
+		let multiaddress: MultiAddress<Test> = bsp_multiaddress().to_vec().try_into().unwrap();
+
+		// Register BSP in Identity Pallet.
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 2));
+
+		// Dispatch storage request.
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			multiaddress.clone(),
+		));
+
+		// The XOR distance of the 8-byte fingerprint cannot be decoded as a `u128` threshold.
+		assert_noop!(
+			FileSystem::bsp_volunteer(
+				RuntimeOrigin::signed(2),
+				location,
+				fingerprint,
+				multiaddress
+			),
+			Error::<Test>::FailedToDecodeThreshold
+		);
+	});
+}
+
+/// Set the assignment threshold so that BSP `who` becomes eligible for `fingerprint` a few blocks
+/// after it is requested.
+fn set_threshold_eligible_after_a_few_blocks(who: u64, fingerprint: &H256) {
+	let distance: u128 =
+		xor_distance(fingerprint.as_ref(), &BlakeTwo256::hash(&who.encode()).0).unwrap();
+	CurrentAssignmentThreshold::<Test>::put(distance.saturating_sub(5 * 10_000));
+}
+
+#[test]
+fn first_eligible_block_matches_volunteer_check() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 2));
+		set_threshold_eligible_after_a_few_blocks(2, &fingerprint);
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+
+		let file_metadata = StorageRequests::<Test>::get(&location).unwrap();
+		let eligible_at = FileSystem::first_eligible_block(&2, &file_metadata).unwrap();
+		assert_eq!(eligible_at, 6);
+
+		// The BSP cannot volunteer the block before.
+		System::set_block_number(eligible_at - 1);
+		assert_noop!(
+			FileSystem::bsp_volunteer(
+				RuntimeOrigin::signed(2),
+				location.clone(),
+				fingerprint,
+				bsp_multiaddress()
+			),
+			Error::<Test>::ThresholdTooLow
+		);
+
+		// But it can at the first eligible block.
+		System::set_block_number(eligible_at);
+		assert_ok!(FileSystem::bsp_volunteer(
+			RuntimeOrigin::signed(2),
+			location.clone(),
+			fingerprint,
+			bsp_multiaddress()
+		));
+
+		// A BSP that missed a deadline has 80% of the threshold, which reaches its distance
+		// once the unscaled threshold reaches 125% of it, rounded up.
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 3));
+		Reputation::record_failure(&3, Failure::MissedDeadline);
+		assert_eq!(Reputation::score(&3), Perbill::from_percent(80));
+		let distance: u128 =
+			xor_distance(fingerprint.as_ref(), &BlakeTwo256::hash(&3u64.encode()).0).unwrap();
+		let scaled_distance = distance.checked_add(distance.div_ceil(4)).unwrap();
+		CurrentAssignmentThreshold::<Test>::put(scaled_distance - 10 * 10_000);
+		assert_eq!(FileSystem::first_eligible_block(&3, &file_metadata), Some(11));
+
+		System::set_block_number(10);
+		assert_noop!(
+			FileSystem::bsp_volunteer(
+				RuntimeOrigin::signed(3),
+				location.clone(),
+				fingerprint,
+				bsp_multiaddress()
+			),
+			Error::<Test>::ThresholdTooLow
+		);
+		System::set_block_number(11);
+		assert_ok!(FileSystem::bsp_volunteer(
+			RuntimeOrigin::signed(3),
+			location,
+			fingerprint,
+			bsp_multiaddress()
+		));
+
+		// BSPs without reputation never become eligible.
+		Reputation::record_failure(&2, Failure::FailedChallenge);
+		Reputation::record_failure(&2, Failure::FailedChallenge);
+		assert_eq!(FileSystem::first_eligible_block(&2, &file_metadata), None);
+	});
+}
+
+#[test]
+fn offchain_worker_volunteers_local_bsps_once_eligible() {
+	let (offchain, _) = TestOffchainExt::new();
+	let (pool, pool_state) = TestTransactionPoolExt::new();
+	let mut ext = new_test_ext();
+	ext.register_extension(OffchainDbExt::new(offchain.clone()));
+	ext.register_extension(OffchainWorkerExt::new(offchain));
+	ext.register_extension(TransactionPoolExt::new(pool));
+
+	ext.execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+
+		// The keystore holds the keys of BSP 2 and of account 3, which is not a BSP.
+		UintAuthorityId::set_all_keys([2u64, 3]);
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 2));
+		set_threshold_eligible_after_a_few_blocks(2, &fingerprint);
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+		let file_metadata = StorageRequests::<Test>::get(&location).unwrap();
+		let eligible_at = FileSystem::first_eligible_block(&2, &file_metadata).unwrap();
+
+		// Nothing is submitted until the node sets the BSP multiaddress.
+		FileSystem::offchain_worker(eligible_at);
+		assert!(pool_state.read().transactions.is_empty());
+		StorageValueRef::persistent(BSP_MULTIADDRESS_KEY).set(&bsp_multiaddress());
+
+		// Nor before the BSP is eligible.
+		FileSystem::offchain_worker(eligible_at - 1);
+		assert!(pool_state.read().transactions.is_empty());
+
+		// At the first eligible block, the BSP volunteers.
+		FileSystem::offchain_worker(eligible_at);
+		let transaction = pool_state.write().transactions.pop().unwrap();
+		assert!(pool_state.read().transactions.is_empty());
+		let transaction = Extrinsic::decode(&mut &transaction[..]).unwrap();
+		assert_eq!(transaction.signature, Some((2, ())));
+		assert_eq!(
+			transaction.call,
+			RuntimeCall::FileSystem(crate::Call::bsp_volunteer {
+				location: location.clone(),
+				fingerprint,
+				bsp_multiaddress: bsp_multiaddress(),
+			})
+		);
+
+		// Once it volunteered, it no longer does.
+		System::set_block_number(eligible_at);
+		assert_ok!(transaction.call.dispatch(RuntimeOrigin::signed(2)));
+		FileSystem::offchain_worker(eligible_at + 1);
+		assert!(pool_state.read().transactions.is_empty());
+	});
+}
+
+#[test]
+fn offchain_worker_resumes_after_the_last_storage_request_checked() {
+	let (offchain, _) = TestOffchainExt::new();
+	let (pool, pool_state) = TestTransactionPoolExt::new();
+	let mut ext = new_test_ext();
+	ext.register_extension(OffchainDbExt::new(offchain.clone()));
+	ext.register_extension(OffchainWorkerExt::new(offchain));
+	ext.register_extension(TransactionPoolExt::new(pool));
+
+	ext.execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		UintAuthorityId::set_all_keys([2u64]);
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 2));
+		StorageValueRef::persistent(BSP_MULTIADDRESS_KEY).set(&bsp_multiaddress());
+
+		// The BSP is eligible for every request, one more than each run checks.
+		for name in [b"a", b"b", b"c"] {
+			assert_ok!(FileSystem::request_storage(
+				RuntimeOrigin::signed(1),
+				FileLocation::<Test>::try_from(name.to_vec()).unwrap(),
+				BlakeTwo256::hash(name),
+				4,
+				MSP,
+				sender_multiaddress(),
+			));
+		}
+		let locations: Vec<_> = StorageRequests::<Test>::iter_keys().collect();
+
+		let volunteered_for = || {
+			let transaction = pool_state.write().transactions.pop().unwrap();
+			assert!(pool_state.read().transactions.is_empty());
+			match Extrinsic::decode(&mut &transaction[..]).unwrap().call {
+				RuntimeCall::FileSystem(crate::Call::bsp_volunteer { location, .. }) => location,
+				call => panic!("Unexpected call {:?}", call),
+			}
+		};
+
+		// The first run checks the first two requests.
+		FileSystem::offchain_worker(1);
+		assert_eq!(volunteered_for(), locations[0]);
+
+		// The next run resumes after them, and reaches the end.
+		FileSystem::offchain_worker(2);
+		assert_eq!(volunteered_for(), locations[2]);
+
+		// So the one after starts over.
+		FileSystem::offchain_worker(3);
+		assert_eq!(volunteered_for(), locations[0]);
+	});
+}
+
+#[test]
+fn storage_request_sender_is_kept_until_fulfilled() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+		let fingerprint = BlakeTwo256::hash(b"test");
+		let bsps = [2, 3, 4, 5, 6];
+
+		for bsp in bsps {
+			assert_ok!(Identity::register_user(RuntimeOrigin::root(), bsp));
+		}
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			location.clone(),
+			fingerprint,
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+
+		// Check that the sender is kept while the storage request is open.
+		for bsp in &bsps[..4] {
+			assert_ok!(FileSystem::bsp_volunteer(
+				RuntimeOrigin::signed(*bsp),
+				location.clone(),
+				fingerprint,
+				bsp_multiaddress()
+			));
+			assert_eq!(StorageRequestSenders::<Test>::get(&location), Some(sender_multiaddress()));
+		}
+
+		// And dropped once it is fulfilled.
+		assert_ok!(FileSystem::bsp_volunteer(
+			RuntimeOrigin::signed(6),
+			location.clone(),
+			fingerprint,
+			bsp_multiaddress()
+		));
+		assert!(!StorageRequests::<Test>::contains_key(&location));
+		assert!(!StorageRequestSenders::<Test>::contains_key(&location));
+	});
+}
+
+#[test]
+fn offchain_worker_skips_storage_requests_without_sender() {
+	let location = FileLocation::<Test>::try_from(b"test".to_vec()).unwrap();
+	let other_location = FileLocation::<Test>::try_from(b"other".to_vec()).unwrap();
+	let (offchain, _) = TestOffchainExt::new();
+	let (pool, pool_state) = TestTransactionPoolExt::new();
+	let mut ext = stored_file_ext(&location);
+	ext.register_extension(OffchainDbExt::new(offchain.clone()));
+	ext.register_extension(OffchainWorkerExt::new(offchain));
+	ext.register_extension(TransactionPoolExt::new(pool));
+
+	ext.execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		UintAuthorityId::set_all_keys([4u64]);
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 4));
+		StorageValueRef::persistent(BSP_MULTIADDRESS_KEY).set(&bsp_multiaddress());
+
+		// Reopen the storage request of the stored file, which has no sender to fetch it from.
+		assert_ok!(Identity::remove_user(RuntimeOrigin::root(), 3));
+		FileSystem::on_idle(1, Weight::MAX);
+		assert!(StorageRequests::<Test>::contains_key(&location));
+		assert!(!StorageRequestSenders::<Test>::contains_key(&location));
+
+		// Nothing is submitted for it.
+		FileSystem::offchain_worker(1);
+		assert!(pool_state.read().transactions.is_empty());
+
+		// While requests with a sender are still volunteered for.
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			other_location.clone(),
+			BlakeTwo256::hash(b"other"),
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+		FileSystem::offchain_worker(2);
+		let transaction = pool_state.write().transactions.pop().unwrap();
+		match Extrinsic::decode(&mut &transaction[..]).unwrap().call {
+			RuntimeCall::FileSystem(crate::Call::bsp_volunteer { location, .. }) =>
+				assert_eq!(location, other_location),
+			call => panic!("Unexpected call {:?}", call),
+		}
+	});
+}
+
+#[test]
+fn offchain_worker_does_not_volunteer_while_locked() {
+	let (offchain, _) = TestOffchainExt::new();
+	let (pool, pool_state) = TestTransactionPoolExt::new();
+	let mut ext = new_test_ext();
+	ext.register_extension(OffchainDbExt::new(offchain.clone()));
+	ext.register_extension(OffchainWorkerExt::new(offchain));
+	ext.register_extension(TransactionPoolExt::new(pool));
+
+	ext.execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		UintAuthorityId::set_all_keys([2u64]);
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 2));
+		StorageValueRef::persistent(BSP_MULTIADDRESS_KEY).set(&bsp_multiaddress());
+		assert_ok!(FileSystem::request_storage(
+			RuntimeOrigin::signed(1),
+			FileLocation::<Test>::try_from(b"test".to_vec()).unwrap(),
+			BlakeTwo256::hash(b"test"),
+			4,
+			MSP,
+			sender_multiaddress(),
+		));
+
+		// Nothing is submitted while another run holds the lock.
+		let mut lock = FileSystem::offchain_lock();
+		let guard = lock.try_lock().unwrap();
+		FileSystem::offchain_worker(1);
+		assert!(pool_state.read().transactions.is_empty());
+
+		// Once it is released, the BSP volunteers.
+		drop(guard);
+		FileSystem::offchain_worker(1);
+		assert_eq!(pool_state.read().transactions.len(), 1);
+	});
+}
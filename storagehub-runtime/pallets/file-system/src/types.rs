@@ -1,30 +1,115 @@
 use codec::{Decode, Encode, MaxEncodedLen};
-use frame_support::BoundedVec;
+use frame_support::{
+	traits::{Currency, NamedReservableCurrency},
+	BoundedVec, DefaultNoBound,
+};
 use frame_system::pallet_prelude::BlockNumberFor;
-use scale_info::TypeInfo;
+use scale_info::{prelude::vec::Vec, TypeInfo};
 
 ///
 #[derive(Encode, Decode, MaxEncodedLen, TypeInfo, Debug, PartialEq, Eq, Clone)]
 #[scale_info(skip_type_params(T))]
 pub struct FileMetadata<T: crate::Config> {
+	pub owner: T::AccountId,
 	pub requested_at: BlockNumberFor<T>,
 	pub fingerprint: Fingerprint<T>,
+	pub size: StorageCount<T>,
 	pub bsps: BoundedVec<StorageProviderId<T>, MaxBsps<T>>,
 	pub is_public: bool,
+	/// The Main Storage Provider picked by the owner, if it has not rejected the file.
+	pub msp: Option<StorageProviderId<T>>,
+	/// Whether the MSP accepted to store the file. Only the MSP's word, see
+	/// [`crate::Pallet::msp_accept_storage_request`].
+	pub msp_accepted: bool,
 }
 
+/// A new version of a stored file, waiting for its BSPs to confirm they fetched it.
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, Debug, PartialEq, Eq, Clone)]
+#[scale_info(skip_type_params(T))]
+pub struct FileUpdate<T: crate::Config> {
+	pub requested_at: BlockNumberFor<T>,
+	pub fingerprint: Fingerprint<T>,
+	pub size: StorageCount<T>,
+	pub confirmed_bsps: BoundedVec<StorageProviderId<T>, MaxBsps<T>>,
+}
+
+/// A previous version of a file, kept in its version history.
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, Debug, PartialEq, Eq, Clone)]
+#[scale_info(skip_type_params(T))]
+pub struct FileVersion<T: crate::Config> {
+	pub fingerprint: Fingerprint<T>,
+	pub size: StorageCount<T>,
+	pub replaced_at: BlockNumberFor<T>,
+}
+
+/// Limits on the files a user can store.
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, Debug, PartialEq, Eq, Clone)]
+#[scale_info(skip_type_params(T))]
+pub struct StorageQuota<T: crate::Config> {
+	pub max_bytes: StorageCount<T>,
+	pub max_files: u32,
+}
+
+/// Total size and number of the files stored by a user.
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, Debug, PartialEq, Eq, Clone, DefaultNoBound)]
+#[scale_info(skip_type_params(T))]
+pub struct StorageUsage<T: crate::Config> {
+	pub bytes: StorageCount<T>,
+	pub files: u32,
+}
+
+/// A request to retrieve a file from one of its BSPs, whose fee is held until the delivery is
+/// settled.
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, Debug, PartialEq, Eq, Clone)]
+#[scale_info(skip_type_params(T))]
+pub struct RetrievalRequest<T: crate::Config> {
+	pub bsp: StorageProviderId<T>,
+	pub fee: BalanceOf<T>,
+	pub requested_at: BlockNumberFor<T>,
+	/// The block in which the BSP claimed to have delivered the file, if it did.
+	pub delivered_at: Option<BlockNumberFor<T>>,
+}
+
+/// A file stored from genesis: its owner, location, fingerprint, size and the BSPs storing it.
+pub type GenesisFile<T> = (
+	<T as frame_system::Config>::AccountId,
+	FileLocation<T>,
+	Fingerprint<T>,
+	StorageCount<T>,
+	Vec<StorageProviderId<T>>,
+);
+
+/// The previous versions of a file, oldest first.
+pub type FileVersionHistory<T> = BoundedVec<FileVersion<T>, MaxFileVersions<T>>;
+
+/// A batch of storage requests: the location, fingerprint and size of each file.
+pub type StorageRequestBatch<T> =
+	BoundedVec<(FileLocation<T>, Fingerprint<T>, StorageCount<T>), MaxBatchSize<T>>;
+
 /// A byte array representing the file path.
 pub type FileLocation<T> = BoundedVec<u8, MaxFilePathSize<T>>;
 
-/// A byte array representing the libp2p multiaddress.
+/// A byte array representing a libp2p PeerId.
+pub type PeerId<T> = BoundedVec<u8, MaxPeerIdSize<T>>;
+
+/// A libp2p multiaddress in its text form, ending with a `/p2p/` component.
 pub type MultiAddress<T> = BoundedVec<u8, MaxMultiAddressSize<T>>;
 
 /// Syntactic sugar for the MaxBsps type used in the FileSystem pallet.
 pub type MaxBsps<T> = <T as crate::Config>::MaxBsps;
 
+/// Syntactic sugar for the MaxBatchSize type used in the FileSystem pallet.
+pub type MaxBatchSize<T> = <T as crate::Config>::MaxBatchSize;
+
+/// Syntactic sugar for the MaxFileVersions type used in the FileSystem pallet.
+pub type MaxFileVersions<T> = <T as crate::Config>::MaxFileVersions;
+
 /// Syntactic sugar for the MaxFilePathSize type used in the FileSystem pallet.
 pub type MaxFilePathSize<T> = <T as crate::Config>::MaxFilePathSize;
 
+/// Syntactic sugar for the MaxPeerIdSize type used in the FileSystem pallet.
+pub type MaxPeerIdSize<T> = <T as crate::Config>::MaxPeerIdSize;
+
 /// Syntactic sugar for the MaxMultiAddressSize type used in the FileSystem pallet.
 pub type MaxMultiAddressSize<T> = <T as crate::Config>::MaxMultiAddressSize;
 
@@ -34,5 +119,14 @@ pub type Fingerprint<T> = <T as crate::Config>::Fingerprint;
 /// Syntactic sugar for the type StorageProviderId used in the System pallet.
 pub type StorageProviderId<T> = <T as frame_system::Config>::AccountId;
 
+/// Syntactic sugar for the balance type of the Currency used in the FileSystem pallet.
+pub type BalanceOf<T> =
+	<<T as crate::Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+/// Syntactic sugar for the reserve identifier of the Currency used in the FileSystem pallet.
+pub type ReserveIdentifierOf<T> = <<T as crate::Config>::Currency as NamedReservableCurrency<
+	<T as frame_system::Config>::AccountId,
+>>::ReserveIdentifier;
+
 /// Syntactic sugar for the type StorageCount used in the System pallet.
 pub type StorageCount<T> = <T as crate::Config>::StorageCount;
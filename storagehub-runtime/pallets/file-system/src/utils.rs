@@ -1,18 +1,52 @@
 use codec::{Decode, Encode};
-use frame_support::{ensure, pallet_prelude::DispatchResult, sp_runtime::BoundedVec, traits::Get};
-use pallet_identity::IdentityInterface;
+use frame_support::{
+	ensure,
+	pallet_prelude::{DispatchError, DispatchResult, Weight},
+	sp_runtime::BoundedVec,
+	traits::{BalanceStatus, Get, NamedReservableCurrency},
+};
+use frame_system::{
+	offchain::{AppCrypto, SendSignedTransaction, Signer},
+	pallet_prelude::BlockNumberFor,
+};
+use pallet_identity::{IdentityInterface, OnIdentityChange};
+use pallet_reputation::{Failure, ReputationInterface};
 use scale_info::prelude::vec::Vec;
+use sp_core::ed25519;
 use sp_runtime::{
-	traits::{BlakeTwo256, Hash},
-	SaturatedConversion, Saturating,
+	offchain::{
+		storage::StorageValueRef,
+		storage_lock::{BlockAndTime, StorageLock},
+		Duration,
+	},
+	traits::{BlakeTwo256, Bounded, Hash, IdentifyAccount, Zero},
+	Perbill, RuntimeAppPublic, SaturatedConversion, Saturating,
 };
 
 use crate::{
-	pallet,
-	types::{FileLocation, FileMetadata, Fingerprint},
-	Config, Error, FilesMapping, Pallet, StorageRequests,
+	multiaddress, pallet,
+	types::{
+		BalanceOf, FileLocation, FileMetadata, FileUpdate, FileVersion, Fingerprint, MaxBsps,
+		MultiAddress, PeerId, RetrievalRequest, StorageCount, StorageQuota, StorageUsage,
+	},
+	BspsFiles, Config, CurrentAssignmentThreshold, Error, Event, FileReaders, FileSystemHooks,
+	FileUpdates, FileVersions, FilesMapping, LeavingBsps, Pallet, RetrievalRequests,
+	StorageRequestSenders, StorageRequests, TotalUsedBspStorage, UserQuotas, UserStorageUsage,
 };
 
+/// Log target of the offchain worker.
+const LOG_TARGET: &str = "runtime::file-system";
+
+/// Offchain persistent storage key of the raw key of the last storage request checked by the
+/// offchain worker, which the next run resumes after.
+pub(crate) const OFFCHAIN_CURSOR_KEY: &[u8] = b"storagehub::bsp-volunteer-cursor";
+
+/// Offchain persistent storage key of the lock held while the offchain worker volunteers.
+pub(crate) const OFFCHAIN_LOCK_KEY: &[u8] = b"storagehub::bsp-volunteer-lock";
+
+/// Prefix of the payload signed by the key of a PeerId to register it.
+const PEER_ID_REGISTRATION_CONTEXT: &[u8] = b"storagehub:register_peer_id";
+
 macro_rules! expect_or_err {
 	($optional:expr, $error_msg:expr, $error_type:path) => {
 		match $optional {
@@ -35,18 +69,36 @@ where
 	T: pallet::Config,
 {
 	pub fn do_request_storage(
+		who: T::AccountId,
 		location: FileLocation<T>,
 		content_id: Fingerprint<T>,
+		size: StorageCount<T>,
+		msp: T::AccountId,
+		sender_multiaddress: MultiAddress<T>,
 	) -> DispatchResult {
 		// TODO: Perform various checks of users funds, storage capacity, etc.
 		// TODO: Not relevant for PoC.
 
+		// Check that the picked MSP is a registered main storage provider.
+		ensure!(<T as Config>::MspsRegistry::get_user(msp.clone()).is_some(), Error::<T>::NotMsp);
+
+		// Check that the file fits in the user's storage quota, and account for it.
+		Self::ensure_file_size(size)?;
+		let mut usage = <UserStorageUsage<T>>::get(&who);
+		usage.bytes = usage.bytes.saturating_add(size);
+		usage.files = usage.files.saturating_add(1);
+		Self::ensure_within_quota(&who, &usage)?;
+
 		// Construct file metadata.
 		let file_metadata = FileMetadata::<T> {
+			owner: who.clone(),
 			requested_at: <frame_system::Pallet<T>>::block_number(),
 			fingerprint: content_id.clone(),
+			size,
 			bsps: BoundedVec::default(),
 			is_public: true,
+			msp: Some(msp),
+			msp_accepted: false,
 		};
 
 		// Check that storage request is not already registered.
@@ -55,88 +107,951 @@ where
 			Error::<T>::StorageRequestAlreadyRegistered
 		);
 
+		// Check that no file is stored at that location. New versions go through `update_file`.
+		ensure!(!<FilesMapping<T>>::contains_key(&location), Error::<T>::FileAlreadyStored);
+
 		// Register storage request.
 		<StorageRequests<T>>::insert(&location, file_metadata);
+		<StorageRequestSenders<T>>::insert(&location, sender_multiaddress);
+		<UserStorageUsage<T>>::insert(&who, usage);
+
+		T::FileSystemHooks::on_storage_requested(&who, &location, size);
+
+		Ok(())
+	}
+
+	/// The storage quota of `who`, which is the default one unless a different one was set.
+	pub fn storage_quota(who: &T::AccountId) -> StorageQuota<T> {
+		<UserQuotas<T>>::get(who).unwrap_or_else(|| StorageQuota::<T> {
+			max_bytes: T::DefaultMaxStoredBytes::get(),
+			max_files: T::DefaultMaxStoredFiles::get(),
+		})
+	}
+
+	/// Check that a file of `size` is not larger than [`crate::Config::MaxFileSize`].
+	fn ensure_file_size(size: StorageCount<T>) -> DispatchResult {
+		ensure!(size <= T::MaxFileSize::get(), Error::<T>::FileTooLarge);
+
+		Ok(())
+	}
+
+	/// Check that `multiaddress` is a valid multiaddress, that peers can dial to fetch files.
+	pub fn ensure_valid_multiaddress(multiaddress: &MultiAddress<T>) -> DispatchResult {
+		ensure!(multiaddress::is_valid(multiaddress), Error::<T>::InvalidMultiAddress);
+
+		Ok(())
+	}
+
+	/// Check that `usage` is within the storage quota of `who`.
+	fn ensure_within_quota(who: &T::AccountId, usage: &StorageUsage<T>) -> DispatchResult {
+		let quota = Self::storage_quota(who);
+		ensure!(usage.bytes <= quota.max_bytes, Error::<T>::StorageQuotaExceeded);
+		ensure!(usage.files <= quota.max_files, Error::<T>::FileCountQuotaExceeded);
+
+		Ok(())
+	}
+
+	/// The metadata of the file at `location`, whether it is stored or still requested.
+	pub fn file_metadata(location: &FileLocation<T>) -> Option<FileMetadata<T>> {
+		<FilesMapping<T>>::get(location).or_else(|| <StorageRequests<T>>::get(location))
+	}
+
+	/// Check that `who` owns the file at `location`.
+	pub fn ensure_file_owner(who: &T::AccountId, location: &FileLocation<T>) -> DispatchResult {
+		let file_metadata = Self::file_metadata(location).ok_or(Error::<T>::FileNotStored)?;
+		ensure!(&file_metadata.owner == who, Error::<T>::NotFileOwner);
+
+		Ok(())
+	}
+
+	/// The payload the key of a PeerId has to sign for `who` to register it: `who` and the
+	/// genesis hash of the chain, so that the signature can't be used for anyone else or replayed
+	/// on another chain.
+	pub fn peer_id_registration_payload(who: &T::AccountId) -> Vec<u8> {
+		let genesis_hash = frame_system::Pallet::<T>::block_hash(BlockNumberFor::<T>::zero());
+		(PEER_ID_REGISTRATION_CONTEXT, who, genesis_hash).encode()
+	}
+
+	/// Check that `signature` is the signature of the registration of `peer_id` for `who`, by the
+	/// ed25519 key the PeerId was derived from.
+	pub fn ensure_peer_id_signature(
+		who: &T::AccountId,
+		peer_id: &PeerId<T>,
+		signature: &ed25519::Signature,
+	) -> DispatchResult {
+		let public_key =
+			multiaddress::ed25519_public_key(peer_id).ok_or(Error::<T>::UnsupportedPeerId)?;
+
+		ensure!(
+			sp_io::crypto::ed25519_verify(
+				signature,
+				&Self::peer_id_registration_payload(who),
+				&ed25519::Public::from_raw(public_key),
+			),
+			Error::<T>::InvalidPeerIdSignature
+		);
+
+		Ok(())
+	}
+
+	pub fn do_set_file_visibility(
+		who: T::AccountId,
+		location: FileLocation<T>,
+		is_public: bool,
+	) -> DispatchResult {
+		// Check that the sender owns the file.
+		Self::ensure_file_owner(&who, &location)?;
+
+		// Update the file and its storage request, if still open.
+		let set_visibility = |file_metadata: &mut Option<FileMetadata<T>>| {
+			if let Some(file_metadata) = file_metadata {
+				file_metadata.is_public = is_public;
+			}
+		};
+		<FilesMapping<T>>::mutate(&location, set_visibility);
+		<StorageRequests<T>>::mutate(&location, set_visibility);
+
+		Ok(())
+	}
+
+	/// Whether `who` can read the file at `location`.
+	///
+	/// Public files can be read by anyone, while private files can only be read by their owner,
+	/// their MSP, their BSPs and the accounts in their allow-list.
+	pub fn can_read(who: &T::AccountId, location: &FileLocation<T>) -> bool {
+		let Some(file_metadata) = Self::file_metadata(location) else { return false };
+
+		file_metadata.is_public ||
+			&file_metadata.owner == who ||
+			file_metadata.bsps.contains(who) ||
+			file_metadata.msp.as_ref() == Some(who) ||
+			<FileReaders<T>>::contains_key(location, who)
+	}
+
+	/// Accept or reject storing the file at `location`, as the MSP picked for it.
+	pub fn do_msp_respond_storage_request(
+		who: &T::AccountId,
+		location: &FileLocation<T>,
+		accept: bool,
+	) -> DispatchResult {
+		// Check that the sender is the MSP picked for the file, and has not accepted it yet.
+		let file_metadata = Self::file_metadata(location).ok_or(Error::<T>::FileNotStored)?;
+		ensure!(file_metadata.msp.as_ref() == Some(who), Error::<T>::NotFileMsp);
+		ensure!(!file_metadata.msp_accepted, Error::<T>::MspAlreadyAccepted);
+
+		// Update the file and its storage request, if still open.
+		let respond = |file_metadata: &mut Option<FileMetadata<T>>| {
+			if let Some(file_metadata) = file_metadata {
+				if accept {
+					file_metadata.msp_accepted = true;
+				} else {
+					file_metadata.msp = None;
+				}
+			}
+		};
+		<FilesMapping<T>>::mutate(location, respond);
+		<StorageRequests<T>>::mutate(location, respond);
+
+		Ok(())
+	}
+
+	/// Hold `fee` from `who` and register its request to retrieve the file at `location` from
+	/// `bsp`.
+	pub fn do_request_retrieval(
+		who: &T::AccountId,
+		location: &FileLocation<T>,
+		bsp: &T::AccountId,
+		fee: BalanceOf<T>,
+	) -> DispatchResult {
+		// Check that the file is stored by the BSP, and that the sender can read it.
+		let file_metadata = <FilesMapping<T>>::get(location).ok_or(Error::<T>::FileNotStored)?;
+		ensure!(file_metadata.bsps.contains(bsp), Error::<T>::NotFileBsp);
+		ensure!(Self::can_read(who, location), Error::<T>::FileAccessDenied);
+
+		// Check that the sender has no pending request for the file.
+		ensure!(
+			!<RetrievalRequests<T>>::contains_key(location, who),
+			Error::<T>::RetrievalAlreadyRequested
+		);
+
+		// Hold the fee and register the request.
+		T::Currency::reserve_named(&T::RetrievalReserveId::get(), who, fee)?;
+		<RetrievalRequests<T>>::insert(
+			location,
+			who,
+			RetrievalRequest::<T> {
+				bsp: bsp.clone(),
+				fee,
+				requested_at: <frame_system::Pallet<T>>::block_number(),
+				delivered_at: None,
+			},
+		);
 
 		Ok(())
 	}
 
+	/// Mark the file at `location` as delivered by `bsp` to `who`, starting its dispute period.
+	pub fn do_bsp_confirm_delivery(
+		bsp: &T::AccountId,
+		location: &FileLocation<T>,
+		who: &T::AccountId,
+	) -> DispatchResult {
+		<RetrievalRequests<T>>::try_mutate(location, who, |retrieval_request| {
+			// Check that the file was requested from the sender, and that it is not too late.
+			let retrieval_request =
+				retrieval_request.as_mut().ok_or(Error::<T>::RetrievalNotRequested)?;
+			ensure!(&retrieval_request.bsp == bsp, Error::<T>::NotRetrievalBsp);
+			ensure!(
+				retrieval_request.delivered_at.is_none(),
+				Error::<T>::RetrievalAlreadyDelivered
+			);
+			ensure!(!Self::is_retrieval_expired(retrieval_request), Error::<T>::RetrievalExpired);
+
+			// Keep the fee held until the delivery is confirmed or disputed.
+			retrieval_request.delivered_at = Some(<frame_system::Pallet<T>>::block_number());
+
+			Ok(())
+		})
+	}
+
+	/// Pay the BSP the fee held for delivering the file at `location` to `who`, on behalf of
+	/// `sender`.
+	///
+	/// Returns the BSP paid and the fee.
+	pub fn do_confirm_retrieval(
+		sender: &T::AccountId,
+		location: &FileLocation<T>,
+		who: &T::AccountId,
+	) -> Result<(T::AccountId, BalanceOf<T>), DispatchError> {
+		// Check that the BSP claimed the delivery, and that the sender is the requester or the
+		// requester ran out of time to dispute it.
+		let retrieval_request =
+			<RetrievalRequests<T>>::get(location, who).ok_or(Error::<T>::RetrievalNotRequested)?;
+		let delivered_at =
+			retrieval_request.delivered_at.ok_or(Error::<T>::RetrievalNotDelivered)?;
+		ensure!(
+			sender == who || Self::is_dispute_period_ended(delivered_at),
+			Error::<T>::DisputePeriodNotEnded
+		);
+
+		// Pay the held fee to the BSP, failing if any of it could not be moved.
+		let shortfall = T::Currency::repatriate_reserved_named(
+			&T::RetrievalReserveId::get(),
+			who,
+			&retrieval_request.bsp,
+			retrieval_request.fee,
+			BalanceStatus::Free,
+		)?;
+		ensure!(shortfall.is_zero(), Error::<T>::RetrievalFeeShortfall);
+		<RetrievalRequests<T>>::remove(location, who);
+
+		Ok((retrieval_request.bsp, retrieval_request.fee))
+	}
+
+	/// Refund `who` the fee held for retrieving the file at `location`, disputing the delivery
+	/// claimed by its BSP.
+	///
+	/// Returns the BSP whose delivery was disputed and the fee refunded.
+	pub fn do_dispute_retrieval(
+		location: &FileLocation<T>,
+		who: &T::AccountId,
+	) -> Result<(T::AccountId, BalanceOf<T>), DispatchError> {
+		// Check that the BSP claimed the delivery, and that it is not too late to dispute it.
+		let retrieval_request =
+			<RetrievalRequests<T>>::get(location, who).ok_or(Error::<T>::RetrievalNotRequested)?;
+		let delivered_at =
+			retrieval_request.delivered_at.ok_or(Error::<T>::RetrievalNotDelivered)?;
+		ensure!(!Self::is_dispute_period_ended(delivered_at), Error::<T>::DisputePeriodEnded);
+
+		// Release the held fee.
+		T::Currency::unreserve_named(&T::RetrievalReserveId::get(), who, retrieval_request.fee);
+		<RetrievalRequests<T>>::remove(location, who);
+
+		Ok((retrieval_request.bsp, retrieval_request.fee))
+	}
+
+	/// Refund `who` the fee held for retrieving the file at `location`, once the BSP ran out of
+	/// time to deliver it.
+	///
+	/// Returns the fee refunded.
+	pub fn do_refund_retrieval(
+		location: &FileLocation<T>,
+		who: &T::AccountId,
+	) -> Result<BalanceOf<T>, DispatchError> {
+		// Check that the retrieval was requested, and that the BSP ran out of time without
+		// claiming to deliver it.
+		let retrieval_request =
+			<RetrievalRequests<T>>::get(location, who).ok_or(Error::<T>::RetrievalNotRequested)?;
+		ensure!(retrieval_request.delivered_at.is_none(), Error::<T>::RetrievalAlreadyDelivered);
+		ensure!(Self::is_retrieval_expired(&retrieval_request), Error::<T>::RetrievalNotExpired);
+
+		// Release the held fee.
+		T::Currency::unreserve_named(&T::RetrievalReserveId::get(), who, retrieval_request.fee);
+		<RetrievalRequests<T>>::remove(location, who);
+
+		Ok(retrieval_request.fee)
+	}
+
+	/// Whether the BSP of `retrieval_request` ran out of time to deliver the file.
+	fn is_retrieval_expired(retrieval_request: &RetrievalRequest<T>) -> bool {
+		let deadline = retrieval_request.requested_at.saturating_add(T::RetrievalTimeout::get());
+		<frame_system::Pallet<T>>::block_number() > deadline
+	}
+
+	/// Whether the requester of a file delivered at `delivered_at` ran out of time to dispute it.
+	fn is_dispute_period_ended(delivered_at: BlockNumberFor<T>) -> bool {
+		let deadline = delivered_at.saturating_add(T::RetrievalDisputePeriod::get());
+		<frame_system::Pallet<T>>::block_number() > deadline
+	}
+
+	/// Register a new version of the file at `location`, replacing any pending one.
+	///
+	/// Returns the BSPs that have to confirm it.
+	pub fn do_update_file(
+		who: T::AccountId,
+		location: FileLocation<T>,
+		fingerprint: Fingerprint<T>,
+		size: StorageCount<T>,
+	) -> Result<BoundedVec<T::AccountId, MaxBsps<T>>, DispatchError> {
+		// Check that the file is stored and that the sender owns it.
+		let file_metadata = <FilesMapping<T>>::get(&location).ok_or(Error::<T>::FileNotStored)?;
+		ensure!(file_metadata.owner == who, Error::<T>::NotFileOwner);
+
+		// Check that the content actually changes.
+		ensure!(file_metadata.fingerprint != fingerprint, Error::<T>::FileVersionUnchanged);
+
+		// Check that the new version fits in the user's storage quota.
+		Self::ensure_file_size(size)?;
+		let mut usage = <UserStorageUsage<T>>::get(&who);
+		usage.bytes = usage.bytes.saturating_sub(file_metadata.size).saturating_add(size);
+		Self::ensure_within_quota(&who, &usage)?;
+
+		// Register the update, dropping the confirmations of any previous pending update.
+		<FileUpdates<T>>::insert(
+			&location,
+			FileUpdate::<T> {
+				requested_at: <frame_system::Pallet<T>>::block_number(),
+				fingerprint,
+				size,
+				confirmed_bsps: BoundedVec::default(),
+			},
+		);
+
+		Ok(file_metadata.bsps)
+	}
+
+	pub fn do_bsp_confirm_update(
+		who: T::AccountId,
+		location: FileLocation<T>,
+		fingerprint: Fingerprint<T>,
+	) -> DispatchResult {
+		// Check that there is a pending update for the file.
+		let mut file_update =
+			<FileUpdates<T>>::get(&location).ok_or(Error::<T>::FileUpdateNotRequested)?;
+
+		// Check that sender is one of the BSPs storing the file.
+		let file_metadata = <FilesMapping<T>>::get(&location).ok_or(Error::<T>::FileNotStored)?;
+		ensure!(file_metadata.bsps.contains(&who), Error::<T>::NotFileBsp);
+
+		// Check that the BSP confirms the pending version, and only once.
+		ensure!(file_update.fingerprint == fingerprint, Error::<T>::FileUpdateMismatch);
+		ensure!(!file_update.confirmed_bsps.contains(&who), Error::<T>::FileUpdateAlreadyConfirmed);
+
+		// Register the confirmation.
+		file_update
+			.confirmed_bsps
+			.try_push(who.clone())
+			.map_err(|_| Error::<T>::FileUpdateAlreadyConfirmed)?;
+		<FileUpdates<T>>::insert(&location, file_update);
+
+		T::Reputation::record_success(&who);
+
+		Ok(())
+	}
+
+	/// Expire the pending update of the file at `location`, once its BSPs ran out of time to
+	/// confirm it.
+	///
+	/// The BSPs that did not confirm it are penalized and removed from the file, or the update is
+	/// dropped if no BSP confirmed it.
+	pub fn do_expire_file_update(location: &FileLocation<T>) -> DispatchResult {
+		// Check that there is a pending update for the file, and that its deadline has passed.
+		let file_update =
+			<FileUpdates<T>>::get(location).ok_or(Error::<T>::FileUpdateNotRequested)?;
+		let deadline = file_update.requested_at.saturating_add(T::UpdateConfirmationPeriod::get());
+		ensure!(
+			<frame_system::Pallet<T>>::block_number() > deadline,
+			Error::<T>::FileUpdateNotExpired
+		);
+
+		// Drop the update if no BSP confirmed it.
+		if file_update.confirmed_bsps.is_empty() {
+			<FileUpdates<T>>::remove(location);
+
+			// Emit event.
+			Self::deposit_event(Event::FileUpdateDropped {
+				location: location.clone(),
+				fingerprint: file_update.fingerprint,
+			});
+
+			return Ok(())
+		}
+
+		// Get the BSPs that did not confirm the update.
+		let mut missed_bsps =
+			<FilesMapping<T>>::get(location).ok_or(Error::<T>::FileNotStored)?.bsps;
+		missed_bsps.retain(|bsp| !file_update.confirmed_bsps.contains(bsp));
+
+		// Emit event.
+		Self::deposit_event(Event::FileUpdateDeadlineMissed {
+			location: location.clone(),
+			fingerprint: file_update.fingerprint,
+			bsps: missed_bsps.clone(),
+		});
+
+		// Penalize them and remove them from the file, which completes the update.
+		for bsp in missed_bsps.iter() {
+			T::Reputation::record_failure(bsp, Failure::MissedDeadline);
+			Self::reassign_file(bsp, location.clone());
+		}
+
+		Ok(())
+	}
+
+	/// Replace the file at `location` with its pending version, if every BSP storing the file
+	/// has confirmed it.
+	///
+	/// The replaced version is kept in the version history, dropping the oldest one if the
+	/// history is full.
+	pub fn try_complete_file_update(location: &FileLocation<T>) {
+		let Some(file_update) = <FileUpdates<T>>::get(location) else { return };
+		let Some(mut file_metadata) = <FilesMapping<T>>::get(location) else { return };
+
+		// Check that every BSP storing the file has confirmed the new version.
+		if file_metadata.bsps.iter().any(|bsp| !file_update.confirmed_bsps.contains(bsp)) {
+			return
+		}
+
+		// Keep the current version in the history.
+		let previous_fingerprint = file_metadata.fingerprint;
+		let previous_version = FileVersion::<T> {
+			fingerprint: file_metadata.fingerprint,
+			size: file_metadata.size,
+			replaced_at: <frame_system::Pallet<T>>::block_number(),
+		};
+		<FileVersions<T>>::mutate(location, |versions| {
+			let _ = versions.force_insert_keep_right(versions.len(), previous_version);
+		});
+
+		// Account for the size of the new version.
+		<UserStorageUsage<T>>::mutate(&file_metadata.owner, |usage| {
+			usage.bytes =
+				usage.bytes.saturating_sub(file_metadata.size).saturating_add(file_update.size);
+		});
+
+		// Account for the size of the new version stored by each BSP.
+		let bsps_count: StorageCount<T> = (file_metadata.bsps.len() as u32).into();
+		Self::decrease_used_bsps_storage(file_metadata.size.saturating_mul(bsps_count));
+		Self::increase_used_bsps_storage(file_update.size.saturating_mul(bsps_count));
+
+		// Replace the current version with the new one.
+		file_metadata.fingerprint = file_update.fingerprint;
+		file_metadata.size = file_update.size;
+		<FilesMapping<T>>::insert(location, file_metadata.clone());
+		<FileUpdates<T>>::remove(location);
+
+		T::FileSystemHooks::on_file_updated(location, &file_metadata);
+
+		// New volunteers of an open storage request have to fetch the new version.
+		<StorageRequests<T>>::mutate(location, |storage_request| {
+			if let Some(storage_request) = storage_request {
+				storage_request.fingerprint = file_update.fingerprint;
+				storage_request.size = file_update.size;
+			}
+		});
+
+		// Emit event.
+		Self::deposit_event(Event::FileUpdated {
+			location: location.clone(),
+			fingerprint: file_update.fingerprint,
+			previous_fingerprint,
+		});
+	}
+
 	pub fn do_bsp_volunteer(
 		who: T::AccountId,
 		location: FileLocation<T>,
 		fingerprint: Fingerprint<T>,
 	) -> DispatchResult {
-		// TODO: Perform various checks of BSP staking, total capacity, etc.
+		// Perform validations and get storage request metadata.
+		let mut file_metadata = Self::check_bsp_volunteer(&who, &location, &fingerprint)?;
+
+		// Add BSP to storage request metadata.
+		file_metadata
+			.bsps
+			.try_push(who.clone())
+			.map_err(|_| Error::<T>::BspAlreadyRegistered)?;
+		<StorageRequests<T>>::set(&location, Some(file_metadata.clone()));
+
+		// Add or update file in FilesMapping now that it has at least one BSP.
+		<FilesMapping<T>>::insert(&location, file_metadata.clone());
+		<BspsFiles<T>>::insert(&who, &location, ());
+		Self::increase_used_bsps_storage(file_metadata.size);
+
+		T::FileSystemHooks::on_bsp_volunteered(&who, &location);
+
+		// Check if maximum number of BSPs has been reached.
+		if file_metadata.bsps.len() == T::MaxBsps::get() as usize {
+			// Clear storage request from StorageRequests.
+			<StorageRequests<T>>::remove(&location);
+			<StorageRequestSenders<T>>::remove(&location);
+
+			T::FileSystemHooks::on_file_stored(&location, &file_metadata);
+		}
+
+		Ok(())
+	}
+
+	/// Check that `who` can volunteer as BSP for the storage request at `location`, returning
+	/// the storage request metadata.
+	///
+	/// This does not modify storage, so it is also used to validate volunteers before they are
+	/// included in a block.
+	pub fn check_bsp_volunteer(
+		who: &T::AccountId,
+		location: &FileLocation<T>,
+		fingerprint: &Fingerprint<T>,
+	) -> Result<FileMetadata<T>, DispatchError> {
+		// TODO: Perform various checks of BSP total capacity, etc.
 		// TODO: Not relevant for PoC.
 
 		// Check that sender is a registered storage provider.
 		ensure!(<T as Config>::BspsRegistry::get_user(who.clone()).is_some(), Error::<T>::NotBsp);
 
+		// Check that it has bonded enough stake to be slashed if it fails to store the file.
+		ensure!(T::Reputation::has_min_stake(who), Error::<T>::InsufficientStake);
+
 		// Check that the storage request exists.
 		ensure!(
-			<StorageRequests<T>>::contains_key(&location),
+			<StorageRequests<T>>::contains_key(location),
 			Error::<T>::StorageRequestNotRegistered
 		);
 
+		// Check that the file is not being updated, as the BSP would have to confirm a version
+		// it was never sent.
+		ensure!(!<FileUpdates<T>>::contains_key(location), Error::<T>::FileUpdatePending);
+
 		// Get storage request metadata.
-		let mut file_metadata = expect_or_err!(
-			<StorageRequests<T>>::get(&location),
+		let file_metadata = expect_or_err!(
+			<StorageRequests<T>>::get(location),
 			"Storage request should exist",
 			Error::<T>::StorageRequestNotRegistered
 		);
 
 		// Check that BSP is not already registered for this storage request.
-		ensure!(!file_metadata.bsps.contains(&who), Error::<T>::BspAlreadyRegistered);
+		ensure!(!file_metadata.bsps.contains(who), Error::<T>::BspAlreadyRegistered);
 
 		// Check that the threshold value is high enough to qualify as BSP for the storage request.
-		let who_bytes = BlakeTwo256::hash(&who.encode()).0;
-		let threshold = calculate_xor(fingerprint.as_ref().try_into().unwrap(), &who_bytes);
-
-		let threshold = T::AssignmentThreshold::decode(&mut &threshold[..])
-			.map_err(|_| Error::<T>::FailedToDecodeThreshold)?;
+		let threshold = Self::bsp_distance(who, fingerprint)?;
 
 		let blocks_since_requested = <frame_system::Pallet<T>>::block_number()
 			.saturating_sub(file_metadata.requested_at)
 			.saturated_into::<u32>();
 
+		ensure!(
+			threshold <= Self::bsp_threshold(who, blocks_since_requested),
+			Error::<T>::ThresholdTooLow
+		);
+
+		Ok(file_metadata)
+	}
+
+	/// The first block at which `who` is eligible as BSP for the storage request of
+	/// `file_metadata`.
+	///
+	/// Returns `None` if the BSP never becomes eligible, e.g. because its reputation is zero.
+	pub fn first_eligible_block(
+		who: &T::AccountId,
+		file_metadata: &FileMetadata<T>,
+	) -> Option<BlockNumberFor<T>> {
+		let threshold = Self::bsp_distance(who, &file_metadata.fingerprint).ok()?;
+		let is_eligible = |blocks_since_requested: u32| {
+			threshold <= Self::bsp_threshold(who, blocks_since_requested)
+		};
+
+		if !is_eligible(u32::MAX) {
+			return None
+		}
+
+		// The threshold of a BSP only increases with time, so search for the first eligible block.
+		let (mut low, mut high) = (0u32, u32::MAX);
+		while low < high {
+			let middle = low + (high - low) / 2;
+			if is_eligible(middle) {
+				high = middle;
+			} else {
+				low = middle + 1;
+			}
+		}
+
+		Some(file_metadata.requested_at.saturating_add(low.into()))
+	}
+
+	/// The XOR distance between `fingerprint` and the hashed account of a BSP, which the BSP is
+	/// eligible for once its threshold reaches.
+	fn bsp_distance(
+		who: &T::AccountId,
+		fingerprint: &Fingerprint<T>,
+	) -> Result<T::AssignmentThreshold, DispatchError> {
+		let who_bytes = BlakeTwo256::hash(&who.encode()).0;
+
+		Ok(xor_distance(fingerprint.as_ref(), &who_bytes)
+			.ok_or(Error::<T>::FailedToDecodeThreshold)?)
+	}
+
+	/// The threshold of `who` for a storage request made `blocks_since_requested` blocks ago.
+	fn bsp_threshold(who: &T::AccountId, blocks_since_requested: u32) -> T::AssignmentThreshold {
 		// Rate multiplier is 10,000.
 		// This can probably be exposed as a configurable parameter.
 		let rate_increase = blocks_since_requested
 			.saturating_mul(10_000u32)
 			.saturated_into::<T::AssignmentThreshold>();
 
-		let min_threshold = rate_increase.saturating_add(T::MinBspsAssignmentThreshold::get());
+		Self::scale_by_reputation(
+			rate_increase.saturating_add(Self::assignment_threshold()),
+			T::Reputation::score(who),
+		)
+	}
 
-		ensure!(threshold <= min_threshold, Error::<T>::ThresholdTooLow);
+	/// Volunteer the BSP keys of the local keystore for the storage requests they are eligible
+	/// for at block `now`.
+	///
+	/// Nothing is submitted until the node running the BSPs sets their multiaddress under
+	/// [`crate::BSP_MULTIADDRESS_KEY`]. A key volunteers for at most one request per block, as
+	/// the nonce of its transactions is read from the chain. Storage requests without a sender in
+	/// [`crate::StorageRequestSenders`] are skipped, as the node could not fetch their file.
+	///
+	/// Each run checks at most [`crate::Config::MaxOffchainVolunteers`] storage requests,
+	/// resuming after the last one checked by the previous run, and holds a lock so that
+	/// overlapping runs do not volunteer twice.
+	pub fn offchain_volunteer_local_bsps(now: BlockNumberFor<T>) {
+		let Ok(Some(bsp_multiaddress)) =
+			StorageValueRef::persistent(crate::BSP_MULTIADDRESS_KEY).get::<MultiAddress<T>>()
+		else {
+			return
+		};
 
-		// Add BSP to storage request metadata.
-		file_metadata
-			.bsps
-			.try_push(who.clone())
-			.map_err(|_| Error::<T>::BspAlreadyRegistered)?;
-		<StorageRequests<T>>::set(&location, Some(file_metadata.clone()));
+		let mut lock = Self::offchain_lock();
+		let Ok(_guard) = lock.try_lock() else {
+			log::debug!(target: LOG_TARGET, "Offchain worker already running, skipping block {:?}", now);
+			return
+		};
 
-		// Check if storage request is in FilesMapping now that it has at least one BSP.
-		if !<FilesMapping<T>>::contains_key(&location) {
-			// Add storage request to FilesMapping.
-			<FilesMapping<T>>::insert(&location, file_metadata.clone());
+		let storage_requests = Self::offchain_next_storage_requests();
+
+		let keys = <T::AuthorityId as AppCrypto<T::Public, T::Signature>>::RuntimeAppPublic::all();
+		for key in keys {
+			let public: T::Public =
+				<T::AuthorityId as AppCrypto<T::Public, T::Signature>>::GenericPublic::from(key)
+					.into();
+			let who = public.clone().into_account();
+
+			// Only registered BSPs with the minimum stake can volunteer.
+			if <T as Config>::BspsRegistry::get_user(who.clone()).is_none() ||
+				!T::Reputation::has_min_stake(&who)
+			{
+				continue
+			}
+
+			// Find the first open storage request the BSP is eligible for.
+			let eligible_request = storage_requests.iter().find(|(location, file_metadata)| {
+				// The BSP fetches files from their sender, so it cannot serve requests without one,
+				// nor volunteer for files being updated.
+				if file_metadata.bsps.contains(&who) ||
+					!<StorageRequestSenders<T>>::contains_key(location) ||
+					<FileUpdates<T>>::contains_key(location)
+				{
+					return false
+				}
+
+				match Self::first_eligible_block(&who, file_metadata) {
+					Some(eligible_at) if eligible_at <= now => true,
+					Some(eligible_at) => {
+						log::debug!(
+							target: LOG_TARGET,
+							"BSP {:?} eligible for {:?} at block {:?}",
+							who,
+							location,
+							eligible_at
+						);
+						false
+					},
+					None => false,
+				}
+			});
+			let Some((location, file_metadata)) = eligible_request else { continue };
+
+			let results = Signer::<T, T::AuthorityId>::all_accounts()
+				.with_filter([public].into())
+				.send_signed_transaction(|_| pallet::Call::bsp_volunteer {
+					location: location.clone(),
+					fingerprint: file_metadata.fingerprint,
+					bsp_multiaddress: bsp_multiaddress.clone(),
+				});
+			for (_, result) in results {
+				match result {
+					Ok(()) => log::info!(
+						target: LOG_TARGET,
+						"BSP {:?} volunteered for {:?}",
+						who,
+						location
+					),
+					Err(()) => log::error!(
+						target: LOG_TARGET,
+						"BSP {:?} failed to volunteer for {:?}",
+						who,
+						location
+					),
+				}
+			}
 		}
+	}
 
-		// Check if maximum number of BSPs has been reached.
-		if file_metadata.bsps.len() == T::MaxBsps::get() as usize {
-			// Clear storage request from StorageRequests.
-			<StorageRequests<T>>::remove(&location);
+	/// The lock held by the offchain worker while it volunteers local BSPs.
+	///
+	/// It expires after a block or a few seconds, in case a run does not release it.
+	pub(crate) fn offchain_lock() -> StorageLock<'static, BlockAndTime<frame_system::Pallet<T>>> {
+		StorageLock::with_block_and_time_deadline(
+			OFFCHAIN_LOCK_KEY,
+			1,
+			Duration::from_millis(6_000),
+		)
+	}
+
+	/// The next [`crate::Config::MaxOffchainVolunteers`] storage requests for the offchain worker
+	/// to check, after the last one checked by its previous run.
+	///
+	/// Once the end of the storage requests is reached, the next run starts over from the first.
+	fn offchain_next_storage_requests() -> Vec<(FileLocation<T>, FileMetadata<T>)> {
+		let mut cursor = StorageValueRef::persistent(OFFCHAIN_CURSOR_KEY);
+		let max = T::MaxOffchainVolunteers::get() as usize;
+
+		let storage_requests: Vec<_> = match cursor.get::<Vec<u8>>() {
+			Ok(Some(last_key)) => <StorageRequests<T>>::iter_from(last_key).take(max).collect(),
+			_ => <StorageRequests<T>>::iter().take(max).collect(),
+		};
+
+		match storage_requests.last() {
+			Some((location, _)) if storage_requests.len() == max =>
+				cursor.set(&<StorageRequests<T>>::hashed_key_for(location)),
+			_ => cursor.clear(),
+		}
+
+		storage_requests
+	}
+
+	/// The threshold to be eligible as BSP for a storage request at the block it was requested.
+	///
+	/// Defaults to [`crate::Config::MinBspsAssignmentThreshold`] when no threshold was set.
+	pub fn assignment_threshold() -> T::AssignmentThreshold {
+		<CurrentAssignmentThreshold<T>>::get().unwrap_or_else(T::MinBspsAssignmentThreshold::get)
+	}
+
+	/// Scale `threshold` by the reputation `score` of a BSP, so that the less reliable a BSP has
+	/// been, the less likely it is to be eligible.
+	fn scale_by_reputation(
+		threshold: T::AssignmentThreshold,
+		score: Perbill,
+	) -> T::AssignmentThreshold {
+		// Thresholds can be as large as the type allows, so multiply without overflowing.
+		score.mul_floor(threshold)
+	}
+
+	/// Recompute the assignment threshold from the number of registered BSPs.
+	///
+	/// The threshold is scaled so that, on average, [`crate::Config::MaxBsps`] BSPs qualify
+	/// for a storage request as soon as it is made, but it never goes below
+	/// [`crate::Config::MinBspsAssignmentThreshold`].
+	pub fn update_assignment_threshold() {
+		let total_bsps = T::BspsRegistry::total_users()
+			.saturated_into::<u128>()
+			.max(1)
+			.saturated_into::<T::AssignmentThreshold>();
+
+		let threshold = (T::AssignmentThreshold::max_value() / total_bsps)
+			.saturating_mul(T::MaxBsps::get().into())
+			.max(T::MinBspsAssignmentThreshold::get());
+
+		<CurrentAssignmentThreshold<T>>::put(threshold);
+	}
+
+	/// Account for `size` more bytes stored by BSPs.
+	fn increase_used_bsps_storage(size: StorageCount<T>) {
+		<TotalUsedBspStorage<T>>::mutate(|total| {
+			*total = Some(total.unwrap_or_default().saturating_add(size))
+		});
+	}
+
+	/// Account for `size` fewer bytes stored by BSPs.
+	fn decrease_used_bsps_storage(size: StorageCount<T>) {
+		<TotalUsedBspStorage<T>>::mutate(|total| {
+			*total = Some(total.unwrap_or_default().saturating_sub(size))
+		});
+	}
+
+	/// Check the invariants of the storage: every stored file has at least one BSP, its BSPs are
+	/// mapped to it in [`BspsFiles`], and [`TotalUsedBspStorage`] is the size of every file times
+	/// the number of BSPs storing it.
+	#[cfg(any(feature = "try-runtime", test))]
+	pub fn do_try_state() -> Result<(), sp_runtime::TryRuntimeError> {
+		let mut total_used_storage = StorageCount::<T>::default();
+		for (location, file_metadata) in <FilesMapping<T>>::iter() {
+			ensure!(!file_metadata.bsps.is_empty(), "Stored files should have at least one BSP");
+			ensure!(
+				file_metadata
+					.bsps
+					.iter()
+					.all(|bsp| <BspsFiles<T>>::contains_key(bsp, &location)),
+				"BSPs of stored files should be mapped to them"
+			);
+
+			total_used_storage = total_used_storage.saturating_add(
+				file_metadata.size.saturating_mul((file_metadata.bsps.len() as u32).into()),
+			);
 		}
+		ensure!(
+			<TotalUsedBspStorage<T>>::get().unwrap_or_default() == total_used_storage,
+			"Storage used by BSPs should be the size of the files they store"
+		);
 
 		Ok(())
 	}
+
+	/// Reassign the files of BSPs that left, within `remaining_weight`.
+	///
+	/// Returns the weight consumed.
+	pub fn do_reassign_leaving_bsps_files(remaining_weight: Weight) -> Weight {
+		let db_weight = T::DbWeight::get();
+		let read_weight = db_weight.reads(1);
+		// Removing a BSP from a file can also delete or update the file.
+		let reassign_weight = db_weight
+			.reads_writes(6, 7)
+			.saturating_add(T::FileSystemHooks::weight().saturating_mul(3));
+		let mut used_weight = Weight::zero();
+
+		loop {
+			// Check that there is enough weight left to process one more file.
+			let next_weight = used_weight
+				.saturating_add(read_weight)
+				.saturating_add(read_weight)
+				.saturating_add(reassign_weight);
+			if next_weight.any_gt(remaining_weight) {
+				break;
+			}
+
+			// Get the next leaving BSP, if any.
+			used_weight.saturating_accrue(read_weight);
+			let Some(bsp) = <LeavingBsps<T>>::iter_keys().next() else { break };
+
+			// Reassign its next file, or mark it as done if it has no files left.
+			used_weight.saturating_accrue(read_weight);
+			match <BspsFiles<T>>::iter_key_prefix(&bsp).next() {
+				Some(location) => {
+					Self::reassign_file(&bsp, location);
+					used_weight.saturating_accrue(reassign_weight);
+				},
+				None => {
+					<LeavingBsps<T>>::remove(&bsp);
+					used_weight.saturating_accrue(db_weight.writes(1));
+				},
+			}
+		}
+
+		used_weight
+	}
+
+	/// Remove `bsp` from the file at `location`, reopening its storage request if needed.
+	fn reassign_file(bsp: &T::AccountId, location: FileLocation<T>) {
+		<BspsFiles<T>>::remove(bsp, &location);
+
+		let Some(mut file_metadata) = <FilesMapping<T>>::get(&location) else { return };
+		if file_metadata.bsps.contains(bsp) {
+			// The BSP no longer stores the file, whoever replaces it.
+			Self::decrease_used_bsps_storage(file_metadata.size);
+		}
+		file_metadata.bsps.retain(|b| b != bsp);
+
+		// Remove the file from FilesMapping if no BSP is storing it anymore.
+		if file_metadata.bsps.is_empty() {
+			<FilesMapping<T>>::remove(&location);
+		} else {
+			<FilesMapping<T>>::insert(&location, file_metadata.clone());
+		}
+
+		T::FileSystemHooks::on_bsp_removed(bsp, &location);
+		if file_metadata.bsps.is_empty() {
+			T::FileSystemHooks::on_file_deleted(&location, &file_metadata);
+		}
+
+		// If the storage request is still open, just drop the BSP from it.
+		if let Some(mut storage_request) = <StorageRequests<T>>::get(&location) {
+			storage_request.bsps.retain(|b| b != bsp);
+			<StorageRequests<T>>::insert(&location, storage_request);
+		} else {
+			// Otherwise, reopen it for new volunteers.
+			file_metadata.requested_at = <frame_system::Pallet<T>>::block_number();
+			<StorageRequests<T>>::insert(&location, file_metadata.clone());
+
+			// Emit event.
+			Self::deposit_event(Event::StorageRequestReopened {
+				location: location.clone(),
+				fingerprint: file_metadata.fingerprint,
+				leaving_bsp: bsp.clone(),
+			});
+		}
+
+		// The BSP no longer has to confirm a pending update, which might now be complete.
+		if let Some(mut file_update) = <FileUpdates<T>>::get(&location) {
+			file_update.confirmed_bsps.retain(|b| b != bsp);
+			<FileUpdates<T>>::insert(&location, file_update);
+			Self::try_complete_file_update(&location);
+		}
+	}
 }
 
-fn calculate_xor(fingerprint: &[u8; 32], bsp: &[u8; 32]) -> Vec<u8> {
-	let mut xor_result = Vec::with_capacity(32);
-	for i in 0..32 {
-		xor_result.push(fingerprint[i] ^ bsp[i]);
+impl<T> OnIdentityChange<T::AccountId> for Pallet<T>
+where
+	T: pallet::Config,
+{
+	fn on_user_registered(who: &T::AccountId) {
+		// A BSP registering again keeps its files.
+		<LeavingBsps<T>>::remove(who);
+
+		Self::update_assignment_threshold();
 	}
 
-	xor_result
+	fn on_user_removed(who: &T::AccountId) {
+		// Its files are reassigned on idle, as there can be too many to do it here.
+		<LeavingBsps<T>>::insert(who, ());
+
+		Self::update_assignment_threshold();
+	}
+
+	fn weight() -> Weight {
+		// `LeavingBsps`, plus the number of BSPs and `CurrentAssignmentThreshold`.
+		T::DbWeight::get().reads_writes(1, 2)
+	}
+}
+
+/// XOR `fingerprint` with `bsp`, repeating `bsp` if the fingerprint is longer.
+fn calculate_xor(fingerprint: &[u8], bsp: &[u8]) -> Vec<u8> {
+	fingerprint.iter().zip(bsp.iter().cycle()).map(|(f, b)| f ^ b).collect()
+}
+
+/// The XOR distance between `fingerprint` and `bsp`, decoded as a threshold from its leading
+/// bytes.
+///
+/// Returns `None` if the fingerprint is narrower than the threshold.
+pub(crate) fn xor_distance<Threshold: Decode>(fingerprint: &[u8], bsp: &[u8]) -> Option<Threshold> {
+	Threshold::decode(&mut &calculate_xor(fingerprint, bsp)[..]).ok()
 }
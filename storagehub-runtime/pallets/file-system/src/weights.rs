@@ -1,29 +1,15 @@
-
-//! Autogenerated weights for pallet_template
+//! Estimated weights for pallet_file_system
 //!
-//! THIS FILE WAS AUTO-GENERATED USING THE SUBSTRATE BENCHMARK CLI VERSION 4.0.0-dev
-//! DATE: 2023-04-06, STEPS: `50`, REPEAT: `20`, LOW RANGE: `[]`, HIGH RANGE: `[]`
-//! WORST CASE MAP SIZE: `1000000`
-//! HOSTNAME: `Alexs-MacBook-Pro-2.local`, CPU: `<UNKNOWN>`
-//! EXECUTION: Some(Wasm), WASM-EXECUTION: Compiled, CHAIN: Some("dev"), DB CACHE: 1024
-
-// Executed Command:
-// ../../target/release/node-template
-// benchmark
-// pallet
-// --chain
-// dev
-// --pallet
-// pallet_template
-// --extrinsic
-// *
-// --steps=50
-// --repeat=20
-// --wasm-execution=compiled
-// --output
-// pallets/template/src/weights.rs
-// --template
-// ../../.maintain/frame-weight-template.hbs
+//! THESE ARE NOT BENCHMARKED WEIGHTS. Each call is weighed from a count of its storage accesses
+//! plus a flat execution cost, which is only good enough for development chains. Replace this file
+//! with the benchmark CLI output before using the pallet on a production chain:
+//!
+//! ./target/release/node-template benchmark pallet --chain dev --pallet pallet_file_system
+//! --extrinsic '*' --steps 50 --repeat 20 --template ./.maintain/frame-weight-template.hbs
+//! --output pallets/file-system/src/weights.rs
+//!
+//! The generated file provides `SubstrateWeight`, which the runtime should then use instead of
+//! `EstimatedWeight`.
 
 #![cfg_attr(rustfmt, rustfmt_skip)]
 #![allow(unused_parens)]
@@ -32,59 +18,401 @@
 use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight}};
 use core::marker::PhantomData;
 
-/// Weight functions needed for pallet_template.
+/// Weight functions needed for pallet_file_system.
 pub trait WeightInfo {
-	fn do_something() -> Weight;
-	fn cause_error() -> Weight;
+	fn request_storage() -> Weight;
+	fn request_storage_batch(n: u32, ) -> Weight;
+	fn bsp_volunteer() -> Weight;
+	fn update_file() -> Weight;
+	fn bsp_confirm_update() -> Weight;
+	fn set_file_visibility() -> Weight;
+	fn grant_read_access() -> Weight;
+	fn revoke_read_access() -> Weight;
+	fn register_peer_id() -> Weight;
+	fn unregister_peer_id() -> Weight;
+	fn set_user_quota() -> Weight;
+	fn msp_accept_storage_request() -> Weight;
+	fn msp_reject_storage_request() -> Weight;
+	fn expire_file_update(n: u32, ) -> Weight;
+	fn report_failed_challenge() -> Weight;
+	fn request_retrieval() -> Weight;
+	fn bsp_confirm_delivery() -> Weight;
+	fn refund_retrieval() -> Weight;
+	fn confirm_retrieval() -> Weight;
+	fn dispute_retrieval() -> Weight;
 }
 
-/// Weights for pallet_template using the Substrate node and recommended hardware.
-pub struct SubstrateWeight<T>(PhantomData<T>);
-impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
-	/// Storage: TemplateModule Something (r:0 w:1)
-	/// Proof: TemplateModule Something (max_values: Some(1), max_size: Some(4), added: 499, mode: MaxEncodedLen)
-	fn do_something() -> Weight {
-		// Proof Size summary in bytes:
-		//  Measured:  `0`
-		//  Estimated: `0`
-		// Minimum execution time: 8_000_000 picoseconds.
-		Weight::from_parts(9_000_000, 0)
+/// Estimated weights for pallet_file_system, derived from storage access counts. Not benchmarked.
+pub struct EstimatedWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for EstimatedWeight<T> {
+	/// Storage: MspIdentity Users (r:1 w:0)
+	/// Storage: FileSystem UserStorageUsage (r:1 w:1)
+	/// Storage: FileSystem UserQuotas (r:1 w:0)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	/// Storage: FileSystem StorageRequestSenders (r:0 w:1)
+	/// Storage: FileSystem FilesMapping (r:1 w:0)
+	fn request_storage() -> Weight {
+		Weight::from_parts(32_000_000, 12_600)
+			.saturating_add(T::DbWeight::get().reads(5_u64))
+			.saturating_add(T::DbWeight::get().writes(3_u64))
+	}
+	/// Storage: MspIdentity Users (r:1 w:0 per request)
+	/// Storage: FileSystem UserStorageUsage (r:1 w:1 per request)
+	/// Storage: FileSystem UserQuotas (r:1 w:0 per request)
+	/// Storage: FileSystem StorageRequests (r:1 w:1 per request)
+	/// Storage: FileSystem StorageRequestSenders (r:0 w:1 per request)
+	/// Storage: FileSystem FilesMapping (r:1 w:0 per request)
+	/// The range of component `n` is `[1, 32]`.
+	fn request_storage_batch(n: u32, ) -> Weight {
+		Weight::from_parts(10_000_000, 0)
+			.saturating_add(Weight::from_parts(32_000_000, 12_600).saturating_mul(n.into()))
+			.saturating_add(T::DbWeight::get().reads((5_u64).saturating_mul(n.into())))
+			.saturating_add(T::DbWeight::get().writes((3_u64).saturating_mul(n.into())))
+	}
+	/// Storage: Identity Users (r:1 w:0)
+	/// Storage: Reputation Stakes (r:1 w:0)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	/// Storage: FileSystem FileUpdates (r:1 w:0)
+	/// Storage: FileSystem StorageRequestSenders (r:0 w:1)
+	/// Storage: FileSystem CurrentAssignmentThreshold (r:1 w:0)
+	/// Storage: Reputation Reputations (r:1 w:0)
+	/// Storage: FileSystem FilesMapping (r:0 w:1)
+	/// Storage: FileSystem BspsFiles (r:0 w:1)
+	/// Storage: FileSystem TotalUsedBspStorage (r:1 w:1)
+	fn bsp_volunteer() -> Weight {
+		Weight::from_parts(42_000_000, 14_500)
+			.saturating_add(T::DbWeight::get().reads(7_u64))
+			.saturating_add(T::DbWeight::get().writes(5_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:0)
+	/// Storage: FileSystem UserStorageUsage (r:1 w:0)
+	/// Storage: FileSystem UserQuotas (r:1 w:0)
+	/// Storage: FileSystem FileUpdates (r:0 w:1)
+	fn update_file() -> Weight {
+		Weight::from_parts(25_000_000, 7_100)
+			.saturating_add(T::DbWeight::get().reads(3_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FileSystem FileUpdates (r:1 w:1)
+	/// Storage: FileSystem FilesMapping (r:1 w:1)
+	/// Storage: FileSystem FileVersions (r:1 w:1)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	/// Storage: FileSystem UserStorageUsage (r:1 w:1)
+	/// Storage: Reputation Reputations (r:1 w:1)
+	/// Storage: FileSystem TotalUsedBspStorage (r:1 w:1)
+	fn bsp_confirm_update() -> Weight {
+		Weight::from_parts(50_000_000, 17_100)
+			.saturating_add(T::DbWeight::get().reads(7_u64))
+			.saturating_add(T::DbWeight::get().writes(7_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:1)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	fn set_file_visibility() -> Weight {
+		Weight::from_parts(25_000_000, 8_000)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
+			.saturating_add(T::DbWeight::get().writes(2_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:0)
+	/// Storage: FileSystem StorageRequests (r:1 w:0)
+	/// Storage: FileSystem FileReaders (r:0 w:1)
+	fn grant_read_access() -> Weight {
+		Weight::from_parts(20_000_000, 8_000)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:0)
+	/// Storage: FileSystem StorageRequests (r:1 w:0)
+	/// Storage: FileSystem FileReaders (r:0 w:1)
+	fn revoke_read_access() -> Weight {
+		Weight::from_parts(20_000_000, 8_000)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
-	/// Storage: TemplateModule Something (r:1 w:1)
-	/// Proof: TemplateModule Something (max_values: Some(1), max_size: Some(4), added: 499, mode: MaxEncodedLen)
-	fn cause_error() -> Weight {
-		// Proof Size summary in bytes:
-		//  Measured:  `32`
-		//  Estimated: `1489`
-		// Minimum execution time: 6_000_000 picoseconds.
-		Weight::from_parts(6_000_000, 1489)
+	/// Storage: System BlockHash (r:1 w:0)
+	/// Storage: FileSystem PeerAccounts (r:1 w:1)
+	fn register_peer_id() -> Weight {
+		Weight::from_parts(65_000_000, 5_300)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FileSystem PeerAccounts (r:1 w:1)
+	fn unregister_peer_id() -> Weight {
+		Weight::from_parts(15_000_000, 2_700)
 			.saturating_add(T::DbWeight::get().reads(1_u64))
 			.saturating_add(T::DbWeight::get().writes(1_u64))
 	}
+	/// Storage: FileSystem UserQuotas (r:0 w:1)
+	fn set_user_quota() -> Weight {
+		Weight::from_parts(12_000_000, 0)
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:1)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	fn msp_accept_storage_request() -> Weight {
+		Weight::from_parts(25_000_000, 8_000)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
+			.saturating_add(T::DbWeight::get().writes(2_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:1)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	fn msp_reject_storage_request() -> Weight {
+		Weight::from_parts(25_000_000, 8_000)
+			.saturating_add(T::DbWeight::get().reads(2_u64))
+			.saturating_add(T::DbWeight::get().writes(2_u64))
+	}
+	/// Storage: FileSystem FileUpdates (r:1 w:1)
+	/// Storage: FileSystem FilesMapping (r:1 w:1)
+	/// Storage: Reputation Reputations (r:1 w:1 per BSP)
+	/// Storage: Reputation Stakes (r:1 w:1 per BSP)
+	/// Storage: System Account (r:1 w:1 per BSP)
+	/// Storage: FileSystem BspsFiles (r:0 w:1 per BSP)
+	/// Storage: FileSystem StorageRequests (r:1 w:1 per BSP)
+	/// Storage: FileSystem FileVersions (r:1 w:1)
+	/// Storage: FileSystem UserStorageUsage (r:1 w:1)
+	/// Storage: FileSystem TotalUsedBspStorage (r:1 w:1)
+	/// The range of component `n` is `[1, 5]`.
+	fn expire_file_update(n: u32, ) -> Weight {
+		Weight::from_parts(45_000_000, 14_600)
+			.saturating_add(Weight::from_parts(40_000_000, 10_400).saturating_mul(n.into()))
+			.saturating_add(T::DbWeight::get().reads(5_u64))
+			.saturating_add(T::DbWeight::get().reads((6_u64).saturating_mul(n.into())))
+			.saturating_add(T::DbWeight::get().writes(5_u64))
+			.saturating_add(T::DbWeight::get().writes((6_u64).saturating_mul(n.into())))
+	}
+	/// Storage: FileSystem BspsFiles (r:1 w:0)
+	/// Storage: Reputation Reputations (r:1 w:1)
+	/// Storage: Reputation Stakes (r:1 w:1)
+	/// Storage: System Account (r:1 w:1)
+	fn report_failed_challenge() -> Weight {
+		Weight::from_parts(40_000_000, 11_000)
+			.saturating_add(T::DbWeight::get().reads(4_u64))
+			.saturating_add(T::DbWeight::get().writes(3_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:0)
+	/// Storage: FileSystem FileReaders (r:1 w:0)
+	/// Storage: FileSystem RetrievalRequests (r:1 w:1)
+	/// Storage: Balances Reserves (r:1 w:1)
+	/// Storage: System Account (r:1 w:1)
+	fn request_retrieval() -> Weight {
+		Weight::from_parts(40_000_000, 13_500)
+			.saturating_add(T::DbWeight::get().reads(5_u64))
+			.saturating_add(T::DbWeight::get().writes(3_u64))
+	}
+	/// Storage: FileSystem RetrievalRequests (r:1 w:1)
+	fn bsp_confirm_delivery() -> Weight {
+		Weight::from_parts(20_000_000, 3_600)
+			.saturating_add(T::DbWeight::get().reads(1_u64))
+			.saturating_add(T::DbWeight::get().writes(1_u64))
+	}
+	/// Storage: FileSystem RetrievalRequests (r:1 w:1)
+	/// Storage: Balances Reserves (r:1 w:1)
+	/// Storage: System Account (r:1 w:1)
+	fn refund_retrieval() -> Weight {
+		Weight::from_parts(30_000_000, 8_600)
+			.saturating_add(T::DbWeight::get().reads(3_u64))
+			.saturating_add(T::DbWeight::get().writes(3_u64))
+	}
+	/// Storage: FileSystem RetrievalRequests (r:1 w:1)
+	/// Storage: Balances Reserves (r:1 w:1)
+	/// Storage: System Account (r:2 w:2)
+	fn confirm_retrieval() -> Weight {
+		Weight::from_parts(40_000_000, 11_200)
+			.saturating_add(T::DbWeight::get().reads(4_u64))
+			.saturating_add(T::DbWeight::get().writes(4_u64))
+	}
+	/// Storage: FileSystem RetrievalRequests (r:1 w:1)
+	/// Storage: Balances Reserves (r:1 w:1)
+	/// Storage: System Account (r:1 w:1)
+	fn dispute_retrieval() -> Weight {
+		Weight::from_parts(30_000_000, 8_600)
+			.saturating_add(T::DbWeight::get().reads(3_u64))
+			.saturating_add(T::DbWeight::get().writes(3_u64))
+	}
 }
 
 // For backwards compatibility and tests
 impl WeightInfo for () {
-	/// Storage: TemplateModule Something (r:0 w:1)
-	/// Proof: TemplateModule Something (max_values: Some(1), max_size: Some(4), added: 499, mode: MaxEncodedLen)
-	fn do_something() -> Weight {
-		// Proof Size summary in bytes:
-		//  Measured:  `0`
-		//  Estimated: `0`
-		// Minimum execution time: 8_000_000 picoseconds.
-		Weight::from_parts(9_000_000, 0)
+	/// Storage: MspIdentity Users (r:1 w:0)
+	/// Storage: FileSystem UserStorageUsage (r:1 w:1)
+	/// Storage: FileSystem UserQuotas (r:1 w:0)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	/// Storage: FileSystem StorageRequestSenders (r:0 w:1)
+	/// Storage: FileSystem FilesMapping (r:1 w:0)
+	fn request_storage() -> Weight {
+		Weight::from_parts(32_000_000, 12_600)
+			.saturating_add(RocksDbWeight::get().reads(5_u64))
+			.saturating_add(RocksDbWeight::get().writes(3_u64))
+	}
+	/// Storage: MspIdentity Users (r:1 w:0 per request)
+	/// Storage: FileSystem UserStorageUsage (r:1 w:1 per request)
+	/// Storage: FileSystem UserQuotas (r:1 w:0 per request)
+	/// Storage: FileSystem StorageRequests (r:1 w:1 per request)
+	/// Storage: FileSystem StorageRequestSenders (r:0 w:1 per request)
+	/// Storage: FileSystem FilesMapping (r:1 w:0 per request)
+	/// The range of component `n` is `[1, 32]`.
+	fn request_storage_batch(n: u32, ) -> Weight {
+		Weight::from_parts(10_000_000, 0)
+			.saturating_add(Weight::from_parts(32_000_000, 12_600).saturating_mul(n.into()))
+			.saturating_add(RocksDbWeight::get().reads((5_u64).saturating_mul(n.into())))
+			.saturating_add(RocksDbWeight::get().writes((3_u64).saturating_mul(n.into())))
+	}
+	/// Storage: Identity Users (r:1 w:0)
+	/// Storage: Reputation Stakes (r:1 w:0)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	/// Storage: FileSystem FileUpdates (r:1 w:0)
+	/// Storage: FileSystem StorageRequestSenders (r:0 w:1)
+	/// Storage: FileSystem CurrentAssignmentThreshold (r:1 w:0)
+	/// Storage: Reputation Reputations (r:1 w:0)
+	/// Storage: FileSystem FilesMapping (r:0 w:1)
+	/// Storage: FileSystem BspsFiles (r:0 w:1)
+	/// Storage: FileSystem TotalUsedBspStorage (r:1 w:1)
+	fn bsp_volunteer() -> Weight {
+		Weight::from_parts(42_000_000, 14_500)
+			.saturating_add(RocksDbWeight::get().reads(7_u64))
+			.saturating_add(RocksDbWeight::get().writes(5_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:0)
+	/// Storage: FileSystem UserStorageUsage (r:1 w:0)
+	/// Storage: FileSystem UserQuotas (r:1 w:0)
+	/// Storage: FileSystem FileUpdates (r:0 w:1)
+	fn update_file() -> Weight {
+		Weight::from_parts(25_000_000, 7_100)
+			.saturating_add(RocksDbWeight::get().reads(3_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FileSystem FileUpdates (r:1 w:1)
+	/// Storage: FileSystem FilesMapping (r:1 w:1)
+	/// Storage: FileSystem FileVersions (r:1 w:1)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	/// Storage: FileSystem UserStorageUsage (r:1 w:1)
+	/// Storage: Reputation Reputations (r:1 w:1)
+	/// Storage: FileSystem TotalUsedBspStorage (r:1 w:1)
+	fn bsp_confirm_update() -> Weight {
+		Weight::from_parts(50_000_000, 17_100)
+			.saturating_add(RocksDbWeight::get().reads(7_u64))
+			.saturating_add(RocksDbWeight::get().writes(7_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:1)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	fn set_file_visibility() -> Weight {
+		Weight::from_parts(25_000_000, 8_000)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
+			.saturating_add(RocksDbWeight::get().writes(2_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:0)
+	/// Storage: FileSystem StorageRequests (r:1 w:0)
+	/// Storage: FileSystem FileReaders (r:0 w:1)
+	fn grant_read_access() -> Weight {
+		Weight::from_parts(20_000_000, 8_000)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:0)
+	/// Storage: FileSystem StorageRequests (r:1 w:0)
+	/// Storage: FileSystem FileReaders (r:0 w:1)
+	fn revoke_read_access() -> Weight {
+		Weight::from_parts(20_000_000, 8_000)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: System BlockHash (r:1 w:0)
+	/// Storage: FileSystem PeerAccounts (r:1 w:1)
+	fn register_peer_id() -> Weight {
+		Weight::from_parts(65_000_000, 5_300)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
-	/// Storage: TemplateModule Something (r:1 w:1)
-	/// Proof: TemplateModule Something (max_values: Some(1), max_size: Some(4), added: 499, mode: MaxEncodedLen)
-	fn cause_error() -> Weight {
-		// Proof Size summary in bytes:
-		//  Measured:  `32`
-		//  Estimated: `1489`
-		// Minimum execution time: 6_000_000 picoseconds.
-		Weight::from_parts(6_000_000, 1489)
+	/// Storage: FileSystem PeerAccounts (r:1 w:1)
+	fn unregister_peer_id() -> Weight {
+		Weight::from_parts(15_000_000, 2_700)
 			.saturating_add(RocksDbWeight::get().reads(1_u64))
 			.saturating_add(RocksDbWeight::get().writes(1_u64))
 	}
+	/// Storage: FileSystem UserQuotas (r:0 w:1)
+	fn set_user_quota() -> Weight {
+		Weight::from_parts(12_000_000, 0)
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:1)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	fn msp_accept_storage_request() -> Weight {
+		Weight::from_parts(25_000_000, 8_000)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
+			.saturating_add(RocksDbWeight::get().writes(2_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:1)
+	/// Storage: FileSystem StorageRequests (r:1 w:1)
+	fn msp_reject_storage_request() -> Weight {
+		Weight::from_parts(25_000_000, 8_000)
+			.saturating_add(RocksDbWeight::get().reads(2_u64))
+			.saturating_add(RocksDbWeight::get().writes(2_u64))
+	}
+	/// Storage: FileSystem FileUpdates (r:1 w:1)
+	/// Storage: FileSystem FilesMapping (r:1 w:1)
+	/// Storage: Reputation Reputations (r:1 w:1 per BSP)
+	/// Storage: Reputation Stakes (r:1 w:1 per BSP)
+	/// Storage: System Account (r:1 w:1 per BSP)
+	/// Storage: FileSystem BspsFiles (r:0 w:1 per BSP)
+	/// Storage: FileSystem StorageRequests (r:1 w:1 per BSP)
+	/// Storage: FileSystem FileVersions (r:1 w:1)
+	/// Storage: FileSystem UserStorageUsage (r:1 w:1)
+	/// Storage: FileSystem TotalUsedBspStorage (r:1 w:1)
+	/// The range of component `n` is `[1, 5]`.
+	fn expire_file_update(n: u32, ) -> Weight {
+		Weight::from_parts(45_000_000, 14_600)
+			.saturating_add(Weight::from_parts(40_000_000, 10_400).saturating_mul(n.into()))
+			.saturating_add(RocksDbWeight::get().reads(5_u64))
+			.saturating_add(RocksDbWeight::get().reads((6_u64).saturating_mul(n.into())))
+			.saturating_add(RocksDbWeight::get().writes(5_u64))
+			.saturating_add(RocksDbWeight::get().writes((6_u64).saturating_mul(n.into())))
+	}
+	/// Storage: FileSystem BspsFiles (r:1 w:0)
+	/// Storage: Reputation Reputations (r:1 w:1)
+	/// Storage: Reputation Stakes (r:1 w:1)
+	/// Storage: System Account (r:1 w:1)
+	fn report_failed_challenge() -> Weight {
+		Weight::from_parts(40_000_000, 11_000)
+			.saturating_add(RocksDbWeight::get().reads(4_u64))
+			.saturating_add(RocksDbWeight::get().writes(3_u64))
+	}
+	/// Storage: FileSystem FilesMapping (r:1 w:0)
+	/// Storage: FileSystem FileReaders (r:1 w:0)
+	/// Storage: FileSystem RetrievalRequests (r:1 w:1)
+	/// Storage: Balances Reserves (r:1 w:1)
+	/// Storage: System Account (r:1 w:1)
+	fn request_retrieval() -> Weight {
+		Weight::from_parts(40_000_000, 13_500)
+			.saturating_add(RocksDbWeight::get().reads(5_u64))
+			.saturating_add(RocksDbWeight::get().writes(3_u64))
+	}
+	/// Storage: FileSystem RetrievalRequests (r:1 w:1)
+	fn bsp_confirm_delivery() -> Weight {
+		Weight::from_parts(20_000_000, 3_600)
+			.saturating_add(RocksDbWeight::get().reads(1_u64))
+			.saturating_add(RocksDbWeight::get().writes(1_u64))
+	}
+	/// Storage: FileSystem RetrievalRequests (r:1 w:1)
+	/// Storage: Balances Reserves (r:1 w:1)
+	/// Storage: System Account (r:1 w:1)
+	fn refund_retrieval() -> Weight {
+		Weight::from_parts(30_000_000, 8_600)
+			.saturating_add(RocksDbWeight::get().reads(3_u64))
+			.saturating_add(RocksDbWeight::get().writes(3_u64))
+	}
+	/// Storage: FileSystem RetrievalRequests (r:1 w:1)
+	/// Storage: Balances Reserves (r:1 w:1)
+	/// Storage: System Account (r:2 w:2)
+	fn confirm_retrieval() -> Weight {
+		Weight::from_parts(40_000_000, 11_200)
+			.saturating_add(RocksDbWeight::get().reads(4_u64))
+			.saturating_add(RocksDbWeight::get().writes(4_u64))
+	}
+	/// Storage: FileSystem RetrievalRequests (r:1 w:1)
+	/// Storage: Balances Reserves (r:1 w:1)
+	/// Storage: System Account (r:1 w:1)
+	fn dispute_retrieval() -> Weight {
+		Weight::from_parts(30_000_000, 8_600)
+			.saturating_add(RocksDbWeight::get().reads(3_u64))
+			.saturating_add(RocksDbWeight::get().writes(3_u64))
+	}
 }
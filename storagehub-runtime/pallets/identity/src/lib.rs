@@ -2,16 +2,19 @@
 
 use core::{cmp::Ord, fmt::Debug};
 use frame_support::{
-	codec::FullCodec,
+	codec::{Decode, Encode, FullCodec},
 	ensure,
 	pallet_prelude::{
 		DispatchError, DispatchResult, MaxEncodedLen, MaybeSerializeDeserialize, Member, Parameter,
 	},
-	scale_info,
+	scale_info::{self, TypeInfo},
 	sp_runtime::{
 		traits::{AtLeast32BitUnsigned, Get},
-		ArithmeticError,
+		ArithmeticError, RuntimeDebug,
 	},
+	sp_std::vec::Vec,
+	traits::{Currency, ReservableCurrency},
+	weights::Weight,
 };
 pub use pallet::*;
 
@@ -24,40 +27,123 @@ mod tests;
 #[cfg(feature = "runtime-benchmarks")]
 mod benchmarking;
 
+pub mod migrations;
+
+/// The balance type of the currency candidates reserve their application deposit in.
+pub type BalanceOf<T, I = ()> =
+	<<T as Config<I>>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+/// A pending application to be registered as a user.
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug, PartialEq, Eq, Clone)]
+pub struct Application<BlockNumber, Balance> {
+	/// The block at which the candidate applied.
+	pub applied_at: BlockNumber,
+	/// The deposit reserved from the candidate while the application is pending.
+	pub deposit: Balance,
+}
+
 #[frame_support::pallet]
 pub mod pallet {
-	use frame_support::{pallet_prelude::*, sp_runtime::ArithmeticError};
+	use super::{Application, BalanceOf, OnIdentityChange};
+	use frame_support::{
+		pallet_prelude::*,
+		sp_std::vec::Vec,
+		traits::{Imbalance, ReservableCurrency},
+	};
 	use frame_system::pallet_prelude::*;
 
+	/// The current storage version.
+	pub const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
 	#[pallet::pallet]
-	pub struct Pallet<T>(_);
+	#[pallet::storage_version(STORAGE_VERSION)]
+	pub struct Pallet<T, I = ()>(_);
 
 	/// Configure the pallet by specifying the parameters and types on which it depends.
 	#[pallet::config]
-	pub trait Config: frame_system::Config {
+	pub trait Config<I: 'static = ()>: frame_system::Config {
 		/// Because this pallet emits events, it depends on the runtime's definition of an event.
-		type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+		type RuntimeEvent: From<Event<Self, I>>
+			+ IsType<<Self as frame_system::Config>::RuntimeEvent>;
 
 		/// The maximum number of registered users.
 		#[pallet::constant]
 		type MaxUsers: Get<u128>;
+
+		/// The origin allowed to register users, i.e. to approve applications.
+		type RegistrationOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+
+		/// The origin allowed to remove users.
+		type RemovalOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+
+		/// Handler notified whenever a user is registered or removed.
+		type OnIdentityChange: OnIdentityChange<Self::AccountId>;
+
+		/// The currency application deposits are reserved in.
+		type Currency: ReservableCurrency<Self::AccountId>;
+
+		/// The deposit reserved from candidates when they apply, returned when their application
+		/// is approved or withdrawn, and slashed when it is rejected.
+		#[pallet::constant]
+		type ApplicationDeposit: Get<BalanceOf<Self, I>>;
 	}
 
 	/// Mapping of registered users. Being included in the map is equivalent to being registered.
 	#[pallet::storage]
 	#[pallet::getter(fn users)]
-	pub type Users<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, ()>;
+	pub type Users<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, ()>;
 
 	/// The number of registered users.
 	#[pallet::storage]
 	#[pallet::getter(fn count)]
-	pub type Count<T: Config> = StorageValue<_, u128, ValueQuery>;
+	pub type Count<T: Config<I>, I: 'static = ()> = StorageValue<_, u128, ValueQuery>;
+
+	/// Mapping of candidates that applied to be registered, to their pending application.
+	#[pallet::storage]
+	#[pallet::getter(fn applications)]
+	pub type Applications<T: Config<I>, I: 'static = ()> = StorageMap<
+		_,
+		Blake2_128Concat,
+		T::AccountId,
+		Application<BlockNumberFor<T>, BalanceOf<T, I>>,
+	>;
+
+	#[pallet::genesis_config]
+	#[derive(frame_support::DefaultNoBound)]
+	pub struct GenesisConfig<T: Config<I>, I: 'static = ()> {
+		/// Users that are registered from genesis.
+		pub users: Vec<T::AccountId>,
+		#[serde(skip)]
+		pub phantom: PhantomData<I>,
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config<I>, I: 'static> BuildGenesisConfig for GenesisConfig<T, I> {
+		fn build(&self) {
+			assert!(
+				self.users.len() as u128 <= T::MaxUsers::get(),
+				"Genesis users exceed the maximum number of users"
+			);
+
+			for user in &self.users {
+				assert!(!Users::<T, I>::contains_key(user), "Genesis users must be unique");
+				Users::<T, I>::insert(user, ());
+			}
+			Count::<T, I>::put(self.users.len() as u128);
+
+			// Notify dependent pallets, once every user is accounted for.
+			for user in &self.users {
+				T::OnIdentityChange::on_user_registered(user);
+			}
+		}
+	}
 
 	// Pallets use events to inform users when important changes are made.
 	// https://docs.substrate.io/main-docs/build/events-errors/
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
-	pub enum Event<T: Config> {
+	pub enum Event<T: Config<I>, I: 'static = ()> {
 		/// An event that is emitted when a new user is registered.
 		///
 		/// It includes the account id of the user.
@@ -73,11 +159,36 @@ pub mod pallet {
 		/// # Arguments
 		/// 	- `user`: The account id of the user.
 		RemovedUser { user: T::AccountId },
+
+		/// An event that is emitted when a candidate applies to be registered.
+		///
+		/// It includes the account id of the candidate.
+		///
+		/// # Arguments
+		/// 	- `candidate`: The account id of the candidate.
+		NewApplication { candidate: T::AccountId },
+
+		/// An event that is emitted when a candidate withdraws its application.
+		///
+		/// It includes the account id of the candidate.
+		///
+		/// # Arguments
+		/// 	- `candidate`: The account id of the candidate.
+		ApplicationWithdrawn { candidate: T::AccountId },
+
+		/// An event that is emitted when an application is rejected.
+		///
+		/// It includes the account id of the candidate and the deposit slashed from it.
+		///
+		/// # Arguments
+		/// 	- `candidate`: The account id of the candidate.
+		/// 	- `slashed`: The part of the application deposit that was slashed.
+		ApplicationRejected { candidate: T::AccountId, slashed: BalanceOf<T, I> },
 	}
 
 	// Errors inform users that something went wrong.
 	#[pallet::error]
-	pub enum Error<T> {
+	pub enum Error<T, I = ()> {
 		/// User is not registered.
 		NotRegistered,
 
@@ -86,80 +197,128 @@ pub mod pallet {
 
 		/// The maximum number of users has been reached.
 		MaximumOfUsersReached,
+
+		/// Candidate has already applied to be registered.
+		AlreadyApplied,
+
+		/// Candidate has not applied to be registered.
+		NotApplied,
+	}
+
+	#[pallet::hooks]
+	impl<T: Config<I>, I: 'static> Hooks<BlockNumberFor<T>> for Pallet<T, I> {
+		#[cfg(feature = "try-runtime")]
+		fn try_state(_n: BlockNumberFor<T>) -> Result<(), sp_runtime::TryRuntimeError> {
+			Self::do_try_state()
+		}
 	}
 
 	// Dispatchable functions allows users to interact with the pallet and invoke state changes.
 	// These functions materialize as "extrinsics", which are often compared to transactions.
 	// Dispatchable functions must be annotated with a weight and must return a DispatchResult.
 	#[pallet::call]
-	impl<T: Config> Pallet<T> {
+	impl<T: Config<I>, I: 'static> Pallet<T, I> {
 		/// Register a new user.
+		///
+		/// Can only be called by [`Config::RegistrationOrigin`]. If the user had applied to be
+		/// registered, its application is approved and its deposit returned.
 		#[pallet::call_index(0)]
-		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1).ref_time())]
+		#[pallet::weight(
+			10_000 +
+				T::DbWeight::get().reads_writes(3,4).ref_time() +
+				T::OnIdentityChange::weight().ref_time()
+		)]
 		pub fn register_user(origin: OriginFor<T>, who: T::AccountId) -> DispatchResult {
 			// Check origin.
-			ensure_root(origin)?;
+			T::RegistrationOrigin::ensure_origin(origin)?;
 
-			// Check that user is not already registered.
-			ensure!(!Users::<T>::contains_key(&who), Error::<T>::AlreadyRegistered);
+			// Register user.
+			Self::do_register_user(who)
+		}
 
-			// Increment user count and return error if maximum is reached.
-			let mut count = Count::<T>::get();
-			ensure!(count < T::MaxUsers::get(), Error::<T>::MaximumOfUsersReached);
-			count = match count.checked_add(1) {
-				Some(count) => count,
-				None => {
-					#[cfg(test)]
-					unreachable!("Overflow cannot happen after just checking that count < T::MaxUsers::get()");
+		/// Remove a user.
+		///
+		/// Can only be called by [`Config::RemovalOrigin`].
+		#[pallet::call_index(1)]
+		#[pallet::weight(
+			10_000 +
+				T::DbWeight::get().reads_writes(1,1).ref_time() +
+				T::OnIdentityChange::weight().ref_time()
+		)]
+		pub fn remove_user(origin: OriginFor<T>, who: T::AccountId) -> DispatchResult {
+			// Check origin.
+			T::RemovalOrigin::ensure_origin(origin)?;
 
-					#[allow(unreachable_code)]
-					{
-						Err(DispatchError::Arithmetic(ArithmeticError::Overflow))?
-					}
-				},
-			};
-			Count::<T>::put(count);
+			// Remove user.
+			Self::do_remove_user(who)
+		}
 
-			// Register user.
-			Users::<T>::insert(&who, ());
+		/// Apply to be registered as a user, reserving [`Config::ApplicationDeposit`].
+		///
+		/// The application stays pending until [`Config::RegistrationOrigin`] approves it by
+		/// registering the candidate or rejects it, or until the candidate withdraws it.
+		#[pallet::call_index(2)]
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3,2).ref_time())]
+		pub fn apply(origin: OriginFor<T>) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Check that candidate is not registered nor has already applied.
+			ensure!(!Users::<T, I>::contains_key(&who), Error::<T, I>::AlreadyRegistered);
+			ensure!(!Applications::<T, I>::contains_key(&who), Error::<T, I>::AlreadyApplied);
+
+			// Reserve the deposit and register application.
+			let deposit = T::ApplicationDeposit::get();
+			T::Currency::reserve(&who, deposit)?;
+			Applications::<T, I>::insert(
+				&who,
+				Application { applied_at: <frame_system::Pallet<T>>::block_number(), deposit },
+			);
 
 			// Emit event.
-			Self::deposit_event(Event::NewUser { user: who });
+			Self::deposit_event(Event::NewApplication { candidate: who });
 
 			Ok(())
 		}
 
-		/// Remove a user.
-		#[pallet::call_index(1)]
-		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1).ref_time())]
-		pub fn remove_user(origin: OriginFor<T>, who: T::AccountId) -> DispatchResult {
-			// Check origin.
-			ensure_root(origin)?;
+		/// Withdraw a pending application, returning its deposit.
+		#[pallet::call_index(3)]
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,2).ref_time())]
+		pub fn withdraw_application(origin: OriginFor<T>) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
 
-			// Check that user is registered.
-			ensure!(Users::<T>::contains_key(&who), Error::<T>::NotRegistered);
+			// Remove application, checking that candidate has applied.
+			let application = Applications::<T, I>::take(&who).ok_or(Error::<T, I>::NotApplied)?;
 
-			// Decrement user count.
-			let mut count = Count::<T>::get();
-			count = match count.checked_sub(1) {
-				Some(count) => count,
-				None => {
-					#[cfg(test)]
-					unreachable!("Underflow cannot happen as it would mean that the user was not accounted for.");
+			// Return the deposit.
+			T::Currency::unreserve(&who, application.deposit);
 
-					#[allow(unreachable_code)]
-					{
-						Err(DispatchError::Arithmetic(ArithmeticError::Underflow))?
-					}
-				},
-			};
-			Count::<T>::put(count);
+			// Emit event.
+			Self::deposit_event(Event::ApplicationWithdrawn { candidate: who });
 
-			// Remove user.
-			Users::<T>::remove(&who);
+			Ok(())
+		}
+
+		/// Reject a pending application, slashing its deposit.
+		///
+		/// Can only be called by [`Config::RegistrationOrigin`].
+		#[pallet::call_index(4)]
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,2).ref_time())]
+		pub fn reject_application(origin: OriginFor<T>, candidate: T::AccountId) -> DispatchResult {
+			// Check origin.
+			T::RegistrationOrigin::ensure_origin(origin)?;
+
+			// Remove application, checking that candidate has applied.
+			let application =
+				Applications::<T, I>::take(&candidate).ok_or(Error::<T, I>::NotApplied)?;
+
+			// Slash the deposit, which is burnt.
+			let (imbalance, _) = T::Currency::slash_reserved(&candidate, application.deposit);
+			let slashed = imbalance.peek();
 
 			// Emit event.
-			Self::deposit_event(Event::RemovedUser { user: who });
+			Self::deposit_event(Event::ApplicationRejected { candidate, slashed });
 
 			Ok(())
 		}
@@ -196,9 +355,43 @@ pub trait IdentityInterface {
 
 	/// Register a new user.
 	fn register_user(who: Self::AccountId) -> DispatchResult;
+
+	/// Remove a registered user.
+	fn remove_user(who: Self::AccountId) -> DispatchResult;
+
+	/// Get up to `limit` registered users, starting after `start_after` if given.
+	///
+	/// Users are returned in storage order, so the last user returned can be used as
+	/// `start_after` to get the next page.
+	fn get_users(start_after: Option<Self::AccountId>, limit: u32) -> Vec<Self::AccountId>;
+}
+
+/// A handler for changes in the registry of users.
+///
+/// It allows dependent pallets to react to users being registered or removed.
+pub trait OnIdentityChange<AccountId> {
+	/// Called after a user is registered.
+	fn on_user_registered(who: &AccountId);
+
+	/// Called after a user is removed.
+	fn on_user_removed(who: &AccountId);
+
+	/// The weight of the heaviest of [`Self::on_user_registered`] and [`Self::on_user_removed`],
+	/// accounted for in the calls that register and remove users.
+	fn weight() -> Weight;
+}
+
+impl<AccountId> OnIdentityChange<AccountId> for () {
+	fn on_user_registered(_who: &AccountId) {}
+
+	fn on_user_removed(_who: &AccountId) {}
+
+	fn weight() -> Weight {
+		Weight::zero()
+	}
 }
 
-impl<T: Config> IdentityInterface for Pallet<T> {
+impl<T: Config<I>, I: 'static> IdentityInterface for Pallet<T, I> {
 	type AccountId = T::AccountId;
 
 	type User = ();
@@ -206,7 +399,7 @@ impl<T: Config> IdentityInterface for Pallet<T> {
 	type UserCount = u128;
 
 	fn get_user(who: Self::AccountId) -> Option<Self::User> {
-		if Users::<T>::contains_key(&who) {
+		if Users::<T, I>::contains_key(&who) {
 			Some(())
 		} else {
 			None
@@ -214,16 +407,36 @@ impl<T: Config> IdentityInterface for Pallet<T> {
 	}
 
 	fn total_users() -> Self::UserCount {
-		Count::<T>::get()
+		Count::<T, I>::get()
+	}
+
+	fn register_user(who: Self::AccountId) -> DispatchResult {
+		Self::do_register_user(who)
+	}
+
+	fn remove_user(who: Self::AccountId) -> DispatchResult {
+		Self::do_remove_user(who)
 	}
 
-	fn register_user(who: Self::AccountId) -> frame_support::pallet_prelude::DispatchResult {
+	fn get_users(start_after: Option<Self::AccountId>, limit: u32) -> Vec<Self::AccountId> {
+		let iter = match start_after {
+			Some(user) => Users::<T, I>::iter_keys_from(Users::<T, I>::hashed_key_for(user)),
+			None => Users::<T, I>::iter_keys(),
+		};
+
+		iter.take(limit as usize).collect()
+	}
+}
+
+impl<T: Config<I>, I: 'static> Pallet<T, I> {
+	/// Register a new user, clearing its application if any.
+	pub fn do_register_user(who: T::AccountId) -> DispatchResult {
 		// Check that user is not already registered.
-		ensure!(!Users::<T>::contains_key(&who), Error::<T>::AlreadyRegistered);
+		ensure!(!Users::<T, I>::contains_key(&who), Error::<T, I>::AlreadyRegistered);
 
 		// Increment user count and return error if maximum is reached.
-		let mut count = Count::<T>::get();
-		ensure!(count < T::MaxUsers::get(), Error::<T>::MaximumOfUsersReached);
+		let mut count = Count::<T, I>::get();
+		ensure!(count < T::MaxUsers::get(), Error::<T, I>::MaximumOfUsersReached);
 		count =
 			match count.checked_add(1) {
 				Some(count) => count,
@@ -237,10 +450,64 @@ impl<T: Config> IdentityInterface for Pallet<T> {
 					}
 				},
 			};
-		Count::<T>::put(count);
+		Count::<T, I>::put(count);
+
+		// Register user and clear its application, if any, returning its deposit.
+		Users::<T, I>::insert(&who, ());
+		if let Some(application) = Applications::<T, I>::take(&who) {
+			T::Currency::unreserve(&who, application.deposit);
+		}
+
+		// Notify dependent pallets.
+		T::OnIdentityChange::on_user_registered(&who);
+
+		// Emit event.
+		Self::deposit_event(Event::NewUser { user: who });
+
+		Ok(())
+	}
+
+	/// Remove a registered user.
+	pub fn do_remove_user(who: T::AccountId) -> DispatchResult {
+		// Check that user is registered.
+		ensure!(Users::<T, I>::contains_key(&who), Error::<T, I>::NotRegistered);
+
+		// Decrement user count.
+		let mut count = Count::<T, I>::get();
+		count =
+			match count.checked_sub(1) {
+				Some(count) => count,
+				None => {
+					#[cfg(test)]
+					unreachable!("Underflow cannot happen as it would mean that the user was not accounted for.");
+
+					#[allow(unreachable_code)]
+					{
+						Err(DispatchError::Arithmetic(ArithmeticError::Underflow))?
+					}
+				},
+			};
+		Count::<T, I>::put(count);
+
+		// Remove user.
+		Users::<T, I>::remove(&who);
+
+		// Notify dependent pallets.
+		T::OnIdentityChange::on_user_removed(&who);
+
+		// Emit event.
+		Self::deposit_event(Event::RemovedUser { user: who });
+
+		Ok(())
+	}
 
-		// Register user.
-		Users::<T>::insert(&who, ());
+	/// Check the invariants of the storage: [`Count`] is the number of [`Users`].
+	#[cfg(any(feature = "try-runtime", test))]
+	pub fn do_try_state() -> Result<(), sp_runtime::TryRuntimeError> {
+		ensure!(
+			Count::<T, I>::get() == Users::<T, I>::iter_keys().count() as u128,
+			"Count should be the number of registered users"
+		);
 
 		Ok(())
 	}
@@ -0,0 +1,54 @@
+//! Storage migrations of the Identity Pallet.
+//!
+//! Each version of the storage has its own module, with the migration from the previous version.
+//! The runtime runs them in its `Executive` on upgrade, and they do nothing if the storage is not
+//! at the version they migrate from.
+
+use frame_support::{
+	pallet_prelude::*,
+	traits::{GetStorageVersion, OnRuntimeUpgrade},
+};
+#[cfg(feature = "try-runtime")]
+use frame_support::{sp_runtime::TryRuntimeError, sp_std::vec::Vec};
+
+use crate::{Config, Pallet};
+
+/// Version 1, the first versioned storage.
+pub mod v1 {
+	use super::*;
+
+	/// Set the storage version to 1.
+	///
+	/// Chains launched before the storage was versioned are at version 0, with the same layout,
+	/// so there is nothing to translate.
+	pub struct MigrateToV1<T, I = ()>(PhantomData<(T, I)>);
+
+	impl<T: Config<I>, I: 'static> OnRuntimeUpgrade for MigrateToV1<T, I> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T, I>::on_chain_storage_version() != 0 {
+				return T::DbWeight::get().reads(1)
+			}
+
+			StorageVersion::new(1).put::<Pallet<T, I>>();
+
+			T::DbWeight::get().reads_writes(1, 1)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<Vec<u8>, TryRuntimeError> {
+			ensure!(
+				Pallet::<T, I>::on_chain_storage_version() <= 1,
+				"Storage version should be at most 1"
+			);
+
+			Ok(Vec::new())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(_state: Vec<u8>) -> Result<(), TryRuntimeError> {
+			ensure!(Pallet::<T, I>::on_chain_storage_version() == 1, "Storage version should be 1");
+
+			Pallet::<T, I>::do_try_state()
+		}
+	}
+}
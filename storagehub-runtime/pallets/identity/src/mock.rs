@@ -1,5 +1,9 @@
 use crate as pallet_identity;
-use frame_support::traits::{ConstU128, ConstU16, ConstU32, ConstU64};
+use frame_support::{
+	ord_parameter_types,
+	traits::{ConstU128, ConstU16, ConstU32, ConstU64, EitherOfDiverse},
+};
+use frame_system::{EnsureRoot, EnsureSignedBy};
 use sp_core::H256;
 use sp_runtime::{
 	traits::{BlakeTwo256, IdentityLookup},
@@ -10,6 +14,12 @@ type Block = frame_system::mocking::MockBlock<Test>;
 type Balance = u128;
 
 pub const MAX_USERS: u128 = 10;
+pub const APPLICATION_DEPOSIT: Balance = 100;
+pub const INITIAL_BALANCE: Balance = 1_000;
+
+ord_parameter_types! {
+	pub const Registrar: u64 = 100;
+}
 
 // Configure a mock runtime to test the pallet.
 frame_support::construct_runtime!(
@@ -66,9 +76,21 @@ impl pallet_balances::Config for Test {
 impl pallet_identity::Config for Test {
 	type RuntimeEvent = RuntimeEvent;
 	type MaxUsers = ConstU128<MAX_USERS>;
+	type RegistrationOrigin = EitherOfDiverse<EnsureRoot<u64>, EnsureSignedBy<Registrar, u64>>;
+	type RemovalOrigin = EnsureRoot<u64>;
+	type OnIdentityChange = ();
+	type Currency = Balances;
+	type ApplicationDeposit = ConstU128<APPLICATION_DEPOSIT>;
 }
 
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
-	frame_system::GenesisConfig::<Test>::default().build_storage().unwrap().into()
+	let mut storage = frame_system::GenesisConfig::<Test>::default().build_storage().unwrap();
+	pallet_balances::GenesisConfig::<Test> {
+		balances: (1..=5).map(|who| (who, INITIAL_BALANCE)).collect(),
+	}
+	.assimilate_storage(&mut storage)
+	.unwrap();
+
+	storage.into()
 }
@@ -1,6 +1,12 @@
-use crate::{mock::*, Error, Event};
-use frame_support::{assert_noop, assert_ok};
-use sp_runtime::DispatchError;
+use crate::{
+	migrations::v1::MigrateToV1, mock::*, Application, Count, Error, Event, IdentityInterface,
+	STORAGE_VERSION,
+};
+use frame_support::{
+	assert_noop, assert_ok,
+	traits::{GetStorageVersion, OnRuntimeUpgrade, StorageVersion},
+};
+use sp_runtime::{BuildStorage, DispatchError};
 
 #[test]
 fn register_user_success() {
@@ -33,6 +39,43 @@ fn register_user_not_root() {
 	});
 }
 
+#[test]
+fn register_user_by_registration_origin() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Register user.
+		assert_ok!(Identity::register_user(RuntimeOrigin::signed(Registrar::get()), 1));
+
+		// Check that event was emitted.
+		System::assert_last_event(Event::NewUser { user: 1 }.into());
+
+		// Check storage.
+		assert_eq!(Identity::users(1), Some(()));
+	});
+}
+
+#[test]
+fn register_user_approves_application() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Apply and register user.
+		assert_ok!(Identity::apply(RuntimeOrigin::signed(1)));
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 1));
+
+		// Check storage.
+		assert_eq!(Identity::users(1), Some(()));
+		assert_eq!(Identity::applications(1), None);
+
+		// Check that the deposit was returned.
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(Balances::free_balance(1), INITIAL_BALANCE);
+	});
+}
+
 #[test]
 fn register_user_max_reached() {
 	new_test_ext().execute_with(|| {
@@ -93,3 +136,265 @@ fn remove_user_not_registered() {
 		assert_noop!(Identity::remove_user(RuntimeOrigin::root(), 1), Error::<Test>::NotRegistered);
 	});
 }
+
+#[test]
+fn remove_user_not_removal_origin() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Register user.
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 1));
+
+		// Registration origin cannot remove users.
+		assert_noop!(
+			Identity::remove_user(RuntimeOrigin::signed(Registrar::get()), 1),
+			DispatchError::BadOrigin
+		);
+	});
+}
+
+#[test]
+fn apply_success() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Apply.
+		assert_ok!(Identity::apply(RuntimeOrigin::signed(1)));
+
+		// Check that event was emitted.
+		System::assert_last_event(Event::NewApplication { candidate: 1 }.into());
+
+		// Check storage.
+		assert_eq!(
+			Identity::applications(1),
+			Some(Application { applied_at: 1, deposit: APPLICATION_DEPOSIT })
+		);
+		assert_eq!(Identity::users(1), None);
+
+		// Check that the deposit was reserved.
+		assert_eq!(Balances::reserved_balance(1), APPLICATION_DEPOSIT);
+	});
+}
+
+#[test]
+fn apply_insufficient_balance() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Apply without the balance to reserve the deposit.
+		assert_noop!(
+			Identity::apply(RuntimeOrigin::signed(6)),
+			pallet_balances::Error::<Test>::InsufficientBalance
+		);
+	});
+}
+
+#[test]
+fn apply_already_applied() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Apply twice.
+		assert_ok!(Identity::apply(RuntimeOrigin::signed(1)));
+		assert_noop!(Identity::apply(RuntimeOrigin::signed(1)), Error::<Test>::AlreadyApplied);
+	});
+}
+
+#[test]
+fn apply_already_registered() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Register user and apply.
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 1));
+		assert_noop!(Identity::apply(RuntimeOrigin::signed(1)), Error::<Test>::AlreadyRegistered);
+	});
+}
+
+#[test]
+fn withdraw_application_success() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Apply and withdraw.
+		assert_ok!(Identity::apply(RuntimeOrigin::signed(1)));
+		assert_ok!(Identity::withdraw_application(RuntimeOrigin::signed(1)));
+
+		// Check that event was emitted.
+		System::assert_last_event(Event::ApplicationWithdrawn { candidate: 1 }.into());
+
+		// Check storage.
+		assert_eq!(Identity::applications(1), None);
+
+		// Check that the deposit was returned.
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(Balances::free_balance(1), INITIAL_BALANCE);
+	});
+}
+
+#[test]
+fn withdraw_application_not_applied() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Withdraw without applying.
+		assert_noop!(
+			Identity::withdraw_application(RuntimeOrigin::signed(1)),
+			Error::<Test>::NotApplied
+		);
+	});
+}
+
+#[test]
+fn reject_application_success() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Apply and reject the application.
+		assert_ok!(Identity::apply(RuntimeOrigin::signed(1)));
+		assert_ok!(Identity::reject_application(RuntimeOrigin::root(), 1));
+
+		// Check that event was emitted.
+		System::assert_last_event(
+			Event::ApplicationRejected { candidate: 1, slashed: APPLICATION_DEPOSIT }.into(),
+		);
+
+		// Check storage.
+		assert_eq!(Identity::applications(1), None);
+		assert_eq!(Identity::users(1), None);
+
+		// Check that the deposit was slashed.
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(Balances::free_balance(1), INITIAL_BALANCE - APPLICATION_DEPOSIT);
+	});
+}
+
+#[test]
+fn reject_application_not_registration_origin() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Apply and reject the application as the candidate.
+		assert_ok!(Identity::apply(RuntimeOrigin::signed(1)));
+		assert_noop!(
+			Identity::reject_application(RuntimeOrigin::signed(1), 1),
+			DispatchError::BadOrigin
+		);
+	});
+}
+
+#[test]
+fn reject_application_not_applied() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Reject without applying.
+		assert_noop!(
+			Identity::reject_application(RuntimeOrigin::root(), 1),
+			Error::<Test>::NotApplied
+		);
+	});
+}
+
+#[test]
+fn genesis_config_registers_users() {
+	let storage = RuntimeGenesisConfig {
+		identity: IdentityConfig { users: vec![1, 2, 3], ..Default::default() },
+		..Default::default()
+	}
+	.build_storage()
+	.unwrap();
+
+	sp_io::TestExternalities::from(storage).execute_with(|| {
+		// Check storage.
+		assert_eq!(Identity::users(1), Some(()));
+		assert_eq!(Identity::users(2), Some(()));
+		assert_eq!(Identity::users(3), Some(()));
+		assert_eq!(Identity::users(4), None);
+		assert_eq!(Identity::count(), 3);
+	});
+}
+
+#[test]
+fn users_are_paginated() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Register users through the interface.
+		for user in 1..=5 {
+			assert_ok!(<Identity as IdentityInterface>::register_user(user));
+		}
+
+		// Get users in pages of two.
+		let first_page = <Identity as IdentityInterface>::get_users(None, 2);
+		let second_page = <Identity as IdentityInterface>::get_users(first_page.last().cloned(), 2);
+		let third_page = <Identity as IdentityInterface>::get_users(second_page.last().cloned(), 2);
+		assert_eq!(first_page.len(), 2);
+		assert_eq!(second_page.len(), 2);
+		assert_eq!(third_page.len(), 1);
+
+		// Check that all users were returned exactly once.
+		let mut users = [first_page, second_page, third_page].concat();
+		users.sort();
+		assert_eq!(users, vec![1, 2, 3, 4, 5]);
+	});
+}
+
+#[test]
+fn remove_user_through_interface() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 1));
+		assert_ok!(<Identity as IdentityInterface>::remove_user(1));
+
+		// Check that the user is removed and the event is deposited.
+		assert_eq!(Identity::total_users(), 0);
+		assert_noop!(<Identity as IdentityInterface>::remove_user(1), Error::<Test>::NotRegistered);
+		System::assert_last_event(Event::RemovedUser { user: 1 }.into());
+	});
+}
+
+#[test]
+fn try_state_checks_user_count() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 1));
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 2));
+		assert_ok!(Identity::remove_user(RuntimeOrigin::root(), 1));
+		assert_ok!(Identity::do_try_state());
+
+		// The count has to match the registered users.
+		Count::<Test>::put(2);
+		assert!(Identity::do_try_state().is_err());
+	});
+}
+
+#[test]
+fn migration_to_v1_sets_storage_version() {
+	new_test_ext().execute_with(|| {
+		// Chains launched before the storage was versioned are at version 0.
+		StorageVersion::new(0).put::<Identity>();
+		assert_ok!(Identity::register_user(RuntimeOrigin::root(), 1));
+
+		// The migration sets the version, keeping the users.
+		MigrateToV1::<Test>::on_runtime_upgrade();
+		assert_eq!(Identity::on_chain_storage_version(), STORAGE_VERSION);
+		assert_eq!(Identity::users(1), Some(()));
+
+		// Running the migration again does nothing.
+		MigrateToV1::<Test>::on_runtime_upgrade();
+		assert_eq!(Identity::on_chain_storage_version(), STORAGE_VERSION);
+	});
+}
@@ -0,0 +1,435 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Reputation of Storage Providers.
+//!
+//! Providers start with [`Config::InitialScore`], which goes up every time they do what they
+//! committed to and down every time they fail to. Providers can bond part of their balance as
+//! stake, a fraction of which is slashed after [`Config::MaxFailures`] failures in a row.
+//!
+//! The stake is reserved under [`Config::ReserveId`], apart from the balance other pallets
+//! reserve. Unbonded stake stays reserved, and can still be slashed, for
+//! [`Config::UnbondingPeriod`] blocks before it can be withdrawn with
+//! [`Pallet::withdraw_unbonded`].
+//!
+//! Other pallets report successes and failures, and read the scores, through
+//! [`ReputationInterface`].
+
+use codec::{Decode, Encode, MaxEncodedLen};
+use frame_support::{
+	ensure,
+	pallet_prelude::DispatchResult,
+	sp_runtime::{
+		traits::{Saturating, Zero},
+		Perbill, RuntimeDebug,
+	},
+	traits::{Currency, Get, Imbalance, NamedReservableCurrency, OnUnbalanced},
+	BoundedVec,
+};
+use frame_system::pallet_prelude::BlockNumberFor;
+pub use pallet::*;
+use scale_info::TypeInfo;
+
+#[cfg(test)]
+mod mock;
+
+#[cfg(test)]
+mod tests;
+
+/// The balance type of the currency providers bond as stake.
+pub type BalanceOf<T> =
+	<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+/// The identifier the stake of providers is reserved under.
+pub type ReserveIdentifierOf<T> = <<T as Config>::Currency as NamedReservableCurrency<
+	<T as frame_system::Config>::AccountId,
+>>::ReserveIdentifier;
+
+/// The stake a provider is unbonding, oldest first.
+pub type UnbondingChunks<T> =
+	BoundedVec<UnbondingChunk<BalanceOf<T>, BlockNumberFor<T>>, <T as Config>::MaxUnbondingChunks>;
+
+/// The imbalance created when slashing the stake of a provider.
+pub type NegativeImbalanceOf<T> = <<T as Config>::Currency as Currency<
+	<T as frame_system::Config>::AccountId,
+>>::NegativeImbalance;
+
+/// The reputation of a provider.
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug, PartialEq, Eq, Clone, Copy)]
+pub struct ProviderReputation {
+	/// How reliable the provider has been, from not at all to fully.
+	pub score: Perbill,
+	/// The number of failures since the last success or slash.
+	pub failures: u32,
+}
+
+/// Part of the stake of a provider that is being unbonded.
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug, PartialEq, Eq, Clone, Copy)]
+pub struct UnbondingChunk<Balance, BlockNumber> {
+	/// The amount being unbonded, which can still be slashed.
+	pub amount: Balance,
+	/// The block from which it can be withdrawn.
+	pub unlocks_at: BlockNumber,
+}
+
+/// The ways a provider can fail.
+#[derive(Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug, PartialEq, Eq, Clone, Copy)]
+pub enum Failure {
+	/// The provider did not confirm something it had to before its deadline.
+	MissedDeadline,
+	/// The provider could not prove that it stores a file it was challenged for.
+	FailedChallenge,
+}
+
+#[frame_support::pallet]
+pub mod pallet {
+	use super::*;
+	use frame_support::pallet_prelude::*;
+	use frame_system::pallet_prelude::*;
+	use scale_info::prelude::vec::Vec;
+
+	#[pallet::pallet]
+	pub struct Pallet<T>(_);
+
+	/// Configure the pallet by specifying the parameters and types on which it depends.
+	#[pallet::config]
+	pub trait Config: frame_system::Config {
+		/// Because this pallet emits events, it depends on the runtime's definition of an event.
+		type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+
+		/// The currency providers bond as stake, reserving it under [`Config::ReserveId`].
+		type Currency: NamedReservableCurrency<Self::AccountId>;
+
+		/// The identifier the stake is reserved under, so that slashing it never touches the
+		/// balance reserved by other pallets.
+		#[pallet::constant]
+		type ReserveId: Get<ReserveIdentifierOf<Self>>;
+
+		/// The stake a provider must have bonded to take on new commitments.
+		#[pallet::constant]
+		type MinStake: Get<BalanceOf<Self>>;
+
+		/// The number of blocks unbonded stake stays reserved, and can be slashed, before it can
+		/// be withdrawn.
+		#[pallet::constant]
+		type UnbondingPeriod: Get<BlockNumberFor<Self>>;
+
+		/// The maximum number of chunks a provider can be unbonding at once.
+		#[pallet::constant]
+		type MaxUnbondingChunks: Get<u32>;
+
+		/// Handler for the stake slashed from providers. Dropping it burns it.
+		type OnSlash: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+		/// The score of providers that have not succeeded nor failed yet.
+		#[pallet::constant]
+		type InitialScore: Get<Perbill>;
+
+		/// How much the score of a provider goes up each time it succeeds.
+		#[pallet::constant]
+		type SuccessReward: Get<Perbill>;
+
+		/// How much the score of a provider goes down each time it misses a deadline.
+		#[pallet::constant]
+		type MissedDeadlinePenalty: Get<Perbill>;
+
+		/// How much the score of a provider goes down each time it fails a challenge.
+		#[pallet::constant]
+		type FailedChallengePenalty: Get<Perbill>;
+
+		/// The number of failures in a row after which the stake of a provider is slashed.
+		#[pallet::constant]
+		type MaxFailures: Get<u32>;
+
+		/// The fraction of its stake a provider loses each time it is slashed.
+		#[pallet::constant]
+		type SlashFraction: Get<Perbill>;
+	}
+
+	/// Reputation of the providers that have succeeded or failed at least once.
+	#[pallet::storage]
+	pub type Reputations<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, ProviderReputation>;
+
+	/// Balance bonded by each provider, which is reserved and can be slashed.
+	#[pallet::storage]
+	#[pallet::getter(fn stake)]
+	pub type Stakes<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, BalanceOf<T>, ValueQuery>;
+
+	/// Stake unbonded by each provider, oldest first, which stays reserved until it unlocks.
+	#[pallet::storage]
+	#[pallet::getter(fn unbonding)]
+	pub type Unbonding<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, UnbondingChunks<T>, ValueQuery>;
+
+	#[pallet::genesis_config]
+	#[derive(frame_support::DefaultNoBound)]
+	pub struct GenesisConfig<T: Config> {
+		/// Providers that bond stake from genesis, with the amount they bond.
+		pub stakes: Vec<(T::AccountId, BalanceOf<T>)>,
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> BuildGenesisConfig for GenesisConfig<T> {
+		fn build(&self) {
+			for (who, amount) in &self.stakes {
+				Pallet::<T>::do_bond(who, *amount)
+					.expect("Genesis stakes must be covered by the balance of their provider");
+			}
+		}
+	}
+
+	// Pallets use events to inform users when important changes are made.
+	// https://docs.substrate.io/main-docs/build/events-errors/
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// A provider bonded part of its balance as stake.
+		Bonded { who: T::AccountId, amount: BalanceOf<T> },
+
+		/// A provider unbonded part of its stake, which can be withdrawn from `unlocks_at`.
+		Unbonded { who: T::AccountId, amount: BalanceOf<T>, unlocks_at: BlockNumberFor<T> },
+
+		/// A provider withdrew the stake it unbonded, unreserving it.
+		Withdrawn { who: T::AccountId, amount: BalanceOf<T> },
+
+		/// A provider did what it committed to, raising its score.
+		Rewarded { who: T::AccountId, score: Perbill },
+
+		/// A provider failed, lowering its score.
+		Penalized { who: T::AccountId, failure: Failure, score: Perbill },
+
+		/// A provider failed too many times in a row, and part of its stake was slashed.
+		Slashed { who: T::AccountId, amount: BalanceOf<T> },
+	}
+
+	// Errors inform users that something went wrong.
+	#[pallet::error]
+	pub enum Error<T> {
+		/// Trying to unbond more than the bonded stake.
+		InsufficientStake,
+		/// Trying to unbond while already unbonding [`Config::MaxUnbondingChunks`] chunks.
+		TooManyUnbondingChunks,
+		/// Trying to withdraw while no unbonded stake is unlocked yet.
+		NoUnlockedStake,
+	}
+
+	// Dispatchable functions allows users to interact with the pallet and invoke state changes.
+	// These functions materialize as "extrinsics", which are often compared to transactions.
+	// Dispatchable functions must be annotated with a weight and must return a DispatchResult.
+	#[pallet::call]
+	impl<T: Config> Pallet<T> {
+		/// Bond `amount` of the sender's balance as stake, reserving it.
+		#[pallet::call_index(0)]
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,2).ref_time())]
+		pub fn bond(origin: OriginFor<T>, amount: BalanceOf<T>) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Reserve the amount and add it to the stake.
+			Self::do_bond(&who, amount)?;
+
+			// Emit event.
+			Self::deposit_event(Event::Bonded { who, amount });
+
+			Ok(())
+		}
+
+		/// Unbond `amount` of the sender's stake.
+		///
+		/// The amount stays reserved, and can still be slashed, until it is withdrawn with
+		/// [`Pallet::withdraw_unbonded`] after [`Config::UnbondingPeriod`] blocks.
+		#[pallet::call_index(1)]
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,2).ref_time())]
+		pub fn unbond(origin: OriginFor<T>, amount: BalanceOf<T>) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Check that the sender has bonded enough.
+			let stake = Stakes::<T>::get(&who);
+			ensure!(stake >= amount, Error::<T>::InsufficientStake);
+
+			// Move the amount from the stake to a new unbonding chunk.
+			let unlocks_at =
+				frame_system::Pallet::<T>::block_number().saturating_add(T::UnbondingPeriod::get());
+			Unbonding::<T>::try_mutate(&who, |chunks| {
+				chunks.try_push(UnbondingChunk { amount, unlocks_at })
+			})
+			.map_err(|_| Error::<T>::TooManyUnbondingChunks)?;
+			Stakes::<T>::insert(&who, stake.saturating_sub(amount));
+
+			// Emit event.
+			Self::deposit_event(Event::Unbonded { who, amount, unlocks_at });
+
+			Ok(())
+		}
+
+		/// Withdraw the stake the sender unbonded that is unlocked, unreserving it.
+		#[pallet::call_index(2)]
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,2).ref_time())]
+		pub fn withdraw_unbonded(origin: OriginFor<T>) -> DispatchResult {
+			// Check that the extrinsic was signed and get the signer.
+			let who = ensure_signed(origin)?;
+
+			// Take the unlocked chunks out of the unbonding ones.
+			let now = frame_system::Pallet::<T>::block_number();
+			let mut chunks = Unbonding::<T>::get(&who);
+			let mut amount = BalanceOf::<T>::zero();
+			chunks.retain(|chunk| {
+				let unlocked = chunk.unlocks_at <= now;
+				if unlocked {
+					amount.saturating_accrue(chunk.amount);
+				}
+				!unlocked
+			});
+			ensure!(!amount.is_zero(), Error::<T>::NoUnlockedStake);
+
+			// Unreserve them.
+			Self::set_unbonding(&who, chunks);
+			T::Currency::unreserve_named(&T::ReserveId::get(), &who, amount);
+
+			// Emit event.
+			Self::deposit_event(Event::Withdrawn { who, amount });
+
+			Ok(())
+		}
+	}
+}
+
+/// A reputation trait that provides a way to score providers for how reliable they are.
+pub trait ReputationInterface {
+	/// The type which can be used to identify accounts.
+	type AccountId;
+
+	/// The score of a provider, from not reliable at all to fully reliable.
+	fn score(who: &Self::AccountId) -> Perbill;
+
+	/// Whether a provider has bonded at least the minimum stake, as required to take on new
+	/// commitments.
+	fn has_min_stake(who: &Self::AccountId) -> bool;
+
+	/// Record that a provider did what it committed to.
+	fn record_success(who: &Self::AccountId);
+
+	/// Record that a provider failed, slashing its stake if it failed too many times in a row.
+	fn record_failure(who: &Self::AccountId, failure: Failure);
+
+	/// Endow a provider with the balance to bond the minimum stake, and bond it.
+	#[cfg(feature = "runtime-benchmarks")]
+	fn bond_min_stake(who: &Self::AccountId);
+}
+
+impl<T: Config> ReputationInterface for Pallet<T> {
+	type AccountId = T::AccountId;
+
+	fn score(who: &Self::AccountId) -> Perbill {
+		Self::reputation(who).score
+	}
+
+	fn has_min_stake(who: &Self::AccountId) -> bool {
+		Self::stake(who) >= T::MinStake::get()
+	}
+
+	fn record_success(who: &Self::AccountId) {
+		let mut reputation = Self::reputation(who);
+		reputation.score = reputation.score.saturating_add(T::SuccessReward::get());
+		reputation.failures = 0;
+		Reputations::<T>::insert(who, reputation);
+
+		// Emit event.
+		Self::deposit_event(Event::Rewarded { who: who.clone(), score: reputation.score });
+	}
+
+	fn record_failure(who: &Self::AccountId, failure: Failure) {
+		let penalty = match failure {
+			Failure::MissedDeadline => T::MissedDeadlinePenalty::get(),
+			Failure::FailedChallenge => T::FailedChallengePenalty::get(),
+		};
+
+		let mut reputation = Self::reputation(who);
+		reputation.score = reputation.score.saturating_sub(penalty);
+		reputation.failures = reputation.failures.saturating_add(1);
+
+		// Emit event.
+		Self::deposit_event(Event::Penalized {
+			who: who.clone(),
+			failure,
+			score: reputation.score,
+		});
+
+		// Slash the provider if it failed too many times in a row.
+		if reputation.failures >= T::MaxFailures::get() {
+			reputation.failures = 0;
+			Self::slash(who);
+		}
+
+		Reputations::<T>::insert(who, reputation);
+	}
+
+	#[cfg(feature = "runtime-benchmarks")]
+	fn bond_min_stake(who: &Self::AccountId) {
+		let _ = T::Currency::deposit_creating(
+			who,
+			T::MinStake::get().saturating_add(T::Currency::minimum_balance()),
+		);
+		Self::do_bond(who, T::MinStake::get()).expect("The provider was just endowed; qed");
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// The reputation of `who`, which is the initial one if it has not succeeded nor failed yet.
+	pub fn reputation(who: &T::AccountId) -> ProviderReputation {
+		Reputations::<T>::get(who)
+			.unwrap_or(ProviderReputation { score: T::InitialScore::get(), failures: 0 })
+	}
+
+	/// Reserve `amount` of the balance of `who` and add it to its stake.
+	fn do_bond(who: &T::AccountId, amount: BalanceOf<T>) -> DispatchResult {
+		T::Currency::reserve_named(&T::ReserveId::get(), who, amount)?;
+		Stakes::<T>::mutate(who, |stake| stake.saturating_accrue(amount));
+
+		Ok(())
+	}
+
+	/// Set the unbonding chunks of `who`, clearing them if there are none left.
+	fn set_unbonding(who: &T::AccountId, chunks: UnbondingChunks<T>) {
+		if chunks.is_empty() {
+			Unbonding::<T>::remove(who);
+		} else {
+			Unbonding::<T>::insert(who, chunks);
+		}
+	}
+
+	/// Slash [`Config::SlashFraction`] of the stake of `who`, including the stake it is unbonding.
+	///
+	/// The bonded stake is slashed first, then the unbonding chunks that unlock last.
+	fn slash(who: &T::AccountId) {
+		let mut stake = Stakes::<T>::get(who);
+		let mut chunks = Unbonding::<T>::get(who);
+		let total = chunks.iter().fold(stake, |total, chunk| total.saturating_add(chunk.amount));
+
+		let (imbalance, _) = T::Currency::slash_reserved_named(
+			&T::ReserveId::get(),
+			who,
+			T::SlashFraction::get() * total,
+		);
+		let amount = imbalance.peek();
+
+		// Take the slashed amount out of the stake and the chunks it was slashed from.
+		let mut remaining = amount;
+		for slashed in core::iter::once(&mut stake)
+			.chain(chunks.iter_mut().rev().map(|chunk| &mut chunk.amount))
+		{
+			let taken = remaining.min(*slashed);
+			*slashed = slashed.saturating_sub(taken);
+			remaining = remaining.saturating_sub(taken);
+		}
+		chunks.retain(|chunk| !chunk.amount.is_zero());
+		Stakes::<T>::insert(who, stake);
+		Self::set_unbonding(who, chunks);
+		T::OnSlash::on_unbalanced(imbalance);
+
+		// Emit event.
+		Self::deposit_event(Event::Slashed { who: who.clone(), amount });
+	}
+}
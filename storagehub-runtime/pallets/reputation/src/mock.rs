@@ -0,0 +1,105 @@
+use crate as pallet_reputation;
+use frame_support::{
+	parameter_types,
+	traits::{ConstU128, ConstU16, ConstU32, ConstU64},
+};
+use sp_core::H256;
+use sp_runtime::{
+	traits::{BlakeTwo256, IdentityLookup},
+	BuildStorage, Perbill,
+};
+
+type Block = frame_system::mocking::MockBlock<Test>;
+type Balance = u128;
+
+/// The balance every account starts with.
+pub const INITIAL_BALANCE: Balance = 1_000;
+
+// Configure a mock runtime to test the pallet.
+frame_support::construct_runtime!(
+	pub enum Test
+	{
+		System: frame_system,
+		Balances: pallet_balances,
+		Reputation: pallet_reputation,
+	}
+);
+
+impl frame_system::Config for Test {
+	type BaseCallFilter = frame_support::traits::Everything;
+	type BlockWeights = ();
+	type BlockLength = ();
+	type DbWeight = ();
+	type RuntimeOrigin = RuntimeOrigin;
+	type RuntimeCall = RuntimeCall;
+	type Nonce = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Block = Block;
+	type RuntimeEvent = RuntimeEvent;
+	type BlockHashCount = ConstU64<250>;
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type AccountData = pallet_balances::AccountData<Balance>;
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+	type SS58Prefix = ConstU16<42>;
+	type OnSetCode = ();
+	type MaxConsumers = frame_support::traits::ConstU32<16>;
+}
+
+impl pallet_balances::Config for Test {
+	type Balance = Balance;
+	type DustRemoval = ();
+	type RuntimeEvent = RuntimeEvent;
+	type ExistentialDeposit = ConstU128<1>;
+	type AccountStore = System;
+	type WeightInfo = ();
+	type MaxLocks = ConstU32<10>;
+	type MaxReserves = ConstU32<2>;
+	type ReserveIdentifier = [u8; 8];
+	type RuntimeHoldReason = ();
+	type FreezeIdentifier = u128;
+	type MaxHolds = ConstU32<10>;
+	type MaxFreezes = ConstU32<10>;
+}
+
+parameter_types! {
+	pub const InitialScore: Perbill = Perbill::from_percent(50);
+	pub const SuccessReward: Perbill = Perbill::from_percent(10);
+	pub const MissedDeadlinePenalty: Perbill = Perbill::from_percent(10);
+	pub const FailedChallengePenalty: Perbill = Perbill::from_percent(20);
+	pub const SlashFraction: Perbill = Perbill::from_percent(50);
+	pub const ReserveId: [u8; 8] = *b"reputatn";
+}
+
+impl pallet_reputation::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type Currency = Balances;
+	type OnSlash = ();
+	type InitialScore = InitialScore;
+	type SuccessReward = SuccessReward;
+	type MissedDeadlinePenalty = MissedDeadlinePenalty;
+	type FailedChallengePenalty = FailedChallengePenalty;
+	type MaxFailures = ConstU32<3>;
+	type SlashFraction = SlashFraction;
+	type ReserveId = ReserveId;
+	type MinStake = ConstU128<50>;
+	type UnbondingPeriod = ConstU64<10>;
+	type MaxUnbondingChunks = ConstU32<2>;
+}
+
+// Build genesis storage according to the mock runtime, with account 3 bonding the minimum stake.
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	RuntimeGenesisConfig {
+		balances: BalancesConfig { balances: (1..=3).map(|who| (who, INITIAL_BALANCE)).collect() },
+		reputation: ReputationConfig { stakes: vec![(3, 50)] },
+		..Default::default()
+	}
+	.build_storage()
+	.unwrap()
+	.into()
+}
@@ -0,0 +1,230 @@
+use crate::{
+	mock::*, Error, Event, Failure, ProviderReputation, ReputationInterface, Reputations,
+	UnbondingChunk,
+};
+use frame_support::{
+	assert_noop, assert_ok,
+	traits::{Currency, NamedReservableCurrency, ReservableCurrency},
+};
+use sp_runtime::Perbill;
+
+#[test]
+fn bond_and_unbond_success() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Bond stake.
+		assert_ok!(Reputation::bond(RuntimeOrigin::signed(1), 100));
+		System::assert_last_event(Event::Bonded { who: 1, amount: 100 }.into());
+		assert_eq!(Reputation::stake(1), 100);
+		assert_eq!(Balances::reserved_balance(1), 100);
+
+		// Unbond part of it, which stays reserved until it unlocks.
+		assert_ok!(Reputation::unbond(RuntimeOrigin::signed(1), 40));
+		System::assert_last_event(Event::Unbonded { who: 1, amount: 40, unlocks_at: 11 }.into());
+		assert_eq!(Reputation::stake(1), 60);
+		assert_eq!(
+			Reputation::unbonding(1).to_vec(),
+			vec![UnbondingChunk { amount: 40, unlocks_at: 11 }]
+		);
+		assert_eq!(Balances::reserved_balance(1), 100);
+
+		// Cannot unbond more than what is left.
+		assert_noop!(
+			Reputation::unbond(RuntimeOrigin::signed(1), 61),
+			Error::<Test>::InsufficientStake
+		);
+
+		// Nor withdraw it before it unlocks.
+		System::set_block_number(10);
+		assert_noop!(
+			Reputation::withdraw_unbonded(RuntimeOrigin::signed(1)),
+			Error::<Test>::NoUnlockedStake
+		);
+
+		// Once it unlocks, it is unreserved when withdrawn.
+		System::set_block_number(11);
+		assert_ok!(Reputation::withdraw_unbonded(RuntimeOrigin::signed(1)));
+		System::assert_last_event(Event::Withdrawn { who: 1, amount: 40 }.into());
+		assert!(Reputation::unbonding(1).is_empty());
+		assert_eq!(Balances::reserved_balance(1), 60);
+	});
+}
+
+#[test]
+fn unbond_fails_with_too_many_chunks() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		assert_ok!(Reputation::bond(RuntimeOrigin::signed(1), 100));
+		assert_ok!(Reputation::unbond(RuntimeOrigin::signed(1), 10));
+		assert_ok!(Reputation::unbond(RuntimeOrigin::signed(1), 10));
+
+		assert_noop!(
+			Reputation::unbond(RuntimeOrigin::signed(1), 10),
+			Error::<Test>::TooManyUnbondingChunks
+		);
+
+		// Withdrawing unlocked chunks makes room for new ones.
+		System::set_block_number(11);
+		assert_ok!(Reputation::withdraw_unbonded(RuntimeOrigin::signed(1)));
+		assert_ok!(Reputation::unbond(RuntimeOrigin::signed(1), 10));
+	});
+}
+
+#[test]
+fn min_stake_is_bonded_from_genesis() {
+	new_test_ext().execute_with(|| {
+		// Check that account 3 bonded the minimum stake at genesis.
+		assert_eq!(Reputation::stake(3), 50);
+		assert_eq!(Balances::reserved_balance_named(&ReserveId::get(), &3), 50);
+		assert!(Reputation::has_min_stake(&3));
+
+		// Others have to bond it.
+		assert!(!Reputation::has_min_stake(&1));
+		assert_ok!(Reputation::bond(RuntimeOrigin::signed(1), 49));
+		assert!(!Reputation::has_min_stake(&1));
+		assert_ok!(Reputation::bond(RuntimeOrigin::signed(1), 1));
+		assert!(Reputation::has_min_stake(&1));
+
+		// Unbonding below it loses it right away.
+		assert_ok!(Reputation::unbond(RuntimeOrigin::signed(1), 1));
+		assert!(!Reputation::has_min_stake(&1));
+	});
+}
+
+#[test]
+fn bond_fails_without_enough_balance() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		assert_noop!(
+			Reputation::bond(RuntimeOrigin::signed(1), INITIAL_BALANCE + 1),
+			pallet_balances::Error::<Test>::InsufficientBalance
+		);
+	});
+}
+
+#[test]
+fn score_follows_successes_and_failures() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		// Providers start with the initial score.
+		assert_eq!(Reputation::score(&1), Perbill::from_percent(50));
+
+		// Successes raise it.
+		Reputation::record_success(&1);
+		System::assert_last_event(
+			Event::Rewarded { who: 1, score: Perbill::from_percent(60) }.into(),
+		);
+
+		// Failures lower it, depending on how they failed.
+		Reputation::record_failure(&1, Failure::MissedDeadline);
+		System::assert_last_event(
+			Event::Penalized {
+				who: 1,
+				failure: Failure::MissedDeadline,
+				score: Perbill::from_percent(50),
+			}
+			.into(),
+		);
+		Reputation::record_failure(&1, Failure::FailedChallenge);
+		assert_eq!(
+			Reputations::<Test>::get(1),
+			Some(ProviderReputation { score: Perbill::from_percent(30), failures: 2 })
+		);
+
+		// The score saturates at both ends.
+		for _ in 0..10 {
+			Reputation::record_success(&1);
+		}
+		assert_eq!(Reputation::score(&1), Perbill::one());
+		for _ in 0..10 {
+			Reputation::record_failure(&2, Failure::FailedChallenge);
+		}
+		assert_eq!(Reputation::score(&2), Perbill::zero());
+	});
+}
+
+#[test]
+fn repeated_failures_slash_stake() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		assert_ok!(Reputation::bond(RuntimeOrigin::signed(1), 100));
+
+		// A success resets the failures in a row.
+		Reputation::record_failure(&1, Failure::MissedDeadline);
+		Reputation::record_failure(&1, Failure::MissedDeadline);
+		Reputation::record_success(&1);
+		Reputation::record_failure(&1, Failure::MissedDeadline);
+		Reputation::record_failure(&1, Failure::MissedDeadline);
+		assert_eq!(Reputation::stake(1), 100);
+
+		// The third failure in a row slashes half of the stake.
+		Reputation::record_failure(&1, Failure::FailedChallenge);
+		System::assert_last_event(Event::Slashed { who: 1, amount: 50 }.into());
+		assert_eq!(Reputation::stake(1), 50);
+		assert_eq!(Balances::reserved_balance(1), 50);
+		assert_eq!(Balances::total_balance(&1), INITIAL_BALANCE - 50);
+		assert_eq!(Reputations::<Test>::get(1).map(|reputation| reputation.failures), Some(0));
+
+		// Only the stake is slashed, not the balance reserved by other pallets.
+		assert_ok!(Balances::reserve(&1, 500));
+		for _ in 0..3 {
+			Reputation::record_failure(&1, Failure::MissedDeadline);
+		}
+		System::assert_last_event(Event::Slashed { who: 1, amount: 25 }.into());
+		assert_eq!(Reputation::stake(1), 25);
+		assert_eq!(Balances::reserved_balance_named(&ReserveId::get(), &1), 25);
+		assert_eq!(Balances::reserved_balance(1), 525);
+
+		// Providers without stake are slashed nothing.
+		for _ in 0..3 {
+			Reputation::record_failure(&2, Failure::MissedDeadline);
+		}
+		System::assert_last_event(Event::Slashed { who: 2, amount: 0 }.into());
+	});
+}
+
+#[test]
+fn unbonding_stake_is_slashed() {
+	new_test_ext().execute_with(|| {
+		// Go past genesis block so events get deposited
+		System::set_block_number(1);
+
+		assert_ok!(Reputation::bond(RuntimeOrigin::signed(1), 100));
+		assert_ok!(Reputation::unbond(RuntimeOrigin::signed(1), 30));
+		System::set_block_number(2);
+		assert_ok!(Reputation::unbond(RuntimeOrigin::signed(1), 60));
+
+		// Half of the whole stake is slashed, the bonded stake first, then the chunk that
+		// unlocks last.
+		for _ in 0..3 {
+			Reputation::record_failure(&1, Failure::MissedDeadline);
+		}
+		System::assert_last_event(Event::Slashed { who: 1, amount: 50 }.into());
+		assert_eq!(Reputation::stake(1), 0);
+		assert_eq!(
+			Reputation::unbonding(1).to_vec(),
+			vec![
+				UnbondingChunk { amount: 30, unlocks_at: 11 },
+				UnbondingChunk { amount: 20, unlocks_at: 12 }
+			]
+		);
+		assert_eq!(Balances::reserved_balance(1), 50);
+
+		// Only what is left is withdrawn.
+		System::set_block_number(12);
+		assert_ok!(Reputation::withdraw_unbonded(RuntimeOrigin::signed(1)));
+		System::assert_last_event(Event::Withdrawn { who: 1, amount: 50 }.into());
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(Balances::total_balance(&1), INITIAL_BALANCE - 50);
+	});
+}
@@ -6,6 +6,7 @@
 #[cfg(feature = "std")]
 include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 
+use codec::{Decode, Encode, MaxEncodedLen};
 use pallet_grandpa::AuthorityId as GrandpaId;
 use sp_api::impl_runtime_apis;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
@@ -13,10 +14,11 @@ use sp_core::{crypto::KeyTypeId, OpaqueMetadata};
 use sp_runtime::{
 	create_runtime_str, generic, impl_opaque_keys,
 	traits::{
-		AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount, NumberFor, One, Verify,
+		AccountIdConversion, AccountIdLookup, BlakeTwo256, Block as BlockT,
+		Extrinsic as ExtrinsicT, IdentifyAccount, NumberFor, One, Verify,
 	},
 	transaction_validity::{TransactionSource, TransactionValidity},
-	ApplyExtrinsicResult, MultiSignature,
+	ApplyExtrinsicResult, MultiSignature, RuntimeDebug, SaturatedConversion,
 };
 use sp_std::prelude::*;
 #[cfg(feature = "std")]
@@ -27,8 +29,8 @@ use sp_version::RuntimeVersion;
 pub use frame_support::{
 	construct_runtime, parameter_types,
 	traits::{
-		ConstBool, ConstU128, ConstU32, ConstU64, ConstU8, KeyOwnerProofSystem, Randomness,
-		StorageInfo,
+		ConstBool, ConstU128, ConstU32, ConstU64, ConstU8, EitherOfDiverse, InstanceFilter,
+		KeyOwnerProofSystem, Randomness, StorageInfo,
 	},
 	weights::{
 		constants::{
@@ -36,9 +38,10 @@ pub use frame_support::{
 		},
 		IdentityFee, Weight,
 	},
-	StorageValue,
+	PalletId, StorageValue,
 };
 pub use frame_system::Call as SystemCall;
+use frame_system::EnsureRoot;
 pub use pallet_balances::Call as BalancesCall;
 pub use pallet_timestamp::Call as TimestampCall;
 use pallet_transaction_payment::{ConstFeeMultiplier, CurrencyAdapter, Multiplier};
@@ -49,6 +52,9 @@ pub use sp_runtime::{Perbill, Permill};
 /// Import the template pallet.
 pub use pallet_template;
 
+#[cfg(feature = "xcm")]
+pub mod xcm_config;
+
 /// An index to a block.
 pub type BlockNumber = u32;
 
@@ -104,10 +110,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	//   `spec_version`, and `authoring_version` are the same between Wasm and native.
 	// This value is set to 100 to notify Polkadot-JS App (https://polkadot.js.org/apps) to use
 	//   the compatible custom types.
-	spec_version: 100,
+	spec_version: 101,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
-	transaction_version: 1,
+	transaction_version: 2,
 	state_version: 1,
 };
 
@@ -233,7 +239,7 @@ pub const EXISTENTIAL_DEPOSIT: u128 = 500;
 
 impl pallet_balances::Config for Runtime {
 	type MaxLocks = ConstU32<50>;
-	type MaxReserves = ();
+	type MaxReserves = ConstU32<50>;
 	type ReserveIdentifier = [u8; 8];
 	/// The type for recording an account's balance.
 	type Balance = Balance;
@@ -274,22 +280,267 @@ impl pallet_template::Config for Runtime {
 	type WeightInfo = pallet_template::weights::SubstrateWeight<Runtime>;
 }
 
+parameter_types! {
+	pub const ProviderCouncilMotionDuration: BlockNumber = 5 * MINUTES;
+	pub const ProviderCouncilMaxProposals: u32 = 100;
+	pub const ProviderCouncilMaxMembers: u32 = 100;
+	pub ProviderCouncilMaxProposalWeight: Weight = Perbill::from_percent(50) * BlockWeights::get().max_block;
+}
+
+/// The council that approves new Storage Providers.
+type ProviderCouncilInstance = pallet_collective::Instance1;
+impl pallet_collective::Config<ProviderCouncilInstance> for Runtime {
+	type RuntimeOrigin = RuntimeOrigin;
+	type Proposal = RuntimeCall;
+	type RuntimeEvent = RuntimeEvent;
+	type MotionDuration = ProviderCouncilMotionDuration;
+	type MaxProposals = ProviderCouncilMaxProposals;
+	type MaxMembers = ProviderCouncilMaxMembers;
+	type DefaultVote = pallet_collective::PrimeDefaultVote;
+	type WeightInfo = pallet_collective::weights::SubstrateWeight<Runtime>;
+	/// Members are managed through `ProviderCouncilMembership`.
+	type SetMembersOrigin = EnsureRoot<AccountId>;
+	type MaxProposalWeight = ProviderCouncilMaxProposalWeight;
+}
+
+/// Manages the members of the provider council.
+type ProviderCouncilMembershipInstance = pallet_membership::Instance1;
+impl pallet_membership::Config<ProviderCouncilMembershipInstance> for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type AddOrigin = EnsureRoot<AccountId>;
+	type RemoveOrigin = EnsureRoot<AccountId>;
+	type SwapOrigin = EnsureRoot<AccountId>;
+	type ResetOrigin = EnsureRoot<AccountId>;
+	type PrimeOrigin = EnsureRoot<AccountId>;
+	type MembershipInitialized = ProviderCouncil;
+	type MembershipChanged = ProviderCouncil;
+	type MaxMembers = ProviderCouncilMaxMembers;
+	type WeightInfo = pallet_membership::weights::SubstrateWeight<Runtime>;
+}
+
+/// Root or at least half of the provider council.
+type EnsureRootOrHalfProviderCouncil = EitherOfDiverse<
+	EnsureRoot<AccountId>,
+	pallet_collective::EnsureProportionAtLeast<AccountId, ProviderCouncilInstance, 1, 2>,
+>;
+
+parameter_types! {
+	/// Reserved from candidates while their application to be a provider is pending.
+	pub const ProviderApplicationDeposit: Balance = 1_000 * EXISTENTIAL_DEPOSIT;
+}
+
 impl pallet_identity::Config for Runtime {
 	type RuntimeEvent = RuntimeEvent;
 	type MaxUsers = ConstU128<1_000u128>;
+	type RegistrationOrigin = EnsureRootOrHalfProviderCouncil;
+	type RemovalOrigin = EnsureRootOrHalfProviderCouncil;
+	type OnIdentityChange = PalletFileSystem;
+	type Currency = Balances;
+	type ApplicationDeposit = ProviderApplicationDeposit;
+}
+
+/// MSPs are registered in their own instance of the identity pallet, approved by the same council.
+type MspIdentityInstance = pallet_identity::Instance1;
+impl pallet_identity::Config<MspIdentityInstance> for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type MaxUsers = ConstU128<1_000u128>;
+	type RegistrationOrigin = EnsureRootOrHalfProviderCouncil;
+	type RemovalOrigin = EnsureRootOrHalfProviderCouncil;
+	type OnIdentityChange = ();
+	type Currency = Balances;
+	type ApplicationDeposit = ProviderApplicationDeposit;
+}
+
+parameter_types! {
+	pub const RetrievalReserveId: [u8; 8] = *b"retrievl";
 }
 
 impl pallet_file_system::Config for Runtime {
 	type RuntimeEvent = RuntimeEvent;
-	type WeightInfo = ();
+	// Estimated, not benchmarked: switch to the generated `SubstrateWeight` once the benchmarks
+	// have been run with `.maintain/frame-weight-template.hbs`.
+	type WeightInfo = pallet_file_system::weights::EstimatedWeight<Runtime>;
+	type AuthorityId = pallet_file_system::crypto::BspAuthId;
 	type BspsRegistry = PalletIdentity;
+	type MspsRegistry = PalletMspIdentity;
 	type Fingerprint = Hash;
 	type StorageCount = u128;
 	type AssignmentThreshold = u128;
 	type MaxBsps = ConstU32<5u32>;
+	type MaxBatchSize = ConstU32<32u32>;
+	type MaxFileSize = ConstU128<{ 1024 * 1024 * 1024 }>;
+	type DefaultMaxStoredBytes = ConstU128<{ 10 * 1024 * 1024 * 1024 }>;
+	type DefaultMaxStoredFiles = ConstU32<1_000u32>;
+	type QuotaOrigin = EnsureRootOrHalfProviderCouncil;
+	type MaxFileVersions = ConstU32<10u32>;
 	type MaxFilePathSize = ConstU32<512u32>;
 	type MaxMultiAddressSize = ConstU32<512u32>;
+	type MaxPeerIdSize = ConstU32<64u32>;
+	type MaxOffchainVolunteers = ConstU32<100u32>;
 	type MinBspsAssignmentThreshold = ConstU128<{ u128::MAX }>;
+	type FileSystemHooks = ();
+	type Reputation = PalletReputation;
+	type ChallengeOrigin = EnsureRootOrHalfProviderCouncil;
+	type UpdateConfirmationPeriod = ConstU32<{ 6 * HOURS }>;
+	type Currency = Balances;
+	type RetrievalTimeout = ConstU32<{ HOURS }>;
+	type RetrievalDisputePeriod = ConstU32<{ HOURS }>;
+	type RetrievalReserveId = RetrievalReserveId;
+}
+
+impl frame_system::offchain::SigningTypes for Runtime {
+	type Public = <Signature as Verify>::Signer;
+	type Signature = Signature;
+}
+
+impl<C> frame_system::offchain::SendTransactionTypes<C> for Runtime
+where
+	RuntimeCall: From<C>,
+{
+	type Extrinsic = UncheckedExtrinsic;
+	type OverarchingCall = RuntimeCall;
+}
+
+impl<C> frame_system::offchain::CreateSignedTransaction<C> for Runtime
+where
+	RuntimeCall: From<C>,
+{
+	fn create_transaction<S: frame_system::offchain::AppCrypto<Self::Public, Self::Signature>>(
+		call: RuntimeCall,
+		public: <Signature as Verify>::Signer,
+		account: AccountId,
+		nonce: Nonce,
+	) -> Option<(RuntimeCall, <UncheckedExtrinsic as ExtrinsicT>::SignaturePayload)> {
+		// Transactions of the offchain worker are valid for as long as block hashes are kept.
+		let period =
+			BlockHashCount::get().checked_next_power_of_two().map(|c| c / 2).unwrap_or(2) as u64;
+		let current_block = System::block_number().saturated_into::<u64>().saturating_sub(1);
+		let extra: SignedExtra = (
+			frame_system::CheckNonZeroSender::<Runtime>::new(),
+			frame_system::CheckSpecVersion::<Runtime>::new(),
+			frame_system::CheckTxVersion::<Runtime>::new(),
+			frame_system::CheckGenesis::<Runtime>::new(),
+			frame_system::CheckEra::<Runtime>::from(generic::Era::mortal(period, current_block)),
+			frame_system::CheckNonce::<Runtime>::from(nonce),
+			frame_system::CheckWeight::<Runtime>::new(),
+			pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(0),
+			pallet_file_system::CheckBspVolunteer::<Runtime>::new(),
+		);
+		let raw_payload = SignedPayload::new(call, extra).ok()?;
+		let signature = raw_payload.using_encoded(|payload| S::sign(payload, public))?;
+		let (call, extra, _) = raw_payload.deconstruct();
+
+		Some((call, (account.into(), signature, extra)))
+	}
+}
+
+parameter_types! {
+	pub const InitialReputationScore: Perbill = Perbill::one();
+	pub const ReputationSuccessReward: Perbill = Perbill::from_percent(1);
+	pub const MissedDeadlinePenalty: Perbill = Perbill::from_percent(10);
+	pub const FailedChallengePenalty: Perbill = Perbill::from_percent(20);
+	pub const ProviderSlashFraction: Perbill = Perbill::from_percent(10);
+	pub const ReputationReserveId: [u8; 8] = *b"reputatn";
+	pub const MinProviderStake: Balance = 1_000 * EXISTENTIAL_DEPOSIT;
+}
+
+/// Providers start fully trusted, and the stake slashed from them is burnt. Unbonded stake can
+/// still be slashed for a week.
+impl pallet_reputation::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type Currency = Balances;
+	type OnSlash = ();
+	type InitialScore = InitialReputationScore;
+	type SuccessReward = ReputationSuccessReward;
+	type MissedDeadlinePenalty = MissedDeadlinePenalty;
+	type FailedChallengePenalty = FailedChallengePenalty;
+	type MaxFailures = ConstU32<3>;
+	type SlashFraction = ProviderSlashFraction;
+	type ReserveId = ReputationReserveId;
+	type MinStake = MinProviderStake;
+	type UnbondingPeriod = ConstU32<{ 7 * DAYS }>;
+	type MaxUnbondingChunks = ConstU32<16>;
+}
+
+/// The kinds of calls a proxy is allowed to make on behalf of its delegator.
+#[derive(
+	Copy,
+	Clone,
+	Eq,
+	PartialEq,
+	Ord,
+	PartialOrd,
+	Encode,
+	Decode,
+	RuntimeDebug,
+	MaxEncodedLen,
+	scale_info::TypeInfo,
+)]
+pub enum ProxyType {
+	/// Any call.
+	Any,
+	/// Only the calls a Storage Provider makes while operating, so that a hot key on the
+	/// provider's server can operate on behalf of a cold account.
+	StorageProvider,
+}
+
+impl Default for ProxyType {
+	fn default() -> Self {
+		Self::Any
+	}
+}
+
+impl InstanceFilter<RuntimeCall> for ProxyType {
+	fn filter(&self, c: &RuntimeCall) -> bool {
+		match self {
+			ProxyType::Any => true,
+			ProxyType::StorageProvider => matches!(
+				c,
+				RuntimeCall::PalletFileSystem(
+					pallet_file_system::Call::bsp_volunteer { .. } |
+						pallet_file_system::Call::bsp_confirm_update { .. } |
+						pallet_file_system::Call::bsp_confirm_delivery { .. } |
+						pallet_file_system::Call::confirm_retrieval { .. } |
+						pallet_file_system::Call::msp_accept_storage_request { .. } |
+						pallet_file_system::Call::msp_reject_storage_request { .. } |
+						pallet_file_system::Call::register_peer_id { .. } |
+						pallet_file_system::Call::unregister_peer_id { .. }
+				)
+			),
+		}
+	}
+
+	fn is_superset(&self, o: &Self) -> bool {
+		match (self, o) {
+			(x, y) if x == y => true,
+			(ProxyType::Any, _) => true,
+			_ => false,
+		}
+	}
+}
+
+parameter_types! {
+	// One storage item: key size 32, value size 8.
+	pub const ProxyDepositBase: Balance = 40 * EXISTENTIAL_DEPOSIT;
+	// Additional storage item size of 33 bytes.
+	pub const ProxyDepositFactor: Balance = 33 * EXISTENTIAL_DEPOSIT;
+	pub const AnnouncementDepositBase: Balance = 40 * EXISTENTIAL_DEPOSIT;
+	pub const AnnouncementDepositFactor: Balance = 66 * EXISTENTIAL_DEPOSIT;
+}
+
+impl pallet_proxy::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type RuntimeCall = RuntimeCall;
+	type Currency = Balances;
+	type ProxyType = ProxyType;
+	type ProxyDepositBase = ProxyDepositBase;
+	type ProxyDepositFactor = ProxyDepositFactor;
+	type MaxProxies = ConstU32<32>;
+	type WeightInfo = pallet_proxy::weights::SubstrateWeight<Runtime>;
+	type MaxPending = ConstU32<32>;
+	type CallHasher = BlakeTwo256;
+	type AnnouncementDepositBase = AnnouncementDepositBase;
+	type AnnouncementDepositFactor = AnnouncementDepositFactor;
 }
 
 // Create the runtime by composing the FRAME pallets that were previously configured.
@@ -302,8 +553,16 @@ construct_runtime!(
 		Balances: pallet_balances,
 		TransactionPayment: pallet_transaction_payment,
 		Sudo: pallet_sudo,
+		ProviderCouncil: pallet_collective::<Instance1>,
+		ProviderCouncilMembership: pallet_membership::<Instance1>,
 		PalletIdentity: pallet_identity,
+		PalletMspIdentity: pallet_identity::<Instance1>,
 		PalletFileSystem: pallet_file_system,
+		PalletReputation: pallet_reputation,
+		Proxy: pallet_proxy,
+		// Only in builds with the experimental `xcm` feature, see `xcm_config`.
+		#[cfg(feature = "xcm")]
+		PolkadotXcm: pallet_xcm,
 		// Include the custom logic from the pallet-template in the runtime.
 		TemplateModule: pallet_template,
 	}
@@ -325,6 +584,7 @@ pub type SignedExtra = (
 	frame_system::CheckNonce<Runtime>,
 	frame_system::CheckWeight<Runtime>,
 	pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+	pallet_file_system::CheckBspVolunteer<Runtime>,
 );
 
 /// Unchecked extrinsic type as expected by this runtime.
@@ -332,6 +592,20 @@ pub type UncheckedExtrinsic =
 	generic::UncheckedExtrinsic<Address, RuntimeCall, Signature, SignedExtra>;
 /// The payload being signed in transactions.
 pub type SignedPayload = generic::SignedPayload<RuntimeCall, SignedExtra>;
+parameter_types! {
+	/// Owner of the files stored before the File System Pallet recorded who requested them. A
+	/// keyless account, so that nobody can update or remove them.
+	pub LegacyFilesOwner: AccountId = PalletId(*b"sh/files").into_account_truncating();
+}
+
+/// Storage migrations to run on runtime upgrade. Each of them does nothing if the storage of its
+/// pallet is not at the version it migrates from.
+pub type Migrations = (
+	pallet_identity::migrations::v1::MigrateToV1<Runtime>,
+	pallet_identity::migrations::v1::MigrateToV1<Runtime, MspIdentityInstance>,
+	pallet_file_system::migrations::v1::MigrateToV1<Runtime, LegacyFilesOwner>,
+);
+
 /// Executive: handles dispatch to the various modules.
 pub type Executive = frame_executive::Executive<
 	Runtime,
@@ -339,6 +613,7 @@ pub type Executive = frame_executive::Executive<
 	frame_system::ChainContext<Runtime>,
 	Runtime,
 	AllPalletsWithSystem,
+	Migrations,
 >;
 
 #[cfg(feature = "runtime-benchmarks")]
@@ -353,6 +628,10 @@ mod benches {
 		[pallet_balances, Balances]
 		[pallet_timestamp, Timestamp]
 		[pallet_sudo, Sudo]
+		[pallet_collective, ProviderCouncil]
+		[pallet_membership, ProviderCouncilMembership]
+		[pallet_proxy, Proxy]
+		[pallet_file_system, PalletFileSystem]
 		[pallet_template, TemplateModule]
 	);
 }
@@ -0,0 +1,168 @@
+//! XCM configuration of the Storage Hub runtime.
+//!
+//! Sibling parachains can `Transact` into the File System Pallet on behalf of their users. Each
+//! remote user gets its own sovereign sub-account on Storage Hub, derived by hashing its location,
+//! which pays for execution and owns the files it requests. Senders that want to know how their
+//! request went append `ReportTransactStatus` to their message and get the dispatch result back.
+//!
+//! Storage Hub runs as a solo chain for now, so there is no transport to route messages through:
+//! [`XcmRouter`] is empty, and [`XcmConfig`] takes its router as a parameter so that the XCM test
+//! network can plug in its own. Neither are the XCM weights benchmarked. This module, along with
+//! `pallet_xcm`, is therefore only part of the runtime when built with the experimental `xcm`
+//! feature, which the integration tests enable. Production builds leave it out until Storage Hub
+//! is onboarded as a parachain, with an XCMP queue and benchmarked weights.
+
+use super::{
+	AccountId, AllPalletsWithSystem, Balance, Balances, PolkadotXcm, Runtime, RuntimeCall,
+	RuntimeEvent, RuntimeOrigin,
+};
+use frame_support::{
+	parameter_types,
+	traits::{ConstU32, Contains, Nothing},
+	weights::IdentityFee,
+};
+use frame_system::EnsureRoot;
+use polkadot_parachain::primitives::{Id as ParaId, Sibling};
+use sp_std::marker::PhantomData;
+use xcm::latest::prelude::*;
+use xcm_builder::{
+	AllowKnownQueryResponses, AllowSubscriptionsFrom, AllowTopLevelPaidExecutionFrom,
+	CurrencyAdapter, DescribeAllTerminal, DescribeFamily, EnsureXcmOrigin, FixedWeightBounds,
+	HashedDescription, IsConcrete, SiblingParachainConvertsVia, SignedToAccountId32,
+	SovereignSignedViaLocation, TakeWeightCredit, UsingComponents, WithComputedOrigin,
+};
+use xcm_executor::XcmExecutor;
+
+parameter_types! {
+	/// The parachain id Storage Hub is meant to be registered with.
+	pub const ParachainId: ParaId = ParaId::new(2000);
+	pub const RelayNetwork: Option<NetworkId> = None;
+	pub UniversalLocation: InteriorMultiLocation = X1(Parachain(ParachainId::get().into()));
+	/// The location of the native token, as seen from Storage Hub.
+	pub const HereLocation: MultiLocation = MultiLocation::here();
+	/// The weight of a single XCM instruction, other than the call of a `Transact`.
+	pub const UnitWeightCost: Weight = Weight::from_parts(1_000_000_000, 64 * 1024);
+	pub const MaxInstructions: u32 = 100;
+	pub const MaxAssetsIntoHolding: u32 = 64;
+	pub ReachableDest: Option<MultiLocation> = None;
+}
+
+/// Converts a location into the account that acts on its behalf.
+///
+/// Sibling parachains get their usual sovereign account, while locations inside them, such as the
+/// accounts of their users, get a sub-account derived from the hash of their description.
+pub type LocationToAccountId = (
+	SiblingParachainConvertsVia<Sibling, AccountId>,
+	HashedDescription<AccountId, DescribeFamily<DescribeAllTerminal>>,
+);
+
+/// Pays for execution, and refunds what is left, in the native token.
+pub type LocalAssetTransactor =
+	CurrencyAdapter<Balances, IsConcrete<HereLocation>, LocationToAccountId, AccountId, ()>;
+
+/// A `Transact` with the `SovereignAccount` origin kind dispatches as signed by the account of
+/// its origin location.
+pub type XcmOriginToTransactDispatchOrigin =
+	SovereignSignedViaLocation<LocationToAccountId, RuntimeOrigin>;
+
+/// Locations inside a sibling parachain, including the parachain itself.
+pub struct SiblingLocations;
+impl Contains<MultiLocation> for SiblingLocations {
+	fn contains(location: &MultiLocation) -> bool {
+		location.parents == 1 && matches!(location.interior.first(), Some(Parachain(_)))
+	}
+}
+
+/// Messages from siblings must pay for their execution, which lets their users `DescendOrigin`
+/// into their own sub-account first.
+pub type Barrier = (
+	TakeWeightCredit,
+	WithComputedOrigin<
+		(
+			AllowTopLevelPaidExecutionFrom<SiblingLocations>,
+			AllowKnownQueryResponses<PolkadotXcm>,
+			AllowSubscriptionsFrom<SiblingLocations>,
+		),
+		UniversalLocation,
+		ConstU32<8>,
+	>,
+);
+
+/// The calls that can be made through `Transact`: storage requests only.
+pub struct SafeCallFilter;
+impl Contains<RuntimeCall> for SafeCallFilter {
+	fn contains(call: &RuntimeCall) -> bool {
+		matches!(
+			call,
+			RuntimeCall::PalletFileSystem(
+				pallet_file_system::Call::request_storage { .. } |
+					pallet_file_system::Call::request_storage_batch { .. }
+			)
+		)
+	}
+}
+
+/// Routes outgoing messages. Storage Hub is not connected to any other chain yet, so sending
+/// fails with `Unroutable`.
+pub type XcmRouter = ();
+
+/// The XCM executor configuration, routing outgoing messages through `Router`.
+pub struct XcmConfig<Router = XcmRouter>(PhantomData<Router>);
+impl<Router: SendXcm> xcm_executor::Config for XcmConfig<Router> {
+	type RuntimeCall = RuntimeCall;
+	type XcmSender = Router;
+	type AssetTransactor = LocalAssetTransactor;
+	type OriginConverter = XcmOriginToTransactDispatchOrigin;
+	type IsReserve = ();
+	type IsTeleporter = ();
+	type UniversalLocation = UniversalLocation;
+	type Barrier = Barrier;
+	type Weigher = FixedWeightBounds<UnitWeightCost, RuntimeCall, MaxInstructions>;
+	type Trader = UsingComponents<IdentityFee<Balance>, HereLocation, AccountId, Balances, ()>;
+	type ResponseHandler = PolkadotXcm;
+	type AssetTrap = PolkadotXcm;
+	type AssetLocker = ();
+	type AssetExchanger = ();
+	type AssetClaims = PolkadotXcm;
+	type SubscriptionService = PolkadotXcm;
+	type PalletInstancesInfo = AllPalletsWithSystem;
+	type MaxAssetsIntoHolding = MaxAssetsIntoHolding;
+	type FeeManager = ();
+	type MessageExporter = ();
+	type UniversalAliases = Nothing;
+	type CallDispatcher = RuntimeCall;
+	type SafeCallFilter = SafeCallFilter;
+	type Aliasers = Nothing;
+}
+
+/// Converts a local signed origin into the location of its account.
+pub type LocalOriginToLocation = SignedToAccountId32<RuntimeOrigin, AccountId, RelayNetwork>;
+
+impl pallet_xcm::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type SendXcmOrigin = EnsureXcmOrigin<RuntimeOrigin, LocalOriginToLocation>;
+	type XcmRouter = XcmRouter;
+	type ExecuteXcmOrigin = EnsureXcmOrigin<RuntimeOrigin, LocalOriginToLocation>;
+	type XcmExecuteFilter = Nothing;
+	type XcmExecutor = XcmExecutor<XcmConfig>;
+	type XcmTeleportFilter = Nothing;
+	type XcmReserveTransferFilter = Nothing;
+	type Weigher = FixedWeightBounds<UnitWeightCost, RuntimeCall, MaxInstructions>;
+	type UniversalLocation = UniversalLocation;
+	type RuntimeOrigin = RuntimeOrigin;
+	type RuntimeCall = RuntimeCall;
+	const VERSION_DISCOVERY_QUEUE_SIZE: u32 = 100;
+	type AdvertisedXcmVersion = pallet_xcm::CurrentXcmVersion;
+	type Currency = Balances;
+	type CurrencyMatcher = IsConcrete<HereLocation>;
+	type TrustedLockers = ();
+	type SovereignAccountOf = LocationToAccountId;
+	type MaxLockers = ConstU32<8>;
+	type MaxRemoteLockConsumers = ConstU32<0>;
+	type RemoteLockConsumerIdentifier = ();
+	// Zero weights, which is why this pallet is left out of production builds.
+	type WeightInfo = pallet_xcm::TestWeightInfo;
+	#[cfg(feature = "runtime-benchmarks")]
+	type ReachableDest = ReachableDest;
+	type AdminOrigin = EnsureRoot<AccountId>;
+}